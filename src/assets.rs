@@ -24,23 +24,65 @@ pub struct AssetHandle {
     path: PathBuf,
 }
 
+/// Named assets, stored in files relative to the executable's directory.
 pub struct AssetDatabase {
     assets: Vec<AssetHandle>,
 }
 
+impl Default for AssetDatabase {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl AssetDatabase {
-    pub fn get<T: Asset<TOptions>, TOptions>(&self, name: &str) -> Result<T, Error> {
+    /// Creates an empty database.
+    pub fn new() -> Self {
+        Self { assets: Vec::new() }
+    }
+
+    /// Registers an asset file under `name`, replacing any asset with the same name.
+    ///
+    /// `path` is relative to the executable's directory.
+    pub fn add<P: Into<PathBuf>>(&mut self, name: &str, path: P) {
+        let path = path.into();
+
+        match self.assets.iter_mut().find(|handle| handle.name == name) {
+            Some(handle) => handle.path = path,
+            None => self.assets.push(AssetHandle {
+                name: name.to_owned(),
+                path,
+            }),
+        }
+    }
+
+    /// Full path of the file of an asset.
+    pub fn path(&self, name: &str) -> Result<PathBuf, Error> {
         match self.get_handle(name) {
             None => Err(AssetError::NameNotFound(name.to_owned()).into()),
             Some(handle) => {
                 let mut full_path = EXE_PATH.clone();
-                full_path.set_file_name(handle.path);
+                full_path.set_file_name(&handle.path);
 
-                T::load_from_bytes(fs::read(full_path));
+                Ok(full_path)
             }
         }
     }
 
+    /// Reads the contents of an asset file.
+    pub fn read(&self, name: &str) -> Result<Vec<u8>, Error> {
+        Ok(fs::read(self.path(name)?)?)
+    }
+
+    /// Loads an asset.
+    pub fn get<T: Asset<TOptions>, TOptions>(
+        &self,
+        name: &str,
+        options: TOptions,
+    ) -> Result<T, Error> {
+        T::load_from_bytes(&self.read(name)?, options)
+    }
+
     fn get_handle(&self, name: &str) -> Option<&AssetHandle> {
         self.assets.iter().find(|handle| handle.name == name)
     }
@@ -0,0 +1,495 @@
+use super::sprites::{Sprite, SpriteRegion, SpriteSheet, TagDirection};
+use failure::Error;
+use std::collections::HashMap;
+
+/// Maximum number of frames an `Animator` advances in a single update,
+/// in case of very short frames or a very long update.
+const MAX_FRAMES_PER_UPDATE: usize = 256;
+
+/// Errors related to sprite animations.
+#[derive(Debug, Fail)]
+pub enum AnimationError {
+    /// The requested clip was not added to the animator.
+    #[fail(display = "No animation clip named '{}'", _0)]
+    ClipNotFound(String),
+}
+
+/// What an animation does after its last frame.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum PlaybackMode {
+    /// Stop on the last frame.
+    Once,
+    /// Start over from the first frame.
+    Loop,
+    /// Play backwards to the first frame, then forwards again.
+    PingPong,
+}
+
+/// One frame of a `SpriteAnimation`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnimationFrame {
+    /// Region of the sprite sheet displayed.
+    pub region: SpriteRegion,
+    /// Time the frame is displayed for, in seconds.
+    pub duration: f32,
+    /// Events reported when the frame is reached.
+    pub events: Vec<String>,
+}
+
+/// Sequence of sprites from a single sprite sheet, with per-frame durations.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpriteAnimation {
+    frames: Vec<AnimationFrame>,
+    pub mode: PlaybackMode,
+}
+
+impl SpriteAnimation {
+    /// Creates an animation without frames.
+    pub fn new(mode: PlaybackMode) -> Self {
+        Self {
+            frames: Vec::new(),
+            mode,
+        }
+    }
+
+    /// Creates an animation from `count` consecutive cells of a row of the sheet's grid,
+    /// each displayed for `frame_duration` seconds.
+    pub fn from_row(
+        sheet: &SpriteSheet,
+        y: u32,
+        count: u32,
+        frame_duration: f32,
+        mode: PlaybackMode,
+    ) -> Self {
+        let mut animation = Self::new(mode);
+        for x in 0..count {
+            animation.add_frame(&sheet.sprite(x, y), frame_duration);
+        }
+
+        animation
+    }
+
+    /// Creates an animation from a tag of the sheet, with the frame durations of the sheet.
+    ///
+    /// Tags play in a loop, and ping-pong tags use `PlaybackMode::PingPong`.
+    pub fn from_tag(sheet: &SpriteSheet, name: &str) -> Option<Self> {
+        let tag = sheet.tag(name)?;
+
+        let (mode, reverse) = match tag.direction {
+            TagDirection::Forward => (PlaybackMode::Loop, false),
+            TagDirection::Reverse => (PlaybackMode::Loop, true),
+            TagDirection::PingPong => (PlaybackMode::PingPong, false),
+            TagDirection::PingPongReverse => (PlaybackMode::PingPong, true),
+        };
+
+        let mut animation = Self::new(mode);
+        for index in tag.from..=tag.to {
+            let frame = sheet.frames().get(index)?;
+            animation.add_frame(&sheet.named(&frame.name)?, frame.duration);
+        }
+
+        if reverse {
+            animation.frames.reverse();
+        }
+
+        Some(animation)
+    }
+
+    /// Adds a sprite as the last frame, displayed for `duration` seconds.
+    pub fn add_frame(&mut self, sprite: &Sprite, duration: f32) {
+        self.frames.push(AnimationFrame {
+            region: sprite.region,
+            duration,
+            events: Vec::new(),
+        });
+    }
+
+    /// Adds an event reported when the frame at `index` is reached.
+    ///
+    /// Returns false if there is no frame at `index`.
+    pub fn add_event(&mut self, index: usize, name: &str) -> bool {
+        match self.frames.get_mut(index) {
+            None => false,
+            Some(frame) => {
+                frame.events.push(name.to_owned());
+                true
+            }
+        }
+    }
+
+    pub fn frames(&self) -> &[AnimationFrame] {
+        &self.frames
+    }
+
+    pub fn frames_mut(&mut self) -> &mut [AnimationFrame] {
+        &mut self.frames
+    }
+
+    /// Time taken to play every frame once, in seconds.
+    pub fn duration(&self) -> f32 {
+        self.frames.iter().map(|frame| frame.duration).sum()
+    }
+}
+
+/// Something that happened during `Animator::update`.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum AnimationEvent {
+    /// A frame with an event was reached.
+    Frame {
+        clip: String,
+        frame: usize,
+        name: String,
+    },
+    /// A clip played to its end, either stopping or transitioning to another clip.
+    Finished { clip: String },
+}
+
+/// Plays named `SpriteAnimation` clips.
+///
+/// Call `update` every frame with the elapsed time, then draw `sprite`.
+#[derive(Debug, Clone)]
+pub struct Animator {
+    clips: HashMap<String, SpriteAnimation>,
+    transitions: HashMap<String, String>,
+    current: Option<String>,
+    frame: usize,
+    elapsed: f32,
+    backward: bool,
+    finished: bool,
+    frame_entered: bool,
+    /// Playback speed multiplier. Default: 1.
+    pub speed: f32,
+}
+
+impl Default for Animator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Animator {
+    /// Creates an animator without clips.
+    pub fn new() -> Self {
+        Self {
+            clips: HashMap::new(),
+            transitions: HashMap::new(),
+            current: None,
+            frame: 0,
+            elapsed: 0.0,
+            backward: false,
+            finished: false,
+            frame_entered: false,
+            speed: 1.0,
+        }
+    }
+
+    /// Adds or replaces a clip. Replacing the current clip restarts it.
+    pub fn add_clip(&mut self, name: &str, clip: SpriteAnimation) {
+        self.clips.insert(name.to_owned(), clip);
+
+        if self.current.as_ref().map(String::as_str) == Some(name) {
+            self.start(name);
+            self.elapsed = 0.0;
+        }
+    }
+
+    pub fn clip(&self, name: &str) -> Option<&SpriteAnimation> {
+        self.clips.get(name)
+    }
+
+    pub fn clip_mut(&mut self, name: &str) -> Option<&mut SpriteAnimation> {
+        self.clips.get_mut(name)
+    }
+
+    /// Makes the clip `from` continue with the clip `to` when it ends,
+    /// instead of stopping or looping.
+    pub fn add_transition(&mut self, from: &str, to: &str) {
+        self.transitions.insert(from.to_owned(), to.to_owned());
+    }
+
+    pub fn remove_transition(&mut self, from: &str) {
+        self.transitions.remove(from);
+    }
+
+    /// Switches to a clip, from its first frame.
+    ///
+    /// Does nothing if the clip is already playing.
+    pub fn play(&mut self, name: &str) -> Result<(), Error> {
+        if self.current.as_ref().map(String::as_str) == Some(name) && !self.finished {
+            return Ok(());
+        }
+
+        self.restart(name)
+    }
+
+    /// Switches to a clip, from its first frame, even if it is already playing.
+    pub fn restart(&mut self, name: &str) -> Result<(), Error> {
+        if !self.clips.contains_key(name) {
+            return Err(AnimationError::ClipNotFound(name.to_owned()).into());
+        }
+
+        self.start(name);
+        self.elapsed = 0.0;
+        Ok(())
+    }
+
+    /// Stops playback. `sprite` returns `None` until a clip is played again.
+    pub fn stop(&mut self) {
+        self.current = None;
+    }
+
+    /// Name of the clip being played.
+    pub fn current_clip(&self) -> Option<&str> {
+        self.current.as_ref().map(String::as_str)
+    }
+
+    /// Index of the frame displayed in the current clip.
+    pub fn current_frame(&self) -> usize {
+        self.frame
+    }
+
+    /// Returns true if a clip played in `PlaybackMode::Once` reached its end.
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Region of the frame being displayed.
+    pub fn region(&self) -> Option<SpriteRegion> {
+        let clip = self.clips.get(self.current.as_ref()?)?;
+        clip.frames.get(self.frame).map(|frame| frame.region)
+    }
+
+    /// Sprite of the frame being displayed, from the sheet the clips were made from.
+    pub fn sprite<'s>(&self, sheet: &'s SpriteSheet) -> Option<Sprite<'s>> {
+        self.region().map(|region| sheet.sprite_from_region(region))
+    }
+
+    /// Advances the animation by `delta` seconds, and returns what happened in the meantime.
+    pub fn update(&mut self, delta: f32) -> Vec<AnimationEvent> {
+        let mut events = Vec::new();
+
+        let mut current = match self.current.clone() {
+            Some(ref current) if !self.finished => current.clone(),
+            _ => return events,
+        };
+
+        if !self.frame_entered {
+            self.frame_entered = true;
+            self.push_frame_events(&current, &mut events);
+        }
+
+        self.elapsed += delta * self.speed;
+
+        for _ in 0..MAX_FRAMES_PER_UPDATE {
+            let (duration, len, mode) = match self.clips.get(&current) {
+                Some(clip) if !clip.frames.is_empty() => (
+                    clip.frames[self.frame].duration,
+                    clip.frames.len(),
+                    clip.mode,
+                ),
+                _ => break,
+            };
+
+            if self.elapsed < duration {
+                break;
+            }
+
+            self.elapsed -= duration;
+
+            match self.next_frame(len, mode) {
+                Some(frame) => self.frame = frame,
+                None => {
+                    //End of the clip
+                    if let Some(next) = self.transitions.get(&current).cloned() {
+                        events.push(AnimationEvent::Finished { clip: current });
+                        self.start(&next);
+                        current = next;
+                    } else {
+                        match mode {
+                            PlaybackMode::Once => {
+                                self.finished = true;
+                                self.elapsed = 0.0;
+                                events.push(AnimationEvent::Finished { clip: current });
+                                break;
+                            }
+                            PlaybackMode::Loop => self.frame = 0,
+                            PlaybackMode::PingPong => {
+                                self.backward = false;
+                                self.frame = if len > 1 { 1 } else { 0 };
+                            }
+                        }
+                    }
+                }
+            }
+
+            self.frame_entered = true;
+            self.push_frame_events(&current, &mut events);
+        }
+
+        events
+    }
+
+    /// Resets the playback state to the first frame of a clip, keeping the elapsed time.
+    fn start(&mut self, name: &str) {
+        self.current = Some(name.to_owned());
+        self.frame = 0;
+        self.backward = false;
+        self.finished = false;
+        self.frame_entered = false;
+    }
+
+    /// Index of the frame after the current one, or `None` at the end of the clip.
+    fn next_frame(&mut self, len: usize, mode: PlaybackMode) -> Option<usize> {
+        match mode {
+            PlaybackMode::PingPong if self.backward => self.frame.checked_sub(1),
+            PlaybackMode::PingPong if self.frame + 1 >= len => {
+                if len > 1 {
+                    self.backward = true;
+                    Some(self.frame - 1)
+                } else {
+                    None
+                }
+            }
+            _ if self.frame + 1 < len => Some(self.frame + 1),
+            _ => None,
+        }
+    }
+
+    fn push_frame_events(&self, clip_name: &str, events: &mut Vec<AnimationEvent>) {
+        let frame = match self.clips.get(clip_name) {
+            Some(clip) => match clip.frames.get(self.frame) {
+                Some(frame) => frame,
+                None => return,
+            },
+            None => return,
+        };
+
+        for name in &frame.events {
+            events.push(AnimationEvent::Frame {
+                clip: clip_name.to_owned(),
+                frame: self.frame,
+                name: name.clone(),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use graphics::sprites::Rect;
+
+    /// Clip of `count` frames of `duration` seconds, showing regions at x = 0, 1, 2...
+    fn clip(count: u32, duration: f32, mode: PlaybackMode) -> SpriteAnimation {
+        SpriteAnimation {
+            frames: (0..count)
+                .map(|x| AnimationFrame {
+                    region: SpriteRegion::new(Rect::new(x, 0, 1, 1)),
+                    duration,
+                    events: Vec::new(),
+                })
+                .collect(),
+            mode,
+        }
+    }
+
+    /// Animator playing the clip "a".
+    fn animator(clips: Vec<(&str, SpriteAnimation)>) -> Animator {
+        let mut animator = Animator::new();
+        for (name, clip) in clips {
+            animator.add_clip(name, clip);
+        }
+
+        animator.play("a").unwrap();
+        animator
+    }
+
+    fn frame_event(frame: usize, name: &str) -> AnimationEvent {
+        AnimationEvent::Frame {
+            clip: "a".to_owned(),
+            frame,
+            name: name.to_owned(),
+        }
+    }
+
+    #[test]
+    fn ping_pong_plays_backwards_then_forwards() {
+        let mut animator = animator(vec![("a", clip(3, 1.0, PlaybackMode::PingPong))]);
+
+        let mut frames = Vec::new();
+        for _ in 0..6 {
+            animator.update(1.0);
+            frames.push(animator.current_frame());
+        }
+
+        assert_eq!(frames, vec![1, 2, 1, 0, 1, 2]);
+        assert_eq!(animator.region().map(|region| region.rect.x), Some(2));
+    }
+
+    #[test]
+    fn once_finishes_on_the_last_frame() {
+        let mut animator = animator(vec![("a", clip(2, 1.0, PlaybackMode::Once))]);
+
+        assert!(animator.update(1.0).is_empty());
+        assert_eq!(
+            animator.update(1.0),
+            vec![AnimationEvent::Finished {
+                clip: "a".to_owned()
+            }]
+        );
+        assert!(animator.is_finished());
+        assert_eq!(animator.current_frame(), 1);
+
+        assert!(animator.update(10.0).is_empty());
+        assert_eq!(animator.current_frame(), 1);
+    }
+
+    #[test]
+    fn transition_keeps_the_remaining_time() {
+        let mut animator = animator(vec![
+            ("a", clip(2, 1.0, PlaybackMode::Once)),
+            ("b", clip(3, 1.0, PlaybackMode::Loop)),
+        ]);
+        animator.add_transition("a", "b");
+
+        let events = animator.update(3.5);
+
+        assert_eq!(
+            events,
+            vec![AnimationEvent::Finished {
+                clip: "a".to_owned()
+            }]
+        );
+        assert_eq!(animator.current_clip(), Some("b"));
+        assert_eq!(animator.current_frame(), 1);
+        assert!(!animator.is_finished());
+    }
+
+    #[test]
+    fn frame_events_fire_once_per_frame_entry() {
+        let mut looping = clip(2, 1.0, PlaybackMode::Loop);
+        looping.add_event(0, "step");
+        looping.add_event(1, "hit");
+        let mut animator = animator(vec![("a", looping)]);
+
+        assert_eq!(animator.update(0.5), vec![frame_event(0, "step")]);
+        assert!(animator.update(0.25).is_empty());
+        assert_eq!(animator.update(0.25), vec![frame_event(1, "hit")]);
+        assert!(animator.update(0.5).is_empty());
+        assert_eq!(animator.update(0.5), vec![frame_event(0, "step")]);
+    }
+
+    #[test]
+    fn large_updates_are_capped() {
+        let mut looping = clip(2, 0.001, PlaybackMode::Loop);
+        looping.add_event(0, "tick");
+        looping.add_event(1, "tick");
+        let mut animator = animator(vec![("a", looping)]);
+
+        //The first frame is entered, then at most MAX_FRAMES_PER_UPDATE others
+        let events = animator.update(1000.0);
+
+        assert_eq!(events.len(), MAX_FRAMES_PER_UPDATE + 1);
+        assert_eq!(animator.current_frame(), MAX_FRAMES_PER_UPDATE % 2);
+    }
+}
@@ -0,0 +1,483 @@
+use super::{
+    sprites::{Rect, SpriteSheet},
+    textures::{Texture, TextureOptions},
+};
+use assets::Asset;
+use failure::Error;
+use image::{self, GenericImage, RgbaImage};
+use ron;
+use std::{cmp::Reverse, collections::BTreeMap, fs, path::Path, str};
+
+/// Errors related to texture atlases.
+#[derive(Debug, Fail)]
+pub enum AtlasError {
+    /// Images could not fit in an atlas of the maximum size.
+    #[fail(display = "Images do not fit in a {}x{} atlas", _0, _0)]
+    AtlasTooSmall(u32),
+    /// Two images were added with the same name.
+    #[fail(display = "Duplicate atlas image name: {}", _0)]
+    DuplicateName(String),
+}
+
+/// Serialized description of a packed atlas: image file and named regions.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AtlasIndex {
+    /// Path of the atlas image, relative to the index file.
+    pub image: String,
+    pub regions: BTreeMap<String, Rect>,
+}
+
+impl Asset<()> for AtlasIndex {
+    fn load_from_bytes(data: &[u8], _options: ()) -> Result<Self, Error> {
+        Ok(ron::de::from_str(str::from_utf8(data)?)?)
+    }
+}
+
+/// Packs many images into a single texture.
+///
+/// Sprites from the same atlas share a texture, so they can be drawn in the same batch.
+pub struct AtlasBuilder {
+    /// Empty pixels between images.
+    pub padding: u32,
+    /// Number of times the border pixels of each image are repeated around it,
+    /// to avoid bleeding when filtering.
+    pub extrude: u32,
+    /// Maximum width and height of the atlas, in pixels.
+    pub max_size: u32,
+    images: Vec<(String, RgbaImage)>,
+}
+
+impl Default for AtlasBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AtlasBuilder {
+    /// Creates an empty atlas builder with 1 pixel of padding, no extrusion,
+    /// and a maximum size of 4096x4096.
+    pub fn new() -> Self {
+        Self {
+            padding: 1,
+            extrude: 0,
+            max_size: 4096,
+            images: Vec::new(),
+        }
+    }
+
+    /// Adds an image to pack.
+    pub fn add_image(&mut self, name: &str, image: RgbaImage) -> Result<(), Error> {
+        if self.images.iter().any(|(n, _)| n == name) {
+            return Err(AtlasError::DuplicateName(name.to_owned()).into());
+        }
+
+        self.images.push((name.to_owned(), image));
+        Ok(())
+    }
+
+    /// Adds an image to pack from encoded bytes (PNG, JPEG...).
+    pub fn add_bytes(&mut self, name: &str, data: &[u8]) -> Result<(), Error> {
+        self.add_image(name, image::load_from_memory(data)?.to_rgba())
+    }
+
+    /// Packs all images into one, without uploading it to OpenGL.
+    ///
+    /// The atlas is the smallest power of two square or 2:1 rectangle the images fit in.
+    pub fn pack(&self) -> Result<PackedAtlas, Error> {
+        let border = 2 * self.extrude + self.padding;
+
+        //Place larger images first
+        let mut order: Vec<usize> = (0..self.images.len()).collect();
+        order.sort_by_key(|&i| {
+            let (width, height) = self.images[i].1.dimensions();
+            (Reverse(height), Reverse(width))
+        });
+
+        //Find the smallest size that fits every image
+        let area: u32 = self
+            .images
+            .iter()
+            .map(|(_, image)| (image.width() + border) * (image.height() + border))
+            .sum();
+
+        let mut width = 1;
+        let mut height = 1;
+        while width * height < area && width <= self.max_size {
+            if width <= height {
+                width *= 2;
+            } else {
+                height *= 2;
+            }
+        }
+
+        let positions = loop {
+            if width > self.max_size || height > self.max_size {
+                return Err(AtlasError::AtlasTooSmall(self.max_size).into());
+            }
+
+            //Padding is only needed between images, not on the far edges
+            let mut skyline = Skyline::new(width + self.padding, height + self.padding);
+            let positions: Option<Vec<(u32, u32)>> = order
+                .iter()
+                .map(|&i| {
+                    let image = &self.images[i].1;
+                    skyline.insert(image.width() + border, image.height() + border)
+                })
+                .collect();
+
+            match positions {
+                Some(positions) => break positions,
+                None if width <= height => width *= 2,
+                None => height *= 2,
+            }
+        };
+
+        //Copy images into the atlas
+        let mut atlas = RgbaImage::new(width, height);
+        let mut regions = BTreeMap::new();
+
+        for (&i, &(x, y)) in order.iter().zip(&positions) {
+            let (name, image) = &self.images[i];
+            let rect = Rect::new(
+                x + self.extrude,
+                y + self.extrude,
+                image.width(),
+                image.height(),
+            );
+
+            atlas.copy_from(image, rect.x, rect.y);
+            self.extrude_borders(&mut atlas, rect);
+
+            regions.insert(name.clone(), rect);
+        }
+
+        Ok(PackedAtlas {
+            image: atlas,
+            regions,
+        })
+    }
+
+    /// Packs all images and uploads the atlas as a texture.
+    pub fn build(&self, options: TextureOptions) -> Result<SpriteSheet, Error> {
+        self.pack()?.into_sprite_sheet(options)
+    }
+
+    /// Repeats the border pixels of `rect` outwards `extrude` times.
+    fn extrude_borders(&self, atlas: &mut RgbaImage, rect: Rect) {
+        if self.extrude == 0 || rect.width == 0 || rect.height == 0 {
+            return;
+        }
+
+        let left = rect.x - self.extrude;
+        let top = rect.y - self.extrude;
+        let right = rect.x + rect.width + self.extrude;
+        let bottom = rect.y + rect.height + self.extrude;
+
+        for y in top..bottom {
+            for x in left..right {
+                //Nearest pixel inside the image
+                let source_x = x.max(rect.x).min(rect.x + rect.width - 1);
+                let source_y = y.max(rect.y).min(rect.y + rect.height - 1);
+
+                if source_x != x || source_y != y {
+                    let pixel = *atlas.get_pixel(source_x, source_y);
+                    atlas.put_pixel(x, y, pixel);
+                }
+            }
+        }
+    }
+}
+
+/// Packed atlas image and the position of each image in it.
+pub struct PackedAtlas {
+    pub image: RgbaImage,
+    pub regions: BTreeMap<String, Rect>,
+}
+
+impl PackedAtlas {
+    /// Saves the atlas as an image and a RON index, to be loaded later with `load`.
+    pub fn save<P: AsRef<Path>>(&self, image_path: P, index_path: P) -> Result<(), Error> {
+        let image_path = image_path.as_ref();
+        let index_path = index_path.as_ref();
+
+        self.image.save(image_path)?;
+
+        //Reference the image relative to the index when possible
+        let image = match (image_path.parent(), index_path.parent()) {
+            (Some(image_dir), Some(index_dir)) if image_dir == index_dir => image_path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned()),
+            _ => None,
+        }
+        .unwrap_or_else(|| image_path.to_string_lossy().into_owned());
+
+        let index = AtlasIndex {
+            image,
+            regions: self.regions.clone(),
+        };
+
+        let ron = ron::ser::to_string_pretty(&index, ron::ser::PrettyConfig::default())?;
+        fs::write(index_path, ron)?;
+
+        Ok(())
+    }
+
+    /// Loads an atlas saved with `save`.
+    pub fn load<P: AsRef<Path>>(index_path: P) -> Result<Self, Error> {
+        let index_path = index_path.as_ref();
+        let index = AtlasIndex::load_from_bytes(&fs::read(index_path)?, ())?;
+
+        let image_path = match index_path.parent() {
+            Some(directory) => directory.join(&index.image),
+            None => index.image.clone().into(),
+        };
+
+        Ok(Self {
+            image: image::open(image_path)?.to_rgba(),
+            regions: index.regions,
+        })
+    }
+
+    /// Uploads the atlas as a texture, and returns it as a sprite sheet
+    /// with one named region per packed image.
+    pub fn into_sprite_sheet(self, options: TextureOptions) -> Result<SpriteSheet, Error> {
+        let (width, height) = self.image.dimensions();
+        let texture = Texture::from_bytes(&self.image.into_raw(), options, width, height)?;
+
+        Ok(SpriteSheet::from_regions(texture, self.regions))
+    }
+}
+
+/// Skyline bin packer: keeps track of the highest used pixel of each column range,
+/// and places rectangles as low as possible.
+struct Skyline {
+    width: u32,
+    height: u32,
+    /// Segments of the skyline, as (x, y, width), sorted by x.
+    segments: Vec<(u32, u32, u32)>,
+}
+
+impl Skyline {
+    fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            segments: vec![(0, 0, width)],
+        }
+    }
+
+    /// Finds room for a rectangle and returns its top left corner,
+    /// or `None` if it does not fit.
+    fn insert(&mut self, width: u32, height: u32) -> Option<(u32, u32)> {
+        //Find the position with the lowest top edge, then the narrowest segment
+        let mut best: Option<(usize, u32, u32, u32)> = None;
+
+        for i in 0..self.segments.len() {
+            if let Some(y) = self.fit(i, width, height) {
+                let segment_width = self.segments[i].2;
+                let better = match best {
+                    None => true,
+                    Some((_, _, best_y, best_width)) => {
+                        y < best_y || (y == best_y && segment_width < best_width)
+                    }
+                };
+
+                if better {
+                    best = Some((i, self.segments[i].0, y, segment_width));
+                }
+            }
+        }
+
+        let (index, x, y, _) = best?;
+        self.place(index, x, y + height, width);
+
+        Some((x, y))
+    }
+
+    /// Height at which a rectangle starting at segment `index` would rest, if it fits.
+    fn fit(&self, index: usize, width: u32, height: u32) -> Option<u32> {
+        let x = self.segments[index].0;
+        if x + width > self.width {
+            return None;
+        }
+
+        let mut y = 0;
+        let mut remaining = width as i64;
+        let mut i = index;
+
+        while remaining > 0 {
+            let (_, segment_y, segment_width) = self.segments[i];
+            y = y.max(segment_y);
+
+            if y + height > self.height {
+                return None;
+            }
+
+            remaining -= segment_width as i64;
+            i += 1;
+        }
+
+        Some(y)
+    }
+
+    /// Raises the skyline to `top` over `width` pixels from `x`.
+    fn place(&mut self, index: usize, x: u32, top: u32, width: u32) {
+        self.segments.insert(index, (x, top, width));
+
+        //Shrink or remove segments covered by the new one
+        let end = x + width;
+        let i = index + 1;
+        while i < self.segments.len() {
+            let (segment_x, segment_y, segment_width) = self.segments[i];
+            if segment_x >= end {
+                break;
+            }
+
+            let segment_end = segment_x + segment_width;
+            if segment_end <= end {
+                self.segments.remove(i);
+            } else {
+                self.segments[i] = (end, segment_y, segment_end - end);
+                break;
+            }
+        }
+
+        //Merge neighbours at the same height
+        let mut i = 0;
+        while i + 1 < self.segments.len() {
+            if self.segments[i].1 == self.segments[i + 1].1 {
+                self.segments[i].2 += self.segments[i + 1].2;
+                self.segments.remove(i + 1);
+            } else {
+                i += 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    /// Whether two rectangles, grown by `padding` on the right and bottom, overlap.
+    fn overlap(a: Rect, b: Rect, padding: u32) -> bool {
+        a.x < b.x + b.width + padding
+            && b.x < a.x + a.width + padding
+            && a.y < b.y + b.height + padding
+            && b.y < a.y + a.height + padding
+    }
+
+    #[test]
+    fn skyline_places_rectangles_as_low_as_possible() {
+        let mut skyline = Skyline::new(10, 10);
+
+        assert_eq!(skyline.insert(4, 4), Some((0, 0)));
+        assert_eq!(skyline.insert(4, 4), Some((4, 0)));
+        //Only 2 pixels are left on the bottom row
+        assert_eq!(skyline.insert(4, 4), Some((0, 4)));
+        assert_eq!(skyline.insert(2, 2), Some((8, 0)));
+    }
+
+    #[test]
+    fn skyline_fills_its_whole_area() {
+        let mut skyline = Skyline::new(10, 10);
+
+        for _ in 0..4 {
+            assert!(skyline.insert(5, 5).is_some());
+        }
+
+        assert_eq!(skyline.insert(1, 1), None);
+    }
+
+    #[test]
+    fn skyline_rejects_rectangles_larger_than_the_bin() {
+        let mut skyline = Skyline::new(10, 10);
+
+        assert_eq!(skyline.insert(11, 1), None);
+        assert_eq!(skyline.insert(1, 11), None);
+        assert_eq!(skyline.insert(10, 10), Some((0, 0)));
+    }
+
+    #[test]
+    fn skyline_merges_segments_at_the_same_height() {
+        let mut skyline = Skyline::new(10, 10);
+
+        skyline.insert(3, 2);
+        skyline.insert(7, 2);
+
+        assert_eq!(skyline.segments, vec![(0, 2, 10)]);
+    }
+
+    #[test]
+    fn pack_copies_images_without_overlap() {
+        let mut builder = AtlasBuilder::new();
+        let sizes = [(16, 16), (8, 24), (30, 5), (1, 1), (12, 12), (7, 9)];
+
+        for (i, &(width, height)) in sizes.iter().enumerate() {
+            let pixel = Rgba {
+                data: [i as u8 * 40, 255 - i as u8 * 40, 0, 255],
+            };
+            let image = RgbaImage::from_pixel(width, height, pixel);
+            builder.add_image(&i.to_string(), image).unwrap();
+        }
+
+        let atlas = builder.pack().unwrap();
+        assert_eq!(atlas.regions.len(), sizes.len());
+
+        for (i, &(width, height)) in sizes.iter().enumerate() {
+            let rect = atlas.regions[&i.to_string()];
+            assert_eq!((rect.width, rect.height), (width, height));
+            assert!(rect.x + rect.width <= atlas.image.width());
+            assert!(rect.y + rect.height <= atlas.image.height());
+
+            let pixel = atlas
+                .image
+                .get_pixel(rect.x + width - 1, rect.y + height - 1);
+            assert_eq!(pixel.data[0], i as u8 * 40);
+
+            for (j, other) in atlas.regions.iter() {
+                if *j != i.to_string() {
+                    assert!(!overlap(rect, *other, builder.padding));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn pack_extrudes_borders() {
+        let mut builder = AtlasBuilder::new();
+        builder.extrude = 2;
+
+        let pixel = Rgba {
+            data: [255, 0, 0, 255],
+        };
+        builder
+            .add_image("red", RgbaImage::from_pixel(4, 4, pixel))
+            .unwrap();
+
+        let atlas = builder.pack().unwrap();
+        let rect = atlas.regions["red"];
+
+        assert_eq!((rect.x, rect.y), (2, 2));
+        assert_eq!(*atlas.image.get_pixel(0, 0), pixel);
+        assert_eq!(*atlas.image.get_pixel(rect.x + 5, rect.y + 5), pixel);
+    }
+
+    #[test]
+    fn pack_fails_if_images_exceed_the_maximum_size() {
+        let mut builder = AtlasBuilder::new();
+        builder.max_size = 16;
+        builder.add_image("large", RgbaImage::new(17, 4)).unwrap();
+
+        assert!(builder.pack().is_err());
+    }
+
+    #[test]
+    fn duplicate_names_are_rejected() {
+        let mut builder = AtlasBuilder::new();
+
+        builder.add_image("a", RgbaImage::new(1, 1)).unwrap();
+        assert!(builder.add_image("a", RgbaImage::new(1, 1)).is_err());
+    }
+}
@@ -1,32 +1,34 @@
 use super::{
+    color::Color,
+    material::Material,
     mesh::{Mesh, BATCH_INSTANCE_SIZE, MAX_BATCH_SIZE},
-    shaders::Program,
-    textures::{Texture, TextureID},
 };
 use gl;
 use maths::{Matrix4f, Vector4f};
 use std::mem;
 
 #[derive(Debug)]
-pub struct DrawCall<'t> {
-    pub program: Program,
+pub struct DrawCall<'m> {
+    /// Program, textures and uniforms to draw with.
+    pub material: &'m Material,
     pub mesh: Mesh,
-    pub texture: &'t Texture,
     pub tex_position: Vector4f,
+    /// Whether the texture area is rotated 90° clockwise, as in packed atlases.
+    pub uv_rotated: bool,
+    /// Layer of the texture to sample from, for texture arrays.
+    pub layer: u32,
     pub matrix: Matrix4f,
+    /// Tint multiplied with the texture by the base programs.
+    pub color: Color,
 }
 
 /// A queued draw call to be rendered.
 pub struct Batch {
-    /// Shader program to use to render.
-    program: Program,
+    /// Material to render with.
+    material: Material,
     /// Mesh to be rendered.
     mesh: Mesh,
 
-    // TODO: use reference instead to get lifetime checking on the texture
-    /// Texture to be rendered.
-    texture: TextureID,
-
     /// Stores the objects' info before it is passed to the VBO
     buffer: [f32; BATCH_INSTANCE_SIZE * MAX_BATCH_SIZE],
 
@@ -35,15 +37,12 @@ pub struct Batch {
 }
 
 impl Batch {
-    pub fn program(&self) -> Program {
-        self.program
+    pub fn material(&self) -> &Material {
+        &self.material
     }
     pub fn mesh(&self) -> Mesh {
         self.mesh
     }
-    pub fn texture(&self) -> TextureID {
-        self.texture
-    }
 
     pub fn obj_count(&self) -> usize {
         self.obj_count
@@ -52,9 +51,8 @@ impl Batch {
     /// Creates an empty batch from specified drawcall.
     pub fn new(drawcall: &DrawCall) -> Self {
         let mut batch = Self {
-            program: drawcall.program,
+            material: drawcall.material.clone(),
             mesh: drawcall.mesh,
-            texture: drawcall.texture.id(),
             buffer: [0.0; BATCH_INSTANCE_SIZE * MAX_BATCH_SIZE],
             obj_count: 0,
         };
@@ -66,10 +64,7 @@ impl Batch {
 
     /// Adds an object to the batch. Returns false if the batch is full.
     pub fn add(&mut self, drawcall: &DrawCall) -> bool {
-        if drawcall.program != self.program
-            || drawcall.mesh != self.mesh
-            || drawcall.texture.id() != self.texture
-        {
+        if drawcall.material.id() != self.material.id() || drawcall.mesh != self.mesh {
             return false;
         }
 
@@ -90,6 +85,18 @@ impl Batch {
             self.buffer[start_index + 4 + i] = drawcall.matrix[i / 4][i % 4];
         }
 
+        //Load tex parameters in buffer
+        self.buffer[start_index + 20] = if drawcall.uv_rotated { 1.0 } else { 0.0 };
+        self.buffer[start_index + 21] = drawcall.layer as f32;
+        self.buffer[start_index + 22] = 0.0;
+        self.buffer[start_index + 23] = 0.0;
+
+        //Load color in buffer
+        self.buffer[start_index + 24] = drawcall.color.r;
+        self.buffer[start_index + 25] = drawcall.color.g;
+        self.buffer[start_index + 26] = drawcall.color.b;
+        self.buffer[start_index + 27] = drawcall.color.a;
+
         self.obj_count += 1;
 
         true
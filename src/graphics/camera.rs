@@ -1,6 +1,64 @@
+use super::color::Color;
 use cgmath::{self, Ortho, PerspectiveFov, Point3};
 use maths::{Matrix4f, Vector2f, Vector2u, Vector3f};
 
+/// Rectangle of the window a camera renders to,
+/// in normalized coordinates: (0, 0) is the bottom left corner, (1, 1) the top right.
+///
+/// Default: the whole window.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Viewport {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Default for Viewport {
+    fn default() -> Self {
+        Self::new(0.0, 0.0, 1.0, 1.0)
+    }
+}
+
+impl Viewport {
+    /// Creates a viewport from its bottom left corner and size.
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// Position of the bottom left corner in pixels.
+    pub fn pixel_position(&self, window_size: Vector2u) -> Vector2u {
+        Vector2u::new(
+            (self.x * window_size.x as f32).round() as u32,
+            (self.y * window_size.y as f32).round() as u32,
+        )
+    }
+
+    /// Size of the viewport in pixels.
+    pub fn pixel_size(&self, window_size: Vector2u) -> Vector2u {
+        Vector2u::new(
+            ((self.width * window_size.x as f32).round() as u32).max(1),
+            ((self.height * window_size.y as f32).round() as u32).max(1),
+        )
+    }
+}
+
+/// Buffers that are cleared before a camera renders.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ClearFlags {
+    /// Clear color and depth, hiding everything rendered before.
+    ColorAndDepth,
+    /// Clear depth only: the camera draws over previous cameras.
+    Depth,
+    /// Clear nothing.
+    Nothing,
+}
+
 /// Different ways to calculate camera width and height from `size`.
 pub enum CameraScaleMode {
     /// `size` will always be width/horizontal FOV.
@@ -35,12 +93,25 @@ pub struct Camera {
 
     /// Whether the camera is perspective or orthographic.
     pub perspective: bool,
+
+    /// Part of the window the camera renders to.
+    pub viewport: Viewport,
+    /// Buffers cleared before the camera renders.
+    pub clear_flags: ClearFlags,
+    /// Color the viewport is cleared with.
+    pub clear_color: Color,
+    /// Cameras are rendered in increasing priority order,
+    /// meaning cameras with higher priority are drawn on top.
+    pub priority: i32,
+    /// Whether debug primitives are drawn by this camera.
+    pub show_debug: bool,
 }
 
 impl Camera {
-    /// Creates a new camera.
+    /// Creates a new camera that renders to the whole window.
     ///
-    /// Same as struct initialization.
+    /// The viewport, clear settings and priority are set to their defaults,
+    /// and can be changed afterwards.
     pub fn new(
         position: Vector3f,
         direction: Vector3f,
@@ -58,6 +129,11 @@ impl Camera {
             size,
             scale_mode,
             perspective,
+            viewport: Viewport::default(),
+            clear_flags: ClearFlags::ColorAndDepth,
+            clear_color: Color::rgb(0.3, 0.3, 0.5),
+            priority: 0,
+            show_debug: true,
         }
     }
 
@@ -67,13 +143,18 @@ impl Camera {
     }
 
     /// Combined projection and view matrices.
+    ///
+    /// `window_size` is the size of the whole window: the camera's viewport is taken into account.
     pub fn matrix(&self, window_size: Vector2u) -> Matrix4f {
         self.proj_matrix(window_size) * self.view_matrix()
     }
 
     /// Projection matrix.
+    ///
+    /// `window_size` is the size of the whole window: the camera's viewport is taken into account.
     pub fn proj_matrix(&self, window_size: Vector2u) -> Matrix4f {
-        let ratio = window_size.x as f32 / window_size.y as f32;
+        let viewport_size = self.viewport.pixel_size(window_size);
+        let ratio = viewport_size.x as f32 / viewport_size.y as f32;
 
         let size: Vector2f = match self.scale_mode {
             CameraScaleMode::Width => Vector2f::new(self.size, self.size / ratio),
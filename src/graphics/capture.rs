@@ -0,0 +1,175 @@
+use failure::Error;
+use gif::{self, SetParameter};
+use image::RgbaImage;
+use std::{
+    fs::{self, File},
+    path::{Path, PathBuf},
+    sync::mpsc::Sender,
+    thread,
+    time::{Duration, Instant},
+};
+
+#[derive(Debug, Fail)]
+pub enum RecordingError {
+    /// A frame has another size than the first one. Contains the index of the frame.
+    #[fail(display = "Frame {} has another size than the first frame", _0)]
+    FrameSizeChanged(usize),
+    /// Frames are too large for a GIF. Contains the width and height of the frames.
+    #[fail(display = "Frames of {}x{} pixels are too large for a GIF", _0, _1)]
+    FrameTooLarge(u32, u32),
+    /// The thread saving the recording panicked.
+    #[fail(display = "Saving the recording panicked")]
+    SavePanicked,
+}
+
+/// Where a `FrameRecorder` saves its frames.
+#[derive(Debug, Clone)]
+pub enum RecordingOutput {
+    /// Single looping animated GIF.
+    Gif(PathBuf),
+    /// One PNG file per frame, named `<prefix>0000.png`, `<prefix>0001.png`...
+    Sequence { directory: PathBuf, prefix: String },
+}
+
+/// Captures a number of frames at a fixed rate, then saves them.
+///
+/// Start a recording with `GraphicsManager::start_recording`.
+#[derive(Debug)]
+pub struct FrameRecorder {
+    output: RecordingOutput,
+    frame_count: usize,
+    interval: Duration,
+    frames: Vec<RgbaImage>,
+    next_capture: Option<Instant>,
+}
+
+impl FrameRecorder {
+    /// Creates a recorder that will capture `frame_count` frames, `fps` times per second.
+    pub fn new(output: RecordingOutput, frame_count: usize, fps: f32) -> Self {
+        let nanos = (1_000_000_000.0 / fps.max(0.001)) as u64;
+
+        Self {
+            output,
+            frame_count,
+            interval: Duration::from_nanos(nanos),
+            frames: Vec::with_capacity(frame_count),
+            next_capture: None,
+        }
+    }
+
+    /// Returns true once all frames have been captured.
+    pub fn is_finished(&self) -> bool {
+        self.frames.len() >= self.frame_count
+    }
+
+    /// Number of frames captured so far.
+    pub fn captured_frames(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Returns true if the frame being rendered should be captured.
+    pub(crate) fn wants_frame(&self) -> bool {
+        !self.is_finished()
+            && self
+                .next_capture
+                .map_or(true, |next| Instant::now() >= next)
+    }
+
+    /// Stores a captured frame.
+    pub(crate) fn add_frame(&mut self, frame: RgbaImage) {
+        //Schedule from the previous capture time to avoid drifting
+        let now = Instant::now();
+        let next = match self.next_capture {
+            Some(next) if next + self.interval > now => next + self.interval,
+            _ => now + self.interval,
+        };
+
+        self.next_capture = Some(next);
+        self.frames.push(frame);
+    }
+
+    /// Saves the frames captured so far.
+    pub fn save(&self) -> Result<(), Error> {
+        match &self.output {
+            RecordingOutput::Gif(path) => self.save_gif(path),
+            RecordingOutput::Sequence { directory, prefix } => {
+                fs::create_dir_all(directory)?;
+
+                for (i, frame) in self.frames.iter().enumerate() {
+                    frame.save(directory.join(format!("{}{:04}.png", prefix, i)))?;
+                }
+
+                Ok(())
+            }
+        }
+    }
+
+    /// Saves the frames captured so far on a background thread, then sends the result.
+    ///
+    /// A result is sent even if saving panics, so the manager never waits forever.
+    pub(crate) fn save_in_background(self, results: Sender<Result<(), Error>>) {
+        thread::spawn(move || {
+            let guard = ResultGuard(Some(results));
+            let result = self.save();
+            guard.send(result);
+        });
+    }
+
+    fn save_gif(&self, path: &Path) -> Result<(), Error> {
+        let (width, height) = match self.frames.first() {
+            Some(frame) => frame.dimensions(),
+            None => return Ok(()),
+        };
+
+        if let Some(index) = self
+            .frames
+            .iter()
+            .position(|frame| frame.dimensions() != (width, height))
+        {
+            return Err(RecordingError::FrameSizeChanged(index).into());
+        }
+
+        let max_size = u32::from(u16::max_value());
+        if width > max_size || height > max_size {
+            return Err(RecordingError::FrameTooLarge(width, height).into());
+        }
+
+        let mut encoder = gif::Encoder::new(File::create(path)?, width as u16, height as u16, &[])?;
+        encoder.set(gif::Repeat::Infinite)?;
+
+        //GIF delays are in hundredths of a second
+        let interval =
+            self.interval.as_secs() as f32 + self.interval.subsec_nanos() as f32 / 1_000_000_000.0;
+        let delay = (interval * 100.0).round() as u16;
+
+        for frame in &self.frames {
+            let mut pixels = frame.clone().into_raw();
+            let mut gif_frame = gif::Frame::from_rgba(width as u16, height as u16, &mut pixels);
+            gif_frame.delay = delay;
+
+            encoder.write_frame(&gif_frame)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Sends the result of a background save, or `SavePanicked` if it is dropped before.
+struct ResultGuard(Option<Sender<Result<(), Error>>>);
+
+impl ResultGuard {
+    fn send(mut self, result: Result<(), Error>) {
+        if let Some(sender) = self.0.take() {
+            //The receiver is gone if the manager was dropped, nobody needs the result then
+            let _ = sender.send(result);
+        }
+    }
+}
+
+impl Drop for ResultGuard {
+    fn drop(&mut self) {
+        if let Some(sender) = self.0.take() {
+            let _ = sender.send(Err(RecordingError::SavePanicked.into()));
+        }
+    }
+}
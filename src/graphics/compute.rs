@@ -0,0 +1,208 @@
+use super::{
+    preprocessor::ShaderDefines,
+    shaders::{Program, Shader, ShaderError, ShaderType},
+};
+use assets::AssetDatabase;
+use failure::Error;
+use gl;
+use std::{marker::PhantomData, mem, ptr};
+
+/// Errors related to storage buffers.
+#[derive(Debug, Fail)]
+pub enum StorageBufferError {
+    /// Tried writing elements outside of the buffer.
+    /// Contains the offset and number of elements written, and the buffer length.
+    #[fail(
+        display = "Cannot write {} elements at {} in a buffer of {} elements",
+        _1, _0, _2
+    )]
+    OutOfBounds(usize, usize, usize),
+}
+
+/// Program made of a single compute shader, run on the GPU with `dispatch`.
+///
+/// Compute shaders require OpenGL 4.3: creating one fails with `ShaderError::Unsupported`
+/// otherwise. Check `ComputeProgram::is_supported` to fall back to another implementation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ComputeProgram {
+    program: Program,
+}
+
+impl ComputeProgram {
+    /// Returns true if the current context supports compute shaders.
+    pub fn is_supported() -> bool {
+        ShaderType::Compute.is_supported()
+    }
+
+    /// Creates a compute program from a compiled compute shader. Deletes the shader afterwards.
+    pub fn new(shader: Shader) -> Result<Self, Error> {
+        if shader.shader_type() != ShaderType::Compute {
+            return Err(
+                ShaderError::WrongShaderType(ShaderType::Compute, shader.shader_type()).into(),
+            );
+        }
+
+        Ok(Self {
+            program: Program::from_stages(&[shader])?,
+        })
+    }
+
+    /// Creates a compute program from GLSL source, see `Shader::from_source_with`.
+    pub fn from_source(
+        source: &str,
+        defines: &ShaderDefines,
+        assets: Option<&AssetDatabase>,
+    ) -> Result<Self, Error> {
+        Self::new(Shader::from_source_with(
+            source,
+            ShaderType::Compute,
+            defines,
+            assets,
+        )?)
+    }
+
+    /// Underlying program, to set uniforms and bind uniform blocks.
+    pub fn program(&self) -> &Program {
+        &self.program
+    }
+
+    /// Runs the shader on `x * y * z` work groups, whose size is set in the shader.
+    ///
+    /// Storage buffers used by the shader must be bound beforehand.
+    /// Their writes are visible to later draws, dispatches and reads once this returns.
+    pub fn dispatch(&self, x: u32, y: u32, z: u32) {
+        self.program.set_used();
+
+        unsafe {
+            gl::DispatchCompute(x, y, z);
+            gl::MemoryBarrier(
+                gl::SHADER_STORAGE_BARRIER_BIT
+                    | gl::VERTEX_ATTRIB_ARRAY_BARRIER_BIT
+                    | gl::BUFFER_UPDATE_BARRIER_BIT,
+            );
+        }
+    }
+}
+
+/// Array of `T` stored on the GPU, read and written by shaders as a `buffer` block.
+///
+/// `T` must be `#[repr(C)]` and match the `std430` layout of the block's elements:
+/// for instance, a `vec3` is aligned like a `vec4`.
+///
+/// Requires OpenGL 4.3. This owns the buffer, meaning the OpenGL buffer is deleted when
+/// `StorageBuffer` goes out of scope.
+#[derive(Debug)]
+pub struct StorageBuffer<T> {
+    id: gl::types::GLuint,
+    len: usize,
+    element: PhantomData<T>,
+}
+
+impl<T> Drop for StorageBuffer<T> {
+    fn drop(&mut self) {
+        unsafe { gl::DeleteBuffers(1, &self.id) }
+    }
+}
+
+impl<T: Copy> StorageBuffer<T> {
+    /// Creates a buffer holding `data`.
+    pub fn new(data: &[T]) -> Result<Self, Error> {
+        Self::allocate(data.len(), data.as_ptr())
+    }
+
+    /// Creates a buffer of `len` elements with uninitialized contents.
+    pub fn empty(len: usize) -> Result<Self, Error> {
+        Self::allocate(len, ptr::null())
+    }
+
+    /// Number of elements.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Replaces elements from `offset`.
+    ///
+    /// Fails if the elements do not fit in the buffer.
+    pub fn update(&mut self, offset: usize, data: &[T]) -> Result<(), Error> {
+        match offset.checked_add(data.len()) {
+            Some(end) if end <= self.len => {}
+            _ => return Err(StorageBufferError::OutOfBounds(offset, data.len(), self.len).into()),
+        }
+
+        unsafe {
+            gl::BindBuffer(gl::SHADER_STORAGE_BUFFER, self.id);
+            gl::BufferSubData(
+                gl::SHADER_STORAGE_BUFFER,
+                (offset * mem::size_of::<T>()) as gl::types::GLintptr,
+                (data.len() * mem::size_of::<T>()) as gl::types::GLsizeiptr,
+                data.as_ptr() as *const gl::types::GLvoid,
+            );
+            gl::BindBuffer(gl::SHADER_STORAGE_BUFFER, 0);
+        }
+
+        Ok(())
+    }
+
+    /// Reads the contents of the buffer back from the GPU.
+    ///
+    /// This waits for the GPU to finish its work, avoid calling it every frame.
+    pub fn read(&self) -> Vec<T> {
+        let mut data = Vec::with_capacity(self.len);
+
+        unsafe {
+            gl::BindBuffer(gl::SHADER_STORAGE_BUFFER, self.id);
+            gl::GetBufferSubData(
+                gl::SHADER_STORAGE_BUFFER,
+                0,
+                (self.len * mem::size_of::<T>()) as gl::types::GLsizeiptr,
+                data.as_mut_ptr() as *mut gl::types::GLvoid,
+            );
+            gl::BindBuffer(gl::SHADER_STORAGE_BUFFER, 0);
+
+            data.set_len(self.len);
+        }
+
+        data
+    }
+
+    /// Binds the buffer to a shader storage binding point,
+    /// set in shaders with `layout (std430, binding = N)`.
+    pub fn bind(&self, binding: u32) {
+        unsafe { gl::BindBufferBase(gl::SHADER_STORAGE_BUFFER, binding, self.id) }
+    }
+
+    pub fn id(&self) -> gl::types::GLuint {
+        self.id
+    }
+
+    fn allocate(len: usize, data: *const T) -> Result<Self, Error> {
+        //Introduced with compute shaders
+        ShaderType::Compute
+            .required_version()
+            .require("Shader storage buffers")?;
+
+        let mut id = 0;
+
+        unsafe {
+            gl::GenBuffers(1, &mut id);
+            gl::BindBuffer(gl::SHADER_STORAGE_BUFFER, id);
+            gl::BufferData(
+                gl::SHADER_STORAGE_BUFFER,
+                (len * mem::size_of::<T>()) as gl::types::GLsizeiptr,
+                data as *const gl::types::GLvoid,
+                gl::DYNAMIC_COPY,
+            );
+            gl::BindBuffer(gl::SHADER_STORAGE_BUFFER, 0);
+        }
+
+        Ok(Self {
+            id,
+            len,
+            element: PhantomData,
+        })
+    }
+}
@@ -0,0 +1,193 @@
+use super::textures::{
+    self, BindableTexture, TextureError, TextureFormat, TextureID, TextureOptions, TextureTarget,
+};
+use assets::Asset;
+use failure::Error;
+use gl;
+use image::{self, imageops, RgbaImage};
+
+/// Errors related to cubemaps.
+#[derive(Debug, Fail)]
+pub enum CubemapError {
+    /// Image is not a horizontal (4x3 faces) or vertical (3x4 faces) cross.
+    /// Contains image width and height.
+    #[fail(display = "{}x{} image is not a cubemap cross", _0, _1)]
+    InvalidCrossLayout(u32, u32),
+    /// Faces are not square.
+    #[fail(display = "Cubemap faces must be square, got {}x{}", _0, _1)]
+    NonSquareFace(u32, u32),
+}
+
+/// Six square textures forming the inside of a cube, sampled in shaders
+/// with a direction as a `samplerCube`. Mostly used for skyboxes and reflections.
+///
+/// Draw one as the sky with `GraphicsManager::draw_skybox`.
+///
+/// This owns the texture, meaning the OpenGL texture is deleted when
+/// `Cubemap` goes out of scope.
+#[derive(Debug)]
+pub struct Cubemap {
+    id: TextureID,
+    size: u32,
+    options: TextureOptions,
+}
+
+/// Loads a cubemap from a single image laid out as a cross.
+///
+/// See `Cubemap::from_cross`.
+impl Asset<TextureOptions> for Cubemap {
+    fn load_from_bytes(data: &[u8], options: TextureOptions) -> Result<Self, Error> {
+        Self::from_cross(&image::load_from_memory(data)?.to_rgba(), options)
+    }
+}
+
+impl Drop for Cubemap {
+    fn drop(&mut self) {
+        unsafe { gl::DeleteTextures(1, &self.id) }
+    }
+}
+
+impl BindableTexture for Cubemap {
+    fn id(&self) -> TextureID {
+        self.id
+    }
+
+    fn target(&self) -> TextureTarget {
+        TextureTarget::Cubemap
+    }
+}
+
+impl PartialEq for Cubemap {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl Eq for Cubemap {}
+
+impl Cubemap {
+    /// Creates a cubemap from six square images of the same size,
+    /// in the order +X, -X, +Y, -Y, +Z, -Z.
+    ///
+    /// The format in `options` is ignored, cubemaps created from images are RGBA.
+    pub fn from_images(faces: &[RgbaImage; 6], options: TextureOptions) -> Result<Self, Error> {
+        let (size, height) = faces[0].dimensions();
+        if size != height {
+            return Err(CubemapError::NonSquareFace(size, height).into());
+        }
+
+        for face in faces.iter() {
+            if face.dimensions() != (size, size) {
+                return Err(TextureError::ImageSizeMismatch(
+                    size,
+                    size,
+                    face.width(),
+                    face.height(),
+                )
+                .into());
+            }
+        }
+
+        let options = TextureOptions {
+            format: TextureFormat::Rgba,
+            ..options
+        };
+
+        let mut id = 0;
+
+        unsafe {
+            gl::GenTextures(1, &mut id);
+            gl::BindTexture(gl::TEXTURE_CUBE_MAP, id);
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+
+            for (i, face) in faces.iter().enumerate() {
+                gl::TexImage2D(
+                    gl::TEXTURE_CUBE_MAP_POSITIVE_X + i as gl::types::GLenum,
+                    0,
+                    options.format.internal_format() as gl::types::GLint,
+                    size as gl::types::GLsizei,
+                    size as gl::types::GLsizei,
+                    0,
+                    options.format.external_format(),
+                    options.format.pixel_type(),
+                    face.as_ptr() as *const gl::types::GLvoid,
+                );
+            }
+
+            textures::apply_options(TextureTarget::Cubemap, &options);
+            gl::TexParameteri(
+                gl::TEXTURE_CUBE_MAP,
+                gl::TEXTURE_WRAP_R,
+                options.v_wrap_mode as gl::types::GLint,
+            );
+            textures::generate_mipmaps(TextureTarget::Cubemap, &options);
+
+            gl::BindTexture(gl::TEXTURE_CUBE_MAP, 0);
+        }
+
+        Ok(Self { id, size, options })
+    }
+
+    /// Creates a cubemap from a single image laid out as a cross.
+    ///
+    /// Horizontal crosses are 4 faces wide and 3 faces high:
+    ///
+    /// ```text
+    ///     +Y
+    /// -X  +Z  +X  -Z
+    ///     -Y
+    /// ```
+    ///
+    /// Vertical crosses are 3 faces wide and 4 faces high, with -Z upside down at the bottom:
+    ///
+    /// ```text
+    ///     +Y
+    /// -X  +Z  +X
+    ///     -Y
+    ///     -Z
+    /// ```
+    pub fn from_cross(image: &RgbaImage, options: TextureOptions) -> Result<Self, Error> {
+        let (width, height) = image.dimensions();
+
+        //Position of each face in the cross, in faces, in the order +X, -X, +Y, -Y, +Z, -Z
+        let (size, positions, vertical) = if width * 3 == height * 4 && width % 4 == 0 {
+            (
+                width / 4,
+                [(2, 1), (0, 1), (1, 0), (1, 2), (1, 1), (3, 1)],
+                false,
+            )
+        } else if width * 4 == height * 3 && width % 3 == 0 {
+            (
+                width / 3,
+                [(2, 1), (0, 1), (1, 0), (1, 2), (1, 1), (1, 3)],
+                true,
+            )
+        } else {
+            return Err(CubemapError::InvalidCrossLayout(width, height).into());
+        };
+
+        //Copy each face from the cross
+        let face = |i: usize| {
+            let (x, y) = positions[i];
+            RgbaImage::from_fn(size, size, |face_x, face_y| {
+                *image.get_pixel(x * size + face_x, y * size + face_y)
+            })
+        };
+
+        let mut faces = [face(0), face(1), face(2), face(3), face(4), face(5)];
+        if vertical {
+            faces[5] = imageops::rotate180(&faces[5]);
+        }
+
+        Self::from_images(&faces, options)
+    }
+
+    /// Size of each face in pixels.
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    pub fn options(&self) -> &TextureOptions {
+        &self.options
+    }
+}
@@ -0,0 +1,260 @@
+use super::{color::Color, dynamic::DynamicVertex};
+use cgmath::InnerSpace;
+use maths::{Vector2f, Vector3f};
+use std::{f32::consts::PI, time::Instant};
+
+/// Number of segments used to draw debug circles.
+const CIRCLE_SEGMENTS: usize = 32;
+
+/// A debug line waiting to be drawn.
+#[derive(Debug, Copy, Clone)]
+struct DebugLine {
+    start: Vector3f,
+    end: Vector3f,
+    color: Color,
+    /// Time left before the line is removed, in seconds.
+    remaining: f32,
+    depth_test: bool,
+}
+
+/// Stores debug primitives as lines until they expire.
+///
+/// Every primitive is drawn at least once, even with a duration of 0.
+#[derive(Debug)]
+pub struct DebugDraw {
+    lines: Vec<DebugLine>,
+    last_update: Option<Instant>,
+}
+
+impl Default for DebugDraw {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DebugDraw {
+    pub fn new() -> Self {
+        Self {
+            lines: Vec::new(),
+            last_update: None,
+        }
+    }
+
+    /// Queues a line between two points.
+    pub fn line(
+        &mut self,
+        start: Vector3f,
+        end: Vector3f,
+        color: Color,
+        duration: f32,
+        depth_test: bool,
+    ) {
+        self.lines.push(DebugLine {
+            start,
+            end,
+            color,
+            remaining: duration,
+            depth_test,
+        });
+    }
+
+    /// Queues a rectangle on the XY plane.
+    pub fn rect(
+        &mut self,
+        center: Vector3f,
+        size: Vector2f,
+        color: Color,
+        duration: f32,
+        depth_test: bool,
+    ) {
+        let half_x = Vector3f::new(size.x / 2.0, 0.0, 0.0);
+        let half_y = Vector3f::new(0.0, size.y / 2.0, 0.0);
+
+        let corners = [
+            center - half_x - half_y,
+            center + half_x - half_y,
+            center + half_x + half_y,
+            center - half_x + half_y,
+        ];
+
+        for i in 0..4 {
+            self.line(
+                corners[i],
+                corners[(i + 1) % 4],
+                color,
+                duration,
+                depth_test,
+            );
+        }
+    }
+
+    /// Queues a circle on the XY plane.
+    pub fn circle(
+        &mut self,
+        center: Vector3f,
+        radius: f32,
+        color: Color,
+        duration: f32,
+        depth_test: bool,
+    ) {
+        let point = |i: usize| {
+            let angle = 2.0 * PI * i as f32 / CIRCLE_SEGMENTS as f32;
+            center + Vector3f::new(angle.cos(), angle.sin(), 0.0) * radius
+        };
+
+        for i in 0..CIRCLE_SEGMENTS {
+            self.line(point(i), point(i + 1), color, duration, depth_test);
+        }
+    }
+
+    /// Queues an axis-aligned wireframe box.
+    pub fn cuboid(
+        &mut self,
+        center: Vector3f,
+        size: Vector3f,
+        color: Color,
+        duration: f32,
+        depth_test: bool,
+    ) {
+        let half = size / 2.0;
+        let corner =
+            |x: f32, y: f32, z: f32| center + Vector3f::new(half.x * x, half.y * y, half.z * z);
+
+        for &(a, b) in &[
+            //Back face
+            ((-1.0, -1.0, -1.0), (1.0, -1.0, -1.0)),
+            ((1.0, -1.0, -1.0), (1.0, 1.0, -1.0)),
+            ((1.0, 1.0, -1.0), (-1.0, 1.0, -1.0)),
+            ((-1.0, 1.0, -1.0), (-1.0, -1.0, -1.0)),
+            //Front face
+            ((-1.0, -1.0, 1.0), (1.0, -1.0, 1.0)),
+            ((1.0, -1.0, 1.0), (1.0, 1.0, 1.0)),
+            ((1.0, 1.0, 1.0), (-1.0, 1.0, 1.0)),
+            ((-1.0, 1.0, 1.0), (-1.0, -1.0, 1.0)),
+            //Edges between faces
+            ((-1.0, -1.0, -1.0), (-1.0, -1.0, 1.0)),
+            ((1.0, -1.0, -1.0), (1.0, -1.0, 1.0)),
+            ((1.0, 1.0, -1.0), (1.0, 1.0, 1.0)),
+            ((-1.0, 1.0, -1.0), (-1.0, 1.0, 1.0)),
+        ] {
+            self.line(
+                corner(a.0, a.1, a.2),
+                corner(b.0, b.1, b.2),
+                color,
+                duration,
+                depth_test,
+            );
+        }
+    }
+
+    /// Queues an arrow from `start` to `end`.
+    pub fn arrow(
+        &mut self,
+        start: Vector3f,
+        end: Vector3f,
+        color: Color,
+        duration: f32,
+        depth_test: bool,
+    ) {
+        self.line(start, end, color, duration, depth_test);
+
+        let direction = end - start;
+        let length = direction.magnitude();
+        if length <= 0.0 {
+            return;
+        }
+        let direction = direction / length;
+
+        //Head lies on the plane containing the arrow and the Z axis,
+        //or the Y axis if the arrow is along Z
+        let up = if direction.z.abs() > 0.99 {
+            Vector3f::new(0.0, 1.0, 0.0)
+        } else {
+            Vector3f::new(0.0, 0.0, 1.0)
+        };
+        let side = direction.cross(up).normalize();
+
+        let head_length = length * 0.2;
+        let back = end - direction * head_length;
+
+        self.line(
+            end,
+            back + side * head_length * 0.5,
+            color,
+            duration,
+            depth_test,
+        );
+        self.line(
+            end,
+            back - side * head_length * 0.5,
+            color,
+            duration,
+            depth_test,
+        );
+    }
+
+    /// Queues the X (red), Y (green) and Z (blue) axes.
+    pub fn axes(&mut self, origin: Vector3f, size: f32, duration: f32, depth_test: bool) {
+        self.arrow(
+            origin,
+            origin + Vector3f::new(size, 0.0, 0.0),
+            Color::RED,
+            duration,
+            depth_test,
+        );
+        self.arrow(
+            origin,
+            origin + Vector3f::new(0.0, size, 0.0),
+            Color::GREEN,
+            duration,
+            depth_test,
+        );
+        self.arrow(
+            origin,
+            origin + Vector3f::new(0.0, 0.0, size),
+            Color::BLUE,
+            duration,
+            depth_test,
+        );
+    }
+
+    /// Vertices of the current lines, as pairs of points for `gl::LINES`.
+    ///
+    /// Only lines with matching `depth_test` are returned.
+    pub fn vertices(&self, depth_test: bool) -> Vec<DynamicVertex> {
+        let uv = Vector2f::new(0.0, 0.0);
+
+        let mut vertices = Vec::new();
+        for line in self
+            .lines
+            .iter()
+            .filter(|line| line.depth_test == depth_test)
+        {
+            vertices.push(DynamicVertex::new(line.start, uv, line.color));
+            vertices.push(DynamicVertex::new(line.end, uv, line.color));
+        }
+
+        vertices
+    }
+
+    /// Advances time since the last update, and removes expired lines.
+    ///
+    /// Call once per frame, after drawing.
+    pub fn update(&mut self) {
+        let now = Instant::now();
+        let elapsed = match self.last_update {
+            Some(last) => {
+                let duration = now - last;
+                duration.as_secs() as f32 + duration.subsec_nanos() as f32 / 1_000_000_000.0
+            }
+            None => 0.0,
+        };
+        self.last_update = Some(now);
+
+        for line in &mut self.lines {
+            line.remaining -= elapsed;
+        }
+
+        self.lines.retain(|line| line.remaining > 0.0);
+    }
+}
@@ -0,0 +1,122 @@
+use super::{color::Color, mesh::locations};
+use gl;
+use maths::{Vector2f, Vector3f, Vector4f};
+use std::{mem, ptr};
+
+/// Vertex of geometry that is rebuilt every frame.
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct DynamicVertex {
+    pub position: Vector3f,
+    pub uv: Vector2f,
+    pub color: Vector4f,
+}
+
+impl DynamicVertex {
+    pub fn new(position: Vector3f, uv: Vector2f, color: Color) -> Self {
+        Self {
+            position,
+            uv,
+            color: color.into(),
+        }
+    }
+}
+
+/// GPU buffer streaming `DynamicVertex` data, drawn without indices.
+pub struct DynamicBuffer {
+    vao: gl::types::GLuint,
+    vbo: gl::types::GLuint,
+}
+
+impl Drop for DynamicBuffer {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteBuffers(1, &self.vbo);
+            gl::DeleteVertexArrays(1, &self.vao);
+        }
+    }
+}
+
+impl Default for DynamicBuffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DynamicBuffer {
+    pub fn new() -> Self {
+        let mut vao = 0;
+        let mut vbo = 0;
+
+        unsafe {
+            gl::GenVertexArrays(1, &mut vao);
+            gl::GenBuffers(1, &mut vbo);
+
+            gl::BindVertexArray(vao);
+            gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
+
+            let stride = mem::size_of::<DynamicVertex>();
+            //Position
+            Self::attrib_array(stride, locations::POSITION, 0, 3);
+            //UV
+            Self::attrib_array(stride, locations::UV, mem::size_of::<Vector3f>(), 2);
+            //Color
+            Self::attrib_array(
+                stride,
+                locations::COLOR,
+                mem::size_of::<Vector3f>() + mem::size_of::<Vector2f>(),
+                4,
+            );
+
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+            gl::BindVertexArray(0);
+        }
+
+        Self { vao, vbo }
+    }
+
+    unsafe fn attrib_array(stride: usize, location: gl::types::GLuint, offset: usize, length: i32) {
+        gl::EnableVertexAttribArray(location);
+        gl::VertexAttribPointer(
+            location,
+            length,
+            gl::FLOAT,
+            gl::FALSE,
+            stride as gl::types::GLint,
+            offset as *const gl::types::GLvoid,
+        );
+    }
+
+    /// Uploads and draws vertices using `mode` (e.g. `gl::LINES`, `gl::TRIANGLES`).
+    ///
+    /// The program and textures must already be bound.
+    pub fn draw(&self, vertices: &[DynamicVertex], mode: gl::types::GLenum) {
+        if vertices.is_empty() {
+            return;
+        }
+
+        unsafe {
+            gl::BindVertexArray(self.vao);
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo);
+
+            //Orphan previous data, then upload
+            gl::BufferData(
+                gl::ARRAY_BUFFER,
+                (vertices.len() * mem::size_of::<DynamicVertex>()) as gl::types::GLsizeiptr,
+                ptr::null(),
+                gl::STREAM_DRAW,
+            );
+            gl::BufferSubData(
+                gl::ARRAY_BUFFER,
+                0,
+                (vertices.len() * mem::size_of::<DynamicVertex>()) as gl::types::GLsizeiptr,
+                vertices.as_ptr() as *const gl::types::GLvoid,
+            );
+
+            gl::DrawArrays(mode, 0, vertices.len() as gl::types::GLsizei);
+
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+            gl::BindVertexArray(0);
+        }
+    }
+}
@@ -0,0 +1,196 @@
+use super::textures::{
+    MaxFilterMode, MinFilterMode, Texture, TextureFormat, TextureOptions, WrapMode,
+};
+use failure::Error;
+use gl;
+use image::{self, RgbaImage};
+use maths::Vector2u;
+
+/// ID of loaded OpenGL Framebuffer
+pub type FramebufferID = gl::types::GLuint;
+
+/// Errors related to framebuffers.
+#[derive(Debug, Fail)]
+pub enum FramebufferError {
+    /// Framebuffer could not be completed. Contains OpenGL status.
+    #[fail(display = "Framebuffer incomplete: status 0x{:X}", _0)]
+    FramebufferIncomplete(gl::types::GLenum),
+    /// Pixels read back from OpenGL did not fit in an image of the expected size.
+    #[fail(display = "Could not read {}x{} pixels", _0, _1)]
+    ReadPixelsFailed(u32, u32),
+}
+
+/// Offscreen render target made of a color texture and a depth buffer.
+///
+/// This owns its OpenGL objects, meaning they are deleted when
+/// `Framebuffer` goes out of scope.
+#[derive(Debug)]
+pub struct Framebuffer {
+    id: FramebufferID,
+    texture: Texture,
+    depth_buffer: gl::types::GLuint,
+}
+
+impl Drop for Framebuffer {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteRenderbuffers(1, &self.depth_buffer);
+            gl::DeleteFramebuffers(1, &self.id);
+        }
+    }
+}
+
+impl Framebuffer {
+    /// Creates a framebuffer of the specified size (in pixels).
+    pub fn new(width: u32, height: u32) -> Result<Self, Error> {
+        let options = TextureOptions {
+            format: TextureFormat::Rgba,
+            h_wrap_mode: WrapMode::ClampToEdge,
+            v_wrap_mode: WrapMode::ClampToEdge,
+            min_filter_mode: MinFilterMode::Linear,
+            max_filter_mode: MaxFilterMode::Linear,
+        };
+
+        let texture = Texture::empty(options, width, height);
+
+        let mut id = 0;
+        let mut depth_buffer = 0;
+
+        unsafe {
+            //Create framebuffer
+            gl::GenFramebuffers(1, &mut id);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, id);
+
+            //Attach color texture
+            gl::FramebufferTexture2D(
+                gl::FRAMEBUFFER,
+                gl::COLOR_ATTACHMENT0,
+                gl::TEXTURE_2D,
+                texture.id(),
+                0,
+            );
+
+            //Create and attach depth buffer
+            gl::GenRenderbuffers(1, &mut depth_buffer);
+            gl::BindRenderbuffer(gl::RENDERBUFFER, depth_buffer);
+            gl::RenderbufferStorage(
+                gl::RENDERBUFFER,
+                gl::DEPTH_COMPONENT24,
+                width as gl::types::GLsizei,
+                height as gl::types::GLsizei,
+            );
+            gl::FramebufferRenderbuffer(
+                gl::FRAMEBUFFER,
+                gl::DEPTH_ATTACHMENT,
+                gl::RENDERBUFFER,
+                depth_buffer,
+            );
+        }
+
+        //Check status
+        let status = unsafe { gl::CheckFramebufferStatus(gl::FRAMEBUFFER) };
+
+        unsafe {
+            //Unbind everything
+            gl::BindRenderbuffer(gl::RENDERBUFFER, 0);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+
+        let framebuffer = Self {
+            id,
+            texture,
+            depth_buffer,
+        };
+
+        if status != gl::FRAMEBUFFER_COMPLETE {
+            return Err(FramebufferError::FramebufferIncomplete(status).into());
+        }
+
+        Ok(framebuffer)
+    }
+
+    /// ID of the framebuffer in OpenGL.
+    pub fn id(&self) -> FramebufferID {
+        self.id
+    }
+
+    /// Texture the framebuffer renders to.
+    pub fn texture(&self) -> &Texture {
+        &self.texture
+    }
+
+    /// Size of the framebuffer in pixels.
+    pub fn size(&self) -> Vector2u {
+        self.texture.size()
+    }
+
+    /// Recreates the framebuffer with a new size. Contents are lost.
+    ///
+    /// Does nothing if the size did not change.
+    pub fn resize(&mut self, width: u32, height: u32) -> Result<(), Error> {
+        if self.size() != Vector2u::new(width, height) {
+            *self = Self::new(width, height)?;
+        }
+
+        Ok(())
+    }
+
+    /// Use this framebuffer as the render target.
+    pub fn bind(&self) {
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.id);
+        }
+    }
+
+    /// Use the window's default framebuffer as the render target.
+    pub fn bind_default() {
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+    }
+
+    /// Reads the contents of the framebuffer to CPU memory.
+    pub fn read_pixels(&self) -> Result<RgbaImage, Error> {
+        self.bind();
+        let image = read_bound_pixels(self.size());
+        Self::bind_default();
+
+        image
+    }
+
+    /// Reads the frame being rendered to the window's back buffer.
+    ///
+    /// Must be called before buffers are swapped, after which their contents are undefined.
+    ///
+    /// `size` is the size of the window in pixels.
+    pub fn read_default_pixels(size: Vector2u) -> Result<RgbaImage, Error> {
+        Self::bind_default();
+
+        unsafe { gl::ReadBuffer(gl::BACK) }
+        read_bound_pixels(size)
+    }
+}
+
+/// Reads pixels from the bound framebuffer, flipped so that the first row is the top of the image.
+fn read_bound_pixels(size: Vector2u) -> Result<RgbaImage, Error> {
+    let mut data = vec![0u8; (4 * size.x * size.y) as usize];
+
+    unsafe {
+        gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+        gl::ReadPixels(
+            0,
+            0,
+            size.x as gl::types::GLsizei,
+            size.y as gl::types::GLsizei,
+            gl::RGBA,
+            gl::UNSIGNED_BYTE,
+            data.as_mut_ptr() as *mut gl::types::GLvoid,
+        );
+    }
+
+    //OpenGL rows start at the bottom
+    let image = RgbaImage::from_raw(size.x, size.y, data)
+        .ok_or(FramebufferError::ReadPixelsFailed(size.x, size.y))?;
+
+    Ok(image::imageops::flip_vertical(&image))
+}
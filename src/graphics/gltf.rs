@@ -0,0 +1,891 @@
+//! Loader for glTF 2.0 files, as JSON (.gltf) or binary (.glb).
+//!
+//! Buffers and images can be base64 data URIs, external files, or the binary chunk of a GLB.
+//! Triangle primitives are loaded with their normals, tangents and 2 UV sets, computing
+//! flat normals and tangents when missing, and metallic-roughness materials with their base color
+//! and normal textures.
+//! Skins, morph targets, animations, cameras and extensions are ignored.
+
+use super::{
+    mesh::{LitVertex, MeshBuilder},
+    model::{
+        self, MaterialDescription, Model, ModelData, ModelError, ModelMesh, ModelOptions,
+        ModelPrimitive,
+    },
+    textures::Texture,
+};
+use assets::Asset;
+use cgmath::{self, Zero};
+use failure::Error;
+use maths::{Matrix4f, Vector2f, Vector3f, Vector4f};
+use serde_json;
+use std::{cmp, collections::HashMap};
+use transform::Transform;
+
+/// First bytes of a GLB file.
+pub(crate) const GLB_MAGIC: &[u8] = b"glTF";
+
+/// Size of the GLB header: magic, version and length.
+const GLB_HEADER_SIZE: usize = 12;
+/// Size of the header of a GLB chunk: length and type.
+const GLB_CHUNK_HEADER_SIZE: usize = 8;
+const GLB_CHUNK_JSON: u32 = 0x4E4F_534A;
+const GLB_CHUNK_BIN: u32 = 0x004E_4942;
+
+const MODE_TRIANGLES: u32 = 4;
+const MODE_TRIANGLE_STRIP: u32 = 5;
+const MODE_TRIANGLE_FAN: u32 = 6;
+
+const BYTE: u32 = 5120;
+const UNSIGNED_BYTE: u32 = 5121;
+const SHORT: u32 = 5122;
+const UNSIGNED_SHORT: u32 = 5123;
+const UNSIGNED_INT: u32 = 5125;
+const FLOAT: u32 = 5126;
+
+const BASE64_PREFIX: &str = ";base64,";
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GltfJson {
+    asset: AssetJson,
+    scene: Option<usize>,
+    #[serde(default)]
+    scenes: Vec<SceneJson>,
+    #[serde(default)]
+    nodes: Vec<NodeJson>,
+    #[serde(default)]
+    meshes: Vec<MeshJson>,
+    #[serde(default)]
+    accessors: Vec<AccessorJson>,
+    #[serde(default)]
+    buffer_views: Vec<BufferViewJson>,
+    #[serde(default)]
+    buffers: Vec<BufferJson>,
+    #[serde(default)]
+    materials: Vec<MaterialJson>,
+    #[serde(default)]
+    textures: Vec<TextureJson>,
+    #[serde(default)]
+    images: Vec<ImageJson>,
+}
+
+#[derive(Deserialize)]
+struct AssetJson {
+    version: String,
+}
+
+#[derive(Deserialize)]
+struct SceneJson {
+    #[serde(default)]
+    nodes: Vec<usize>,
+}
+
+#[derive(Deserialize)]
+struct NodeJson {
+    name: Option<String>,
+    #[serde(default)]
+    children: Vec<usize>,
+    mesh: Option<usize>,
+    /// Column-major transform matrix, replacing translation, rotation and scale.
+    matrix: Option<[f32; 16]>,
+    translation: Option<[f32; 3]>,
+    /// Quaternion, as x, y, z, w.
+    rotation: Option<[f32; 4]>,
+    scale: Option<[f32; 3]>,
+}
+
+#[derive(Deserialize)]
+struct MeshJson {
+    name: Option<String>,
+    primitives: Vec<PrimitiveJson>,
+}
+
+#[derive(Deserialize)]
+struct PrimitiveJson {
+    attributes: HashMap<String, usize>,
+    indices: Option<usize>,
+    material: Option<usize>,
+    #[serde(default = "default_mode")]
+    mode: u32,
+}
+
+fn default_mode() -> u32 {
+    MODE_TRIANGLES
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AccessorJson {
+    /// Accessors without buffer view are filled with zeros.
+    buffer_view: Option<usize>,
+    #[serde(default)]
+    byte_offset: usize,
+    component_type: u32,
+    #[serde(default)]
+    normalized: bool,
+    count: usize,
+    #[serde(rename = "type")]
+    kind: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BufferViewJson {
+    buffer: usize,
+    #[serde(default)]
+    byte_offset: usize,
+    byte_length: usize,
+    byte_stride: Option<usize>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BufferJson {
+    /// Missing for the binary chunk of a GLB.
+    uri: Option<String>,
+    byte_length: usize,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MaterialJson {
+    name: Option<String>,
+    #[serde(default)]
+    pbr_metallic_roughness: PbrJson,
+    normal_texture: Option<TextureInfoJson>,
+    emissive_factor: Option<[f32; 3]>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct PbrJson {
+    base_color_factor: Option<[f32; 4]>,
+    base_color_texture: Option<TextureInfoJson>,
+    metallic_factor: Option<f32>,
+    roughness_factor: Option<f32>,
+}
+
+#[derive(Deserialize)]
+struct TextureInfoJson {
+    index: usize,
+}
+
+#[derive(Deserialize)]
+struct TextureJson {
+    source: Option<usize>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ImageJson {
+    uri: Option<String>,
+    buffer_view: Option<usize>,
+}
+
+/// Values of an accessor, with `components` values per element.
+struct AccessorData {
+    values: Vec<f64>,
+    components: usize,
+}
+
+impl AccessorData {
+    fn len(&self) -> usize {
+        self.values.len() / self.components
+    }
+
+    fn get(&self, element: usize) -> &[f64] {
+        &self.values[element * self.components..(element + 1) * self.components]
+    }
+
+    fn vector2(&self, element: usize) -> Vector2f {
+        let values = self.get(element);
+        Vector2f::new(values[0] as f32, values[1] as f32)
+    }
+
+    fn vector3(&self, element: usize) -> Vector3f {
+        let values = self.get(element);
+        Vector3f::new(values[0] as f32, values[1] as f32, values[2] as f32)
+    }
+
+    fn vector4(&self, element: usize) -> Vector4f {
+        let values = self.get(element);
+        Vector4f::new(
+            values[0] as f32,
+            values[1] as f32,
+            values[2] as f32,
+            values[3] as f32,
+        )
+    }
+}
+
+/// Loads a model from the contents of a .gltf or .glb file.
+pub(crate) fn load(data: &[u8], options: &ModelOptions) -> Result<Model, Error> {
+    let (json, binary) = if data.starts_with(GLB_MAGIC) {
+        parse_glb(data)?
+    } else {
+        (data, None)
+    };
+
+    let gltf: GltfJson = serde_json::from_slice(json)?;
+    if !gltf.asset.version.starts_with("2.") {
+        return Err(ModelError::UnsupportedGltfVersion(gltf.asset.version).into());
+    }
+
+    let mut buffers = Vec::with_capacity(gltf.buffers.len());
+    for (index, buffer) in gltf.buffers.iter().enumerate() {
+        buffers.push(load_buffer(index, buffer, binary, options)?);
+    }
+
+    let mut model = ModelData::new();
+
+    let mut images = Vec::with_capacity(gltf.images.len());
+    for image in &gltf.images {
+        let data = match (&image.uri, image.buffer_view) {
+            (Some(uri), _) => load_uri(uri, options)?,
+            (None, Some(view)) => view_data(&gltf, &buffers, view)?.to_vec(),
+            (None, None) => return Err(invalid("image without uri or buffer view")),
+        };
+
+        let texture = Texture::load_from_bytes(&data, options.texture_options)?;
+        images.push(model.add_texture(texture));
+    }
+
+    let mut materials = Vec::with_capacity(gltf.materials.len());
+    for material in &gltf.materials {
+        let pbr = &material.pbr_metallic_roughness;
+        let mut description =
+            MaterialDescription::new(material.name.as_ref().map_or("", String::as_str));
+
+        if let Some(color) = pbr.base_color_factor {
+            description.base_color = Vector4f::new(color[0], color[1], color[2], color[3]);
+        }
+        if let Some(emissive) = material.emissive_factor {
+            description.emissive = Vector3f::new(emissive[0], emissive[1], emissive[2]);
+        }
+        description.metallic = pbr.metallic_factor.unwrap_or(1.0);
+        description.roughness = pbr.roughness_factor.unwrap_or(1.0);
+        description.base_color_texture =
+            texture_image(&gltf, &images, pbr.base_color_texture.as_ref())?;
+        description.normal_texture =
+            texture_image(&gltf, &images, material.normal_texture.as_ref())?;
+
+        materials.push(model.add_material(&description, options)?);
+    }
+
+    for mesh in &gltf.meshes {
+        let mut primitives = Vec::with_capacity(mesh.primitives.len());
+
+        for primitive in &mesh.primitives {
+            let builder = read_primitive(&gltf, &buffers, primitive)?;
+
+            let material = match primitive.material {
+                Some(material) => *materials
+                    .get(material)
+                    .ok_or_else(|| invalid(&format!("material {} does not exist", material)))?,
+                None => model.default_material(options)?,
+            };
+
+            primitives.push(ModelPrimitive {
+                mesh: builder.build(),
+                material,
+            });
+        }
+
+        model.meshes.push(ModelMesh {
+            name: mesh.name.clone().unwrap_or_default(),
+            primitives,
+        });
+    }
+
+    for node in &gltf.nodes {
+        if let Some(mesh) = node.mesh {
+            if mesh >= gltf.meshes.len() {
+                return Err(invalid(&format!("mesh {} does not exist", mesh)));
+            }
+        }
+
+        let name = node.name.as_ref().map_or("", String::as_str);
+        model.add_node(name, node_transform(node), node.mesh);
+    }
+
+    for (index, node) in gltf.nodes.iter().enumerate() {
+        for &child in &node.children {
+            if child == index {
+                return Err(invalid(&format!("node {} is its own child", child)));
+            }
+
+            match model.nodes.get(child).map(|child| child.parent) {
+                Some(None) => model.nodes[child].parent = Some(index),
+                Some(Some(_)) => return Err(invalid(&format!("node {} has 2 parents", child))),
+                None => return Err(invalid(&format!("node {} does not exist", child))),
+            }
+
+            model.nodes[index].children.push(child);
+        }
+    }
+
+    //With a single parent per node, a cycle is a parent chain longer than the node count
+    for index in 0..model.nodes.len() {
+        let mut parent = model.nodes[index].parent;
+        let mut depth = 0;
+
+        while let Some(node) = parent {
+            depth += 1;
+            if depth > model.nodes.len() {
+                return Err(invalid(&format!("node {} is its own ancestor", index)));
+            }
+
+            parent = model.nodes[node].parent;
+        }
+    }
+
+    //Without scenes, every node without parent is a root
+    let scene = gltf.scene.or_else(|| {
+        if gltf.scenes.is_empty() {
+            None
+        } else {
+            Some(0)
+        }
+    });
+    let roots = match scene {
+        Some(scene) => {
+            let scene = gltf
+                .scenes
+                .get(scene)
+                .ok_or_else(|| invalid(&format!("scene {} does not exist", scene)))?;
+            if scene.nodes.iter().any(|&node| node >= gltf.nodes.len()) {
+                return Err(invalid("scene references a node that does not exist"));
+            }
+            if let Some(&node) = scene
+                .nodes
+                .iter()
+                .find(|&&node| model.nodes[node].parent.is_some())
+            {
+                return Err(invalid(&format!("scene root {} has a parent", node)));
+            }
+
+            Some(scene.nodes.clone())
+        }
+        None => None,
+    };
+
+    Ok(model.into_model(roots))
+}
+
+/// Splits a GLB file into its JSON and binary chunks.
+fn parse_glb(data: &[u8]) -> Result<(&[u8], Option<&[u8]>), Error> {
+    if data.len() < GLB_HEADER_SIZE {
+        return Err(invalid("GLB header is truncated"));
+    }
+
+    let version = read_u32(&data[4..8]);
+    if version != 2 {
+        return Err(ModelError::UnsupportedGltfVersion(version.to_string()).into());
+    }
+
+    let length = cmp::min(read_u32(&data[8..12]) as usize, data.len());
+    let mut offset = GLB_HEADER_SIZE;
+    let mut json = None;
+    let mut binary = None;
+
+    while offset + GLB_CHUNK_HEADER_SIZE <= length {
+        let chunk_length = read_u32(&data[offset..offset + 4]) as usize;
+        let chunk_type = read_u32(&data[offset + 4..offset + 8]);
+        let start = offset + GLB_CHUNK_HEADER_SIZE;
+
+        let chunk = data
+            .get(start..start + chunk_length)
+            .ok_or_else(|| invalid("GLB chunk is truncated"))?;
+
+        match chunk_type {
+            GLB_CHUNK_JSON if json.is_none() => json = Some(chunk),
+            GLB_CHUNK_BIN if binary.is_none() => binary = Some(chunk),
+            //Unknown chunks must be ignored
+            _ => {}
+        }
+
+        offset = start + chunk_length;
+    }
+
+    match json {
+        Some(json) => Ok((json, binary)),
+        None => Err(invalid("GLB has no JSON chunk")),
+    }
+}
+
+/// Loads the data of a buffer. Only the first buffer of a GLB can be its binary chunk.
+fn load_buffer(
+    index: usize,
+    buffer: &BufferJson,
+    binary: Option<&[u8]>,
+    options: &ModelOptions,
+) -> Result<Vec<u8>, Error> {
+    let data = match (&buffer.uri, binary) {
+        (Some(uri), _) => load_uri(uri, options)?,
+        (None, Some(binary)) if index == 0 => binary.to_vec(),
+        (None, _) => return Err(invalid(&format!("buffer {} has no uri", index))),
+    };
+
+    if data.len() < buffer.byte_length {
+        return Err(invalid(&format!(
+            "buffer {} is shorter than its byte length",
+            index
+        )));
+    }
+
+    Ok(data)
+}
+
+/// Loads a base64 data URI, or an external file relative to the model.
+fn load_uri(uri: &str, options: &ModelOptions) -> Result<Vec<u8>, Error> {
+    if uri.starts_with("data:") {
+        match uri.find(BASE64_PREFIX) {
+            Some(start) => decode_base64(&uri[start + BASE64_PREFIX.len()..]),
+            None => Err(invalid("data URI is not base64")),
+        }
+    } else {
+        options.read(&decode_percent(uri))
+    }
+}
+
+/// Bytes of a buffer view.
+fn view_data<'a>(gltf: &GltfJson, buffers: &'a [Vec<u8>], index: usize) -> Result<&'a [u8], Error> {
+    let view = gltf
+        .buffer_views
+        .get(index)
+        .ok_or_else(|| invalid(&format!("buffer view {} does not exist", index)))?;
+    let buffer = buffers
+        .get(view.buffer)
+        .ok_or_else(|| invalid(&format!("buffer {} does not exist", view.buffer)))?;
+
+    buffer
+        .get(view.byte_offset..view.byte_offset + view.byte_length)
+        .ok_or_else(|| invalid(&format!("buffer view {} is out of bounds", index)))
+}
+
+/// Reads the elements of an accessor, converting normalized integers to floats.
+fn read_accessor(
+    gltf: &GltfJson,
+    buffers: &[Vec<u8>],
+    index: usize,
+) -> Result<AccessorData, Error> {
+    let accessor = gltf
+        .accessors
+        .get(index)
+        .ok_or_else(|| invalid(&format!("accessor {} does not exist", index)))?;
+
+    let components = match accessor.kind.as_str() {
+        "SCALAR" => 1,
+        "VEC2" => 2,
+        "VEC3" => 3,
+        "VEC4" => 4,
+        kind => return Err(invalid(&format!("unsupported accessor type {}", kind))),
+    };
+    let size = match accessor.component_type {
+        BYTE | UNSIGNED_BYTE => 1,
+        SHORT | UNSIGNED_SHORT => 2,
+        UNSIGNED_INT | FLOAT => 4,
+        component_type => {
+            return Err(invalid(&format!(
+                "unsupported component type {}",
+                component_type
+            )))
+        }
+    };
+
+    let view = match accessor.buffer_view {
+        Some(view) => view,
+        None => {
+            return Ok(AccessorData {
+                values: vec![0.0; accessor.count * components],
+                components,
+            })
+        }
+    };
+
+    let data = view_data(gltf, buffers, view)?;
+    let stride = gltf.buffer_views[view]
+        .byte_stride
+        .unwrap_or(components * size);
+
+    let mut values = Vec::with_capacity(accessor.count * components);
+    for element in 0..accessor.count {
+        let start = accessor.byte_offset + element * stride;
+        let bytes = data
+            .get(start..start + components * size)
+            .ok_or_else(|| invalid(&format!("accessor {} is out of bounds", index)))?;
+
+        for component in bytes.chunks(size) {
+            values.push(read_component(
+                component,
+                accessor.component_type,
+                accessor.normalized,
+            ));
+        }
+    }
+
+    Ok(AccessorData { values, components })
+}
+
+fn read_component(bytes: &[u8], component_type: u32, normalized: bool) -> f64 {
+    let value = match component_type {
+        BYTE => f64::from(bytes[0] as i8),
+        UNSIGNED_BYTE => f64::from(bytes[0]),
+        SHORT => f64::from(read_u32(bytes) as u16 as i16),
+        UNSIGNED_SHORT => f64::from(read_u32(bytes) as u16),
+        UNSIGNED_INT => f64::from(read_u32(bytes)),
+        _ => f64::from(f32::from_bits(read_u32(bytes))),
+    };
+
+    if !normalized {
+        return value;
+    }
+
+    match component_type {
+        BYTE => (value / f64::from(i8::max_value())).max(-1.0),
+        UNSIGNED_BYTE => value / f64::from(u8::max_value()),
+        SHORT => (value / f64::from(i16::max_value())).max(-1.0),
+        UNSIGNED_SHORT => value / f64::from(u16::max_value()),
+        _ => value,
+    }
+}
+
+/// Reads an attribute of a primitive, which must have one value per vertex.
+fn read_attribute(
+    gltf: &GltfJson,
+    buffers: &[Vec<u8>],
+    primitive: &PrimitiveJson,
+    name: &str,
+    components: usize,
+    vertex_count: usize,
+) -> Result<Option<AccessorData>, Error> {
+    let accessor = match primitive.attributes.get(name) {
+        Some(&accessor) => accessor,
+        None => return Ok(None),
+    };
+
+    let data = read_accessor(gltf, buffers, accessor)?;
+    if data.components < components || data.len() != vertex_count {
+        return Err(invalid(&format!("invalid {} attribute", name)));
+    }
+
+    Ok(Some(data))
+}
+
+/// Reads the vertices and triangles of a primitive.
+fn read_primitive(
+    gltf: &GltfJson,
+    buffers: &[Vec<u8>],
+    primitive: &PrimitiveJson,
+) -> Result<MeshBuilder<LitVertex>, Error> {
+    let positions = match primitive.attributes.get("POSITION") {
+        Some(&accessor) => read_accessor(gltf, buffers, accessor)?,
+        None => return Err(invalid("primitive has no POSITION attribute")),
+    };
+    if positions.components < 3 {
+        return Err(invalid("invalid POSITION attribute"));
+    }
+
+    let count = positions.len();
+    let normals = read_attribute(gltf, buffers, primitive, "NORMAL", 3, count)?;
+    let tangents = read_attribute(gltf, buffers, primitive, "TANGENT", 4, count)?;
+    let uvs = read_attribute(gltf, buffers, primitive, "TEXCOORD_0", 2, count)?;
+    let uvs2 = read_attribute(gltf, buffers, primitive, "TEXCOORD_1", 2, count)?;
+
+    let mut builder = MeshBuilder::new();
+
+    for vertex in 0..count {
+        let uv = uvs
+            .as_ref()
+            .map_or(Vector2f::zero(), |uvs| uvs.vector2(vertex));
+
+        builder.vertices.push(LitVertex {
+            position: positions.vector3(vertex),
+            uv,
+            normal: normals
+                .as_ref()
+                .map_or(Vector3f::zero(), |normals| normals.vector3(vertex)),
+            tangent: tangents
+                .as_ref()
+                .map_or(model::DEFAULT_TANGENT, |tangents| tangents.vector4(vertex)),
+            uv2: uvs2.as_ref().map_or(uv, |uvs2| uvs2.vector2(vertex)),
+        });
+    }
+
+    let indices: Vec<u32> = match primitive.indices {
+        Some(accessor) => read_accessor(gltf, buffers, accessor)?
+            .values
+            .iter()
+            .map(|&index| index as u32)
+            .collect(),
+        None => (0..count as u32).collect(),
+    };
+    if indices.iter().any(|&index| index as usize >= count) {
+        return Err(invalid("primitive index out of bounds"));
+    }
+
+    builder.indices = match primitive.mode {
+        MODE_TRIANGLES => indices,
+        MODE_TRIANGLE_STRIP => (0..indices.len().saturating_sub(2))
+            .flat_map(|i| {
+                //Every other triangle is reversed to keep the winding order
+                if i % 2 == 0 {
+                    vec![indices[i], indices[i + 1], indices[i + 2]]
+                } else {
+                    vec![indices[i + 1], indices[i], indices[i + 2]]
+                }
+            })
+            .collect(),
+        MODE_TRIANGLE_FAN => (1..indices.len().saturating_sub(1))
+            .flat_map(|i| vec![indices[0], indices[i], indices[i + 1]])
+            .collect(),
+        mode => return Err(ModelError::UnsupportedPrimitiveMode(mode).into()),
+    };
+
+    //The specification requires flat normals when they are missing
+    if normals.is_none() {
+        builder.compute_flat_normals();
+    }
+    if tangents.is_none() {
+        builder.compute_tangents();
+    }
+
+    Ok(builder)
+}
+
+/// Index in the model of the image of a texture.
+fn texture_image(
+    gltf: &GltfJson,
+    images: &[usize],
+    info: Option<&TextureInfoJson>,
+) -> Result<Option<usize>, Error> {
+    let index = match info {
+        Some(info) => info.index,
+        None => return Ok(None),
+    };
+
+    let texture = gltf
+        .textures
+        .get(index)
+        .ok_or_else(|| invalid(&format!("texture {} does not exist", index)))?;
+
+    match texture.source {
+        Some(source) => match images.get(source) {
+            Some(&image) => Ok(Some(image)),
+            None => Err(invalid(&format!("image {} does not exist", source))),
+        },
+        //Textures can use images from extensions only
+        None => Ok(None),
+    }
+}
+
+fn node_transform(node: &NodeJson) -> Transform {
+    if let Some(values) = node.matrix {
+        let mut matrix = Matrix4f::zero();
+        for (index, &value) in values.iter().enumerate() {
+            matrix[index / 4][index % 4] = value;
+        }
+
+        return model::decompose_matrix(matrix);
+    }
+
+    let mut transform = Transform::new();
+
+    if let Some(translation) = node.translation {
+        transform.position = Vector3f::new(translation[0], translation[1], translation[2]);
+    }
+    if let Some(rotation) = node.rotation {
+        let quaternion =
+            cgmath::Quaternion::new(rotation[3], rotation[0], rotation[1], rotation[2]);
+        transform.rotation = model::quaternion_to_euler(quaternion);
+    }
+    if let Some(scale) = node.scale {
+        transform.scale = Vector3f::new(scale[0], scale[1], scale[2]);
+    }
+
+    transform
+}
+
+/// Decodes standard or URL-safe base64, with or without padding.
+fn decode_base64(text: &str) -> Result<Vec<u8>, Error> {
+    let mut data = Vec::with_capacity(text.len() * 3 / 4);
+    let mut bits = 0u32;
+    let mut bit_count = 0;
+
+    for byte in text.bytes() {
+        let value = match byte {
+            b'A'..=b'Z' => byte - b'A',
+            b'a'..=b'z' => byte - b'a' + 26,
+            b'0'..=b'9' => byte - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            b'=' => break,
+            _ => return Err(invalid("invalid base64 data")),
+        };
+
+        bits = (bits << 6) | u32::from(value);
+        bit_count += 6;
+
+        if bit_count >= 8 {
+            bit_count -= 8;
+            data.push((bits >> bit_count) as u8);
+            bits &= (1 << bit_count) - 1;
+        }
+    }
+
+    Ok(data)
+}
+
+/// Decodes `%XX` escapes of a relative URI.
+fn decode_percent(uri: &str) -> String {
+    let bytes = uri.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+
+    while index < bytes.len() {
+        let escaped = if bytes[index] == b'%' {
+            uri.get(index + 1..index + 3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+        } else {
+            None
+        };
+
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                index += 3;
+            }
+            None => {
+                decoded.push(bytes[index]);
+                index += 1;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Reads a little-endian integer of up to 4 bytes.
+fn read_u32(bytes: &[u8]) -> u32 {
+    bytes
+        .iter()
+        .rev()
+        .fold(0, |value, &byte| (value << 8) | u32::from(byte))
+}
+
+fn invalid(reason: &str) -> Error {
+    ModelError::InvalidGltf(reason.to_owned()).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// GLB file with the given chunks, as (type, data) pairs.
+    fn glb(chunks: &[(u32, &[u8])]) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend_from_slice(GLB_MAGIC);
+        data.extend_from_slice(&[2, 0, 0, 0]);
+        data.extend_from_slice(&[0; 4]);
+
+        for &(chunk_type, chunk) in chunks {
+            data.extend_from_slice(&(chunk.len() as u32).to_le_bytes());
+            data.extend_from_slice(&chunk_type.to_le_bytes());
+            data.extend_from_slice(chunk);
+        }
+
+        let length = (data.len() as u32).to_le_bytes();
+        data[8..12].copy_from_slice(&length);
+        data
+    }
+
+    #[test]
+    fn glb_chunks_are_split() {
+        let data = glb(&[
+            (GLB_CHUNK_JSON, &b"{}  "[..]),
+            (0x1234_5678, &b"skip"[..]),
+            (GLB_CHUNK_BIN, &[1, 2, 3, 4][..]),
+        ]);
+
+        let (json, binary) = parse_glb(&data).unwrap();
+        assert_eq!(json, b"{}  ");
+        assert_eq!(binary, Some(&[1u8, 2, 3, 4][..]));
+    }
+
+    #[test]
+    fn glb_without_binary_chunk() {
+        let data = glb(&[(GLB_CHUNK_JSON, &b"{}  "[..])]);
+
+        assert_eq!(parse_glb(&data).unwrap(), (&b"{}  "[..], None));
+    }
+
+    #[test]
+    fn invalid_glb_is_rejected() {
+        assert!(parse_glb(b"glTF").is_err());
+        assert!(parse_glb(&glb(&[(GLB_CHUNK_BIN, &[0; 4][..])])).is_err());
+
+        let mut version_1 = glb(&[(GLB_CHUNK_JSON, &b"{}  "[..])]);
+        version_1[4] = 1;
+        assert!(parse_glb(&version_1).is_err());
+
+        let mut truncated = glb(&[(GLB_CHUNK_JSON, &b"{}  "[..])]);
+        truncated.truncate(truncated.len() - 1);
+        assert!(parse_glb(&truncated).is_err());
+    }
+
+    #[test]
+    fn base64_is_decoded() {
+        assert_eq!(decode_base64("").unwrap(), b"");
+        assert_eq!(decode_base64("TWFu").unwrap(), b"Man");
+        assert_eq!(decode_base64("TWE=").unwrap(), b"Ma");
+        assert_eq!(decode_base64("TQ==").unwrap(), b"M");
+        assert_eq!(decode_base64("TQ").unwrap(), b"M");
+    }
+
+    #[test]
+    fn url_safe_base64_is_decoded() {
+        assert_eq!(
+            decode_base64("-_8=").unwrap(),
+            decode_base64("+/8=").unwrap()
+        );
+        assert_eq!(decode_base64("+/8=").unwrap(), vec![0xFB, 0xFF]);
+    }
+
+    #[test]
+    fn invalid_base64_is_rejected() {
+        assert!(decode_base64("TW Fu").is_err());
+        assert!(decode_base64("TWF*").is_err());
+    }
+
+    #[test]
+    fn components_are_little_endian() {
+        assert_eq!(read_component(&[0xFF], BYTE, false), -1.0);
+        assert_eq!(read_component(&[0xFF], UNSIGNED_BYTE, false), 255.0);
+        assert_eq!(read_component(&[0x00, 0x80], SHORT, false), -32768.0);
+        assert_eq!(read_component(&[0x34, 0x12], UNSIGNED_SHORT, false), 4660.0);
+        assert_eq!(
+            read_component(&[0x78, 0x56, 0x34, 0x12], UNSIGNED_INT, false),
+            305_419_896.0
+        );
+        assert_eq!(
+            read_component(&1.5f32.to_bits().to_le_bytes(), FLOAT, false),
+            1.5
+        );
+    }
+
+    #[test]
+    fn normalized_components_are_scaled() {
+        assert_eq!(read_component(&[0xFF], UNSIGNED_BYTE, true), 1.0);
+        assert_eq!(read_component(&[0x7F], BYTE, true), 1.0);
+        //Both -128 and -127 map to -1
+        assert_eq!(read_component(&[0x80], BYTE, true), -1.0);
+        assert_eq!(read_component(&[0x81], BYTE, true), -1.0);
+        assert_eq!(read_component(&[0xFF, 0xFF], UNSIGNED_SHORT, true), 1.0);
+        assert_eq!(read_component(&[0x00, 0x80], SHORT, true), -1.0);
+        assert_eq!(
+            read_component(&0.25f32.to_bits().to_le_bytes(), FLOAT, true),
+            0.25
+        );
+    }
+}
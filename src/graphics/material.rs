@@ -0,0 +1,165 @@
+use super::{
+    shaders::{Program, ShaderError, UniformValue},
+    textures::{BindableTexture, TextureID, TextureTarget},
+};
+use failure::Error;
+use gl;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Maximum number of textures in a material.
+///
+/// This is the minimum number of texture units OpenGL guarantees per shader stage.
+pub const MAX_MATERIAL_TEXTURES: usize = 16;
+
+/// Source of unique material IDs.
+static NEXT_MATERIAL_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// Errors related to materials.
+#[derive(Debug, Fail)]
+pub enum MaterialError {
+    /// Tried adding more than `MAX_MATERIAL_TEXTURES` textures.
+    #[fail(display = "Materials cannot have more than {} textures", _0)]
+    TooManyTextures(usize),
+}
+
+/// Identifies the contents of a `Material`.
+///
+/// Materials with the same ID draw the same way, and can be batched together.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct MaterialID(usize);
+
+impl MaterialID {
+    fn next() -> Self {
+        MaterialID(NEXT_MATERIAL_ID.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+/// Texture bound to a named sampler of a material.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct TextureSlot {
+    pub id: TextureID,
+    pub target: TextureTarget,
+}
+
+/// Describes how to draw something: a shader program, the textures bound
+/// to its samplers, and values for its other uniforms.
+///
+/// Textures are referenced by ID, so they must outlive the draw calls using the material.
+#[derive(Debug, Clone)]
+pub struct Material {
+    id: MaterialID,
+    program: Program,
+    textures: Vec<(String, TextureSlot)>,
+    uniforms: Vec<(String, UniformValue)>,
+}
+
+impl Material {
+    /// Creates a material without textures or uniforms.
+    pub fn new(program: Program) -> Self {
+        Self {
+            id: MaterialID::next(),
+            program,
+            textures: Vec::new(),
+            uniforms: Vec::new(),
+        }
+    }
+
+    /// Identifies the current contents of the material.
+    ///
+    /// Changing the material gives it a new ID, while clones keep the ID until they are changed.
+    pub fn id(&self) -> MaterialID {
+        self.id
+    }
+
+    pub fn program(&self) -> &Program {
+        &self.program
+    }
+
+    pub fn set_program(&mut self, program: Program) {
+        self.program = program;
+        self.id = MaterialID::next();
+    }
+
+    /// Binds a texture to the sampler uniform `name`, replacing the previous one.
+    pub fn set_texture(&mut self, name: &str, texture: &dyn BindableTexture) -> Result<(), Error> {
+        let slot = TextureSlot {
+            id: texture.id(),
+            target: texture.target(),
+        };
+
+        match self.textures.iter().position(|(n, _)| n == name) {
+            Some(index) => self.textures[index].1 = slot,
+            None if self.textures.len() >= MAX_MATERIAL_TEXTURES => {
+                return Err(MaterialError::TooManyTextures(MAX_MATERIAL_TEXTURES).into())
+            }
+            None => self.textures.push((name.to_owned(), slot)),
+        }
+
+        self.id = MaterialID::next();
+        Ok(())
+    }
+
+    /// Texture bound to the sampler uniform `name`.
+    pub fn texture(&self, name: &str) -> Option<TextureSlot> {
+        self.textures
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|&(_, slot)| slot)
+    }
+
+    pub fn remove_texture(&mut self, name: &str) {
+        self.textures.retain(|(n, _)| n != name);
+        self.id = MaterialID::next();
+    }
+
+    /// Sets a uniform value, used every time the material is drawn.
+    ///
+    /// Fails if the uniform has another type in the program.
+    pub fn set_uniform(&mut self, name: &str, value: UniformValue) -> Result<(), ShaderError> {
+        self.program.check_uniform(name, value.glsl_type())?;
+
+        match self.uniforms.iter().position(|(n, _)| n == name) {
+            Some(index) => self.uniforms[index].1 = value,
+            None => self.uniforms.push((name.to_owned(), value)),
+        }
+
+        self.id = MaterialID::next();
+        Ok(())
+    }
+
+    pub fn uniform(&self, name: &str) -> Option<UniformValue> {
+        self.uniforms
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|&(_, value)| value)
+    }
+
+    pub fn remove_uniform(&mut self, name: &str) {
+        self.uniforms.retain(|(n, _)| n != name);
+        self.id = MaterialID::next();
+    }
+
+    /// Uses the program, binds textures to consecutive texture units and sets uniforms.
+    ///
+    /// Fails if a uniform has another type in the program.
+    pub(crate) fn bind(&self) -> Result<(), Error> {
+        self.program.set_used();
+
+        for (unit, (name, slot)) in self.textures.iter().enumerate() {
+            unsafe {
+                gl::ActiveTexture(gl::TEXTURE0 + unit as gl::types::GLenum);
+                gl::BindTexture(slot.target as gl::types::GLenum, slot.id);
+            }
+
+            self.program.set_int(name, unit as i32)?;
+        }
+
+        unsafe { gl::ActiveTexture(gl::TEXTURE0) }
+
+        for (name, value) in &self.uniforms {
+            self.program.set_uniform(name, *value)?;
+        }
+
+        Ok(())
+    }
+}
@@ -1,12 +1,171 @@
+use cgmath::{self, InnerSpace, Matrix, SquareMatrix, Zero};
 use gl;
-use maths::{Vector2f, Vector3f};
-use std::{cmp, mem, ptr};
+use maths::{Matrix4f, Vector2f, Vector3f, Vector4f};
+use std::{cmp, collections::HashMap, f32, mem, ptr};
+use transform::Transform;
 
 //Max amount of instances in a batch
 pub const MAX_BATCH_SIZE: usize = 1000;
 
-//Size of 1 object in the VBO, in floats. 16: matrix, 4: tex coordinates
-pub const BATCH_INSTANCE_SIZE: usize = 20;
+//Size of 1 object in the VBO, in floats. 16: matrix, 4 each: tex coordinates, tex parameters, color
+pub const BATCH_INSTANCE_SIZE: usize = 28;
+
+/// Attribute locations used by the built-in vertex formats and shaders.
+///
+/// Vertex attributes use locations 0 to 7, and per-instance attributes 8 to 14.
+pub mod locations {
+    use gl::types::GLuint;
+
+    pub const POSITION: GLuint = 0;
+    pub const UV: GLuint = 1;
+    pub const NORMAL: GLuint = 2;
+    /// Tangent, with the handedness of the bitangent in `w`.
+    pub const TANGENT: GLuint = 3;
+    /// Vertex color, white in the base programs for formats without one.
+    pub const COLOR: GLuint = 4;
+    /// Second UV set, e.g. for lightmaps.
+    pub const UV2: GLuint = 5;
+    /// Indices of the 4 joints influencing a skinned vertex.
+    pub const JOINTS: GLuint = 6;
+    /// Weights of the 4 joints influencing a skinned vertex.
+    pub const WEIGHTS: GLuint = 7;
+
+    /// Area of the texture drawn by the instance.
+    pub const INSTANCE_TEX_POSITION: GLuint = 8;
+    /// Instance transform matrix, one column per location from this one.
+    pub const INSTANCE_MATRIX: GLuint = 9;
+    /// Instance texture parameters: UV rotation and texture array layer.
+    pub const INSTANCE_TEX_PARAMETERS: GLuint = 13;
+    /// Instance color, multiplied with the texture.
+    pub const INSTANCE_COLOR: GLuint = 14;
+}
+
+/// Type of the components of a vertex attribute.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum AttributeType {
+    Float,
+    /// Integer attribute, read in shaders as `uvec`.
+    UnsignedShort,
+    /// Integer attribute, read in shaders as `uvec`.
+    UnsignedInt,
+}
+
+impl AttributeType {
+    /// Size of one component in bytes.
+    pub fn size(self) -> usize {
+        match self {
+            AttributeType::Float => mem::size_of::<f32>(),
+            AttributeType::UnsignedShort => mem::size_of::<u16>(),
+            AttributeType::UnsignedInt => mem::size_of::<u32>(),
+        }
+    }
+
+    fn gl_type(self) -> gl::types::GLenum {
+        match self {
+            AttributeType::Float => gl::FLOAT,
+            AttributeType::UnsignedShort => gl::UNSIGNED_SHORT,
+            AttributeType::UnsignedInt => gl::UNSIGNED_INT,
+        }
+    }
+}
+
+/// One attribute of a vertex format.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct VertexAttribute {
+    pub location: gl::types::GLuint,
+    /// Number of components, from 1 to 4.
+    pub components: i32,
+    pub attribute_type: AttributeType,
+    /// Offset from the start of the vertex, in bytes.
+    pub offset: usize,
+}
+
+/// Describes how the fields of a vertex are laid out in memory.
+///
+/// Attributes are added in the order of the fields, each right after the previous one,
+/// as in a `#[repr(C)]` struct without padding.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Hash)]
+pub struct VertexLayout {
+    attributes: Vec<VertexAttribute>,
+    size: usize,
+}
+
+impl VertexLayout {
+    pub fn new() -> Self {
+        Self {
+            attributes: Vec::new(),
+            size: 0,
+        }
+    }
+
+    /// Adds the next field as an attribute at `location`.
+    pub fn with(
+        mut self,
+        location: gl::types::GLuint,
+        components: i32,
+        attribute_type: AttributeType,
+    ) -> Self {
+        self.attributes.push(VertexAttribute {
+            location,
+            components,
+            attribute_type,
+            offset: self.size,
+        });
+        self.size += components as usize * attribute_type.size();
+        self
+    }
+
+    /// Skips the next field, which is not passed to shaders.
+    pub fn skip(mut self, bytes: usize) -> Self {
+        self.size += bytes;
+        self
+    }
+
+    pub fn attributes(&self) -> &[VertexAttribute] {
+        &self.attributes
+    }
+
+    /// Size of a vertex in bytes.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Enables and describes the attributes in the bound VAO, for the bound VBO.
+    fn apply(&self, stride: usize) {
+        for attribute in &self.attributes {
+            unsafe {
+                gl::EnableVertexAttribArray(attribute.location);
+
+                let offset = attribute.offset as *const gl::types::GLvoid;
+                match attribute.attribute_type {
+                    AttributeType::Float => gl::VertexAttribPointer(
+                        attribute.location,
+                        attribute.components,
+                        gl::FLOAT,
+                        gl::FALSE,
+                        stride as gl::types::GLint,
+                        offset,
+                    ),
+                    integer => gl::VertexAttribIPointer(
+                        attribute.location,
+                        attribute.components,
+                        integer.gl_type(),
+                        stride as gl::types::GLint,
+                        offset,
+                    ),
+                }
+            }
+        }
+    }
+}
+
+/// Vertex type that can be used in a `MeshBuilder`.
+///
+/// Implementors should be `#[repr(C)]`, with a layout describing every field in order.
+/// Locations of the built-in formats are in `locations`.
+pub trait VertexFormat: Copy {
+    fn layout() -> VertexLayout;
+}
 
 #[derive(Debug, Fail)]
 pub enum MeshError {
@@ -25,29 +184,80 @@ pub struct Vertex {
     pub uv: Vector2f,
 }
 
-impl Vertex {
-    pub fn attrib_arrays() {
-        let stride = mem::size_of::<Self>();
+impl VertexFormat for Vertex {
+    fn layout() -> VertexLayout {
+        VertexLayout::new()
+            .with(locations::POSITION, 3, AttributeType::Float)
+            .with(locations::UV, 2, AttributeType::Float)
+    }
+}
 
-        unsafe {
-            //Position
-            Vertex::attrib_array(stride, 0, 0, 3);
-            //Color
-            Vertex::attrib_array(stride, 1, mem::size_of::<Vector3f>(), 2);
-        }
+/// Vertex with a color, multiplied with the texture by the base programs.
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct ColoredVertex {
+    pub position: Vector3f,
+    pub uv: Vector2f,
+    /// RGBA color.
+    pub color: Vector4f,
+}
+
+impl VertexFormat for ColoredVertex {
+    fn layout() -> VertexLayout {
+        VertexLayout::new()
+            .with(locations::POSITION, 3, AttributeType::Float)
+            .with(locations::UV, 2, AttributeType::Float)
+            .with(locations::COLOR, 4, AttributeType::Float)
     }
+}
 
-    unsafe fn attrib_array(stride: usize, location: gl::types::GLuint, offset: usize, length: i32) {
-        //Vertex location 0: Position
-        gl::EnableVertexAttribArray(location);
-        gl::VertexAttribPointer(
-            location, //Location
-            length,   //Number of components per vertex
-            gl::FLOAT,
-            gl::FALSE,                          //Normalize
-            stride as gl::types::GLint,         //Stride
-            offset as *const gl::types::GLvoid, //Offset
-        );
+/// Vertex of a lit model, with a normal, a tangent for normal mapping, and a second UV set.
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct LitVertex {
+    pub position: Vector3f,
+    pub uv: Vector2f,
+    pub normal: Vector3f,
+    /// Tangent, with the handedness of the bitangent in `w` (1 or -1).
+    pub tangent: Vector4f,
+    pub uv2: Vector2f,
+}
+
+impl VertexFormat for LitVertex {
+    fn layout() -> VertexLayout {
+        VertexLayout::new()
+            .with(locations::POSITION, 3, AttributeType::Float)
+            .with(locations::UV, 2, AttributeType::Float)
+            .with(locations::NORMAL, 3, AttributeType::Float)
+            .with(locations::TANGENT, 4, AttributeType::Float)
+            .with(locations::UV2, 2, AttributeType::Float)
+    }
+}
+
+/// Vertex of a lit model deformed by up to 4 joints of a skeleton.
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct SkinnedVertex {
+    pub position: Vector3f,
+    pub uv: Vector2f,
+    pub normal: Vector3f,
+    /// Tangent, with the handedness of the bitangent in `w` (1 or -1).
+    pub tangent: Vector4f,
+    /// Indices of the joints influencing the vertex.
+    pub joints: [u16; 4],
+    /// Weights of the joints, adding up to 1.
+    pub weights: Vector4f,
+}
+
+impl VertexFormat for SkinnedVertex {
+    fn layout() -> VertexLayout {
+        VertexLayout::new()
+            .with(locations::POSITION, 3, AttributeType::Float)
+            .with(locations::UV, 2, AttributeType::Float)
+            .with(locations::NORMAL, 3, AttributeType::Float)
+            .with(locations::TANGENT, 4, AttributeType::Float)
+            .with(locations::JOINTS, 4, AttributeType::UnsignedShort)
+            .with(locations::WEIGHTS, 4, AttributeType::Float)
     }
 }
 
@@ -120,27 +330,57 @@ impl Ord for Mesh {
     }
 }
 
-#[derive(Debug)]
-pub struct MeshBuilder {
-    pub vertices: Vec<Vertex>,
+/// Vertices and indices of a mesh, generic over the vertex format.
+#[derive(Debug, Clone)]
+pub struct MeshBuilder<V = Vertex> {
+    pub vertices: Vec<V>,
     pub indices: Vec<gl::types::GLuint>,
 }
 
-impl Default for MeshBuilder {
+impl<V: VertexFormat> Default for MeshBuilder<V> {
     fn default() -> Self {
         Self::new()
     }
 }
 
-impl MeshBuilder {
+impl<V: VertexFormat> MeshBuilder<V> {
     ///Initializes an empty MeshBuilder.
-    pub fn new() -> MeshBuilder {
+    pub fn new() -> Self {
         MeshBuilder {
             vertices: Vec::new(),
             indices: Vec::new(),
         }
     }
 
+    /// Triangles as indices in `vertices`.
+    ///
+    /// Incomplete triangles and triangles with indices out of range are skipped.
+    fn triangles<'a>(&'a self) -> impl Iterator<Item = [usize; 3]> + 'a {
+        let vertex_count = self.vertices.len();
+
+        self.indices.chunks(3).filter_map(move |triangle| {
+            if triangle.len() == 3 && triangle.iter().all(|&index| (index as usize) < vertex_count)
+            {
+                Some([
+                    triangle[0] as usize,
+                    triangle[1] as usize,
+                    triangle[2] as usize,
+                ])
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Appends the vertices and triangles of another builder.
+    pub fn merge(&mut self, other: &MeshBuilder<V>) {
+        let offset = self.vertices.len() as gl::types::GLuint;
+
+        self.vertices.extend_from_slice(&other.vertices);
+        self.indices
+            .extend(other.indices.iter().map(|index| index + offset));
+    }
+
     ///Builds a mesh from current vertices and indices.
     pub fn build(&self) -> Mesh {
         let mut vao: gl::types::GLuint = 0;
@@ -160,7 +400,7 @@ impl MeshBuilder {
             gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
             gl::BufferData(
                 gl::ARRAY_BUFFER,
-                (self.vertices.len() * mem::size_of::<Vertex>()) as gl::types::GLsizeiptr, //Data length
+                (self.vertices.len() * mem::size_of::<V>()) as gl::types::GLsizeiptr, //Data length
                 self.vertices.as_ptr() as *const gl::types::GLvoid, //Data location
                 gl::STATIC_DRAW,
             );
@@ -176,15 +416,23 @@ impl MeshBuilder {
         }
 
         //Fill VAO
-        Vertex::attrib_arrays();
+        let layout = V::layout();
+        debug_assert!(
+            layout.size() <= mem::size_of::<V>(),
+            "Vertex layout is larger than the vertex"
+        );
+        layout.apply(mem::size_of::<V>());
 
         //Create batch VBO
         let batch_vbo = Self::empty_vbo(MAX_BATCH_SIZE);
-        Self::add_instanced_attribute(vao, batch_vbo, 2, 4, BATCH_INSTANCE_SIZE as i32, 0); //texture coordinates
-        Self::add_instanced_attribute(vao, batch_vbo, 3, 4, BATCH_INSTANCE_SIZE as i32, 4); //1st column
-        Self::add_instanced_attribute(vao, batch_vbo, 4, 4, BATCH_INSTANCE_SIZE as i32, 8); //2nd column
-        Self::add_instanced_attribute(vao, batch_vbo, 5, 4, BATCH_INSTANCE_SIZE as i32, 12); //3rd column
-        Self::add_instanced_attribute(vao, batch_vbo, 6, 4, BATCH_INSTANCE_SIZE as i32, 16); //4th column
+        let stride = BATCH_INSTANCE_SIZE as i32;
+        Self::add_instanced_attribute(vao, batch_vbo, locations::INSTANCE_TEX_POSITION, 4, stride, 0); //texture coordinates
+        Self::add_instanced_attribute(vao, batch_vbo, locations::INSTANCE_MATRIX, 4, stride, 4); //1st column
+        Self::add_instanced_attribute(vao, batch_vbo, locations::INSTANCE_MATRIX + 1, 4, stride, 8); //2nd column
+        Self::add_instanced_attribute(vao, batch_vbo, locations::INSTANCE_MATRIX + 2, 4, stride, 12); //3rd column
+        Self::add_instanced_attribute(vao, batch_vbo, locations::INSTANCE_MATRIX + 3, 4, stride, 16); //4th column
+        Self::add_instanced_attribute(vao, batch_vbo, locations::INSTANCE_TEX_PARAMETERS, 4, stride, 20); //texture parameters
+        Self::add_instanced_attribute(vao, batch_vbo, locations::INSTANCE_COLOR, 4, stride, 24); //color
 
         //Unbind everything
         unsafe {
@@ -256,3 +504,368 @@ impl MeshBuilder {
         }
     }
 }
+
+/// Modeling utilities for lit vertices.
+impl MeshBuilder<LitVertex> {
+    /// Gives every triangle its own vertices, with the normal of the triangle.
+    ///
+    /// This makes edges look sharp, but triples the number of vertices.
+    /// Triangles with indices out of range are removed.
+    pub fn compute_flat_normals(&mut self) {
+        let mut vertices = Vec::with_capacity(self.indices.len());
+
+        for [a, b, c] in self.triangles() {
+            let mut corners = [self.vertices[a], self.vertices[b], self.vertices[c]];
+            let normal = normalize_or(
+                triangle_normal(&corners[0], &corners[1], &corners[2]),
+                Vector3f::unit_y(),
+            );
+
+            for corner in &mut corners {
+                corner.normal = normal;
+            }
+            vertices.extend_from_slice(&corners);
+        }
+
+        self.indices = (0..vertices.len() as gl::types::GLuint).collect();
+        self.vertices = vertices;
+    }
+
+    /// Sets the normal of every vertex to the average of the normals of its triangles,
+    /// weighted by their area.
+    ///
+    /// Vertices at the same position get the same normal, so UV seams do not show.
+    /// Triangles with indices out of range are ignored.
+    pub fn compute_smooth_normals(&mut self) {
+        let mut normals = HashMap::new();
+
+        for [a, b, c] in self.triangles() {
+            let corners = [&self.vertices[a], &self.vertices[b], &self.vertices[c]];
+            let normal = triangle_normal(corners[0], corners[1], corners[2]);
+
+            for corner in &corners {
+                *normals
+                    .entry(position_key(corner.position))
+                    .or_insert_with(Vector3f::zero) += normal;
+            }
+        }
+
+        for vertex in &mut self.vertices {
+            let normal = normals
+                .get(&position_key(vertex.position))
+                .cloned()
+                .unwrap_or_else(Vector3f::zero);
+            vertex.normal = normalize_or(normal, Vector3f::unit_y());
+        }
+    }
+
+    /// Computes tangents from the normals and UV coordinates, for normal mapping.
+    ///
+    /// Normals must be computed first. The bitangent points towards increasing `v`.
+    /// Triangles with indices out of range are ignored.
+    pub fn compute_tangents(&mut self) {
+        let mut tangents = vec![Vector3f::zero(); self.vertices.len()];
+        let mut bitangents = vec![Vector3f::zero(); self.vertices.len()];
+
+        for triangle in self.triangles() {
+            let (a, b, c) = (
+                &self.vertices[triangle[0]],
+                &self.vertices[triangle[1]],
+                &self.vertices[triangle[2]],
+            );
+            let (edge1, edge2) = (b.position - a.position, c.position - a.position);
+            let (delta1, delta2) = (b.uv - a.uv, c.uv - a.uv);
+
+            let determinant = delta1.x * delta2.y - delta2.x * delta1.y;
+            if determinant.abs() <= f32::EPSILON {
+                //Degenerate UVs
+                continue;
+            }
+
+            let tangent = (edge1 * delta2.y - edge2 * delta1.y) / determinant;
+            let bitangent = (edge2 * delta1.x - edge1 * delta2.x) / determinant;
+
+            for &index in &triangle {
+                tangents[index] += tangent;
+                bitangents[index] += bitangent;
+            }
+        }
+
+        for (index, vertex) in self.vertices.iter_mut().enumerate() {
+            let normal = vertex.normal;
+
+            //Make the tangent perpendicular to the normal
+            let tangent = tangents[index] - normal * normal.dot(tangents[index]);
+            let tangent = normalize_or(tangent, any_perpendicular(normal));
+
+            let handedness = if normal.cross(tangent).dot(bitangents[index]) < 0.0 {
+                -1.0
+            } else {
+                1.0
+            };
+
+            vertex.tangent = tangent.extend(handedness);
+        }
+    }
+
+    /// Merges vertices whose attributes are all within `epsilon` of each other,
+    /// and removes the triangles that become degenerate or have indices out of range.
+    ///
+    /// Vertices that only share a position stay separate, so UV seams and sharp edges
+    /// are kept.
+    pub fn weld(&mut self, epsilon: f32) {
+        let cell_size = epsilon.max(f32::EPSILON);
+        let mut cells: HashMap<(i64, i64, i64), Vec<usize>> = HashMap::new();
+        let mut vertices: Vec<LitVertex> = Vec::new();
+        let mut remap = Vec::with_capacity(self.vertices.len());
+
+        for vertex in &self.vertices {
+            let cell = (
+                (vertex.position.x / cell_size).floor() as i64,
+                (vertex.position.y / cell_size).floor() as i64,
+                (vertex.position.z / cell_size).floor() as i64,
+            );
+
+            //Close vertices can be in a neighboring cell
+            let mut existing = None;
+            'search: for x in -1..=1 {
+                for y in -1..=1 {
+                    for z in -1..=1 {
+                        let neighbor = (cell.0 + x, cell.1 + y, cell.2 + z);
+                        if let Some(candidates) = cells.get(&neighbor) {
+                            existing = candidates.iter().cloned().find(|&candidate| {
+                                vertices_close(&vertices[candidate], vertex, epsilon)
+                            });
+                            if existing.is_some() {
+                                break 'search;
+                            }
+                        }
+                    }
+                }
+            }
+
+            let index = match existing {
+                Some(index) => index,
+                None => {
+                    vertices.push(*vertex);
+                    cells
+                        .entry(cell)
+                        .or_insert_with(Vec::new)
+                        .push(vertices.len() - 1);
+                    vertices.len() - 1
+                }
+            };
+            remap.push(index as gl::types::GLuint);
+        }
+
+        let mut indices = Vec::with_capacity(self.indices.len());
+        for triangle in self.triangles() {
+            let (a, b, c) = (remap[triangle[0]], remap[triangle[1]], remap[triangle[2]]);
+            if a != b && b != c && a != c {
+                indices.extend_from_slice(&[a, b, c]);
+            }
+        }
+
+        self.vertices = vertices;
+        self.indices = indices;
+    }
+
+    /// Applies a `Transform` to the positions, normals and tangents of the vertices.
+    pub fn apply_transform(&mut self, transform: &Transform) {
+        self.apply_matrix(transform.matrix());
+    }
+
+    /// Applies an affine transform matrix to the positions, normals and tangents
+    /// of the vertices.
+    ///
+    /// Mirroring matrices also reverse the winding order of triangles,
+    /// so that they keep facing outwards.
+    pub fn apply_matrix(&mut self, matrix: Matrix4f) {
+        let linear = cgmath::Matrix3::from_cols(
+            matrix.x.truncate(),
+            matrix.y.truncate(),
+            matrix.z.truncate(),
+        );
+        //Normals must stay perpendicular to surfaces under non-uniform scaling
+        let normal_matrix = linear
+            .invert()
+            .map_or(linear, |inverse| inverse.transpose());
+        let mirrored = linear.determinant() < 0.0;
+
+        for vertex in &mut self.vertices {
+            vertex.position = (matrix * vertex.position.extend(1.0)).truncate();
+            vertex.normal = normalize_or(normal_matrix * vertex.normal, vertex.normal);
+
+            let handedness = if mirrored {
+                -vertex.tangent.w
+            } else {
+                vertex.tangent.w
+            };
+            let tangent = vertex.tangent.truncate();
+            vertex.tangent = normalize_or(linear * tangent, tangent).extend(handedness);
+        }
+
+        if mirrored {
+            for triangle in self.indices.chunks_mut(3) {
+                if triangle.len() == 3 {
+                    triangle.swap(1, 2);
+                }
+            }
+        }
+    }
+}
+
+/// Normal of a triangle, with a length of twice its area.
+fn triangle_normal(a: &LitVertex, b: &LitVertex, c: &LitVertex) -> Vector3f {
+    (b.position - a.position).cross(c.position - a.position)
+}
+
+/// Normalized vector, or `fallback` if it has no length.
+fn normalize_or(vector: Vector3f, fallback: Vector3f) -> Vector3f {
+    if vector.magnitude2() > 0.0 {
+        vector.normalize()
+    } else {
+        fallback
+    }
+}
+
+/// Unit vector perpendicular to a unit vector.
+fn any_perpendicular(vector: Vector3f) -> Vector3f {
+    let axis = if vector.x.abs() < 0.9 {
+        Vector3f::unit_x()
+    } else {
+        Vector3f::unit_y()
+    };
+
+    (axis - vector * vector.dot(axis)).normalize()
+}
+
+/// Hashable position, equal for equal positions.
+fn position_key(position: Vector3f) -> [u32; 3] {
+    //Adding 0 turns -0 into 0
+    [
+        (position.x + 0.0).to_bits(),
+        (position.y + 0.0).to_bits(),
+        (position.z + 0.0).to_bits(),
+    ]
+}
+
+fn vertices_close(a: &LitVertex, b: &LitVertex, epsilon: f32) -> bool {
+    let epsilon2 = epsilon * epsilon;
+
+    (a.position - b.position).magnitude2() <= epsilon2
+        && (a.uv - b.uv).magnitude2() <= epsilon2
+        && (a.normal - b.normal).magnitude2() <= epsilon2
+        && (a.tangent - b.tangent).magnitude2() <= epsilon2
+        && (a.uv2 - b.uv2).magnitude2() <= epsilon2
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vertex(x: f32, y: f32) -> LitVertex {
+        LitVertex {
+            position: Vector3f::new(x, y, 0.0),
+            uv: Vector2f::new(x, y),
+            normal: Vector3f::unit_z(),
+            tangent: Vector4f::new(1.0, 0.0, 0.0, 1.0),
+            uv2: Vector2f::new(x, y),
+        }
+    }
+
+    /// Unit square on XY facing +Z, as 2 triangles with their own vertices.
+    fn split_square() -> MeshBuilder<LitVertex> {
+        MeshBuilder {
+            vertices: vec![
+                vertex(0.0, 0.0),
+                vertex(1.0, 0.0),
+                vertex(1.0, 1.0),
+                vertex(0.0, 0.0),
+                vertex(1.0, 1.0),
+                vertex(0.0, 1.0),
+            ],
+            indices: vec![0, 1, 2, 3, 4, 5],
+        }
+    }
+
+    #[test]
+    fn weld_merges_duplicated_vertices() {
+        let mut square = split_square();
+        square.weld(1e-4);
+
+        assert_eq!(square.vertices.len(), 4);
+        assert_eq!(square.indices, vec![0, 1, 2, 0, 2, 3]);
+    }
+
+    #[test]
+    fn weld_keeps_vertices_with_other_attributes() {
+        let mut square = split_square();
+        square.vertices[3].uv = Vector2f::new(0.5, 0.5);
+        square.weld(1e-4);
+
+        assert_eq!(square.vertices.len(), 5);
+    }
+
+    #[test]
+    fn weld_removes_degenerate_triangles() {
+        let mut square = split_square();
+        square.vertices.push(vertex(1.0, 1.00001));
+        square.indices.extend_from_slice(&[0, 2, 6]);
+        square.weld(1e-4);
+
+        assert_eq!(square.vertices.len(), 4);
+        assert_eq!(square.indices.len(), 6);
+    }
+
+    #[test]
+    fn indices_out_of_range_are_skipped() {
+        let mut square = split_square();
+        square.indices.extend_from_slice(&[0, 1, 42, 3]);
+
+        square.compute_smooth_normals();
+        square.compute_tangents();
+        assert!(square
+            .vertices
+            .iter()
+            .all(|vertex| (vertex.normal - Vector3f::unit_z()).magnitude() < 1e-5));
+
+        let mut flat = square.clone();
+        flat.compute_flat_normals();
+        assert_eq!(flat.vertices.len(), 6);
+        assert_eq!(flat.indices.len(), 6);
+
+        square.weld(1e-4);
+        assert_eq!(square.indices.len(), 6);
+    }
+
+    #[test]
+    fn mirroring_matrix_flips_winding() {
+        let mut square = split_square();
+        square.apply_matrix(Matrix4f::from_nonuniform_scale(-1.0, 1.0, 1.0));
+
+        assert_eq!(square.indices, vec![0, 2, 1, 3, 5, 4]);
+
+        //Triangles still face their normals
+        for triangle in square.indices.chunks(3) {
+            let corners: Vec<&LitVertex> = triangle
+                .iter()
+                .map(|&index| &square.vertices[index as usize])
+                .collect();
+            let normal = triangle_normal(corners[0], corners[1], corners[2]);
+
+            assert!(normal.dot(corners[0].normal) > 0.0);
+            assert_eq!(corners[0].tangent.w, -1.0);
+        }
+    }
+
+    #[test]
+    fn rotation_keeps_winding() {
+        let mut square = split_square();
+        square.apply_matrix(Matrix4f::from_angle_z(cgmath::Deg(90.0)));
+
+        assert_eq!(square.indices, vec![0, 1, 2, 3, 4, 5]);
+        assert!((square.vertices[1].position - Vector3f::unit_y()).magnitude() < 1e-5);
+        assert!((square.vertices[1].tangent.truncate() - Vector3f::unit_y()).magnitude() < 1e-5);
+    }
+}
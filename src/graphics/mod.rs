@@ -1,27 +1,66 @@
 use self::{
     batches::{Batch, DrawCall},
-    camera::Camera,
-    mesh::{Mesh, MeshBuilder, Vertex},
-    shaders::Program,
-    shaders::{Shader, ShaderType},
-    sprites::Sprite,
+    camera::{Camera, ClearFlags},
+    capture::{FrameRecorder, RecordingOutput},
+    color::Color,
+    cubemap::Cubemap,
+    debug::DebugDraw,
+    dynamic::{DynamicBuffer, DynamicVertex},
+    framebuffer::Framebuffer,
+    material::Material,
+    mesh::{locations, Mesh, MeshBuilder, Vertex},
+    postprocess::{PostProcessPass, PostProcessStack},
+    program_cache::{self, ProgramCache},
+    shaders::{Program, ProgramID, ShaderType},
+    sprites::{SliceMode, Sprite},
     text::{Font, TextSettings},
-    textures::Texture,
+    texture_array::TextureArray,
+    textures::{BindableTexture, Texture, TextureID, TextureOptions, TextureTarget},
+    uniform_buffer::{FrameUniforms, UniformBuffer, FRAME_BLOCK_BINDING},
 };
 use failure::Error;
 use gl;
-use maths::{Vector2f, Vector2u, Vector3f};
+use image::RgbaImage;
+use maths::{Matrix4f, Vector2f, Vector2u, Vector3f, Vector4f};
 use sdl2;
-use std::ptr;
+use std::{
+    collections::HashMap,
+    env,
+    path::{Path, PathBuf},
+    ptr,
+    sync::mpsc::{self, Receiver, Sender},
+    time::Instant,
+};
 use transform::Transform;
 
+pub mod animation;
+pub mod atlas;
 mod batches;
 pub mod camera;
+pub mod capture;
+pub mod color;
+pub mod compute;
+pub mod cubemap;
+mod debug;
+mod dynamic;
+pub mod framebuffer;
+mod gltf;
+pub mod material;
 pub mod mesh;
+pub mod model;
+mod obj;
+pub mod postprocess;
+pub mod preprocessor;
+pub mod primitives;
+pub mod program_cache;
 pub mod shaders;
+pub mod shapes;
+pub mod sheet_import;
 pub mod sprites;
 pub mod text;
+pub mod texture_array;
 pub mod textures;
+pub mod uniform_buffer;
 
 /// Error related to OpenGL drawing.
 #[derive(Debug, Fail)]
@@ -32,6 +71,22 @@ pub enum GraphicsError {
     /// Error related to OpenGL.
     #[fail(display = "OpenGL Error: {}", _0)]
     GlError(String),
+    /// No camera registered with this ID.
+    #[fail(display = "Camera not found: {:?}", _0)]
+    CameraNotFound(CameraID),
+    /// Pixels of the window were read without keeping frames.
+    #[fail(display = "No rendered frame kept, enable keep_frames to read the window")]
+    NoFrameKept,
+}
+
+/// Handle to a camera registered in a `GraphicsManager`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct CameraID(usize);
+
+/// A registered camera and the draw calls queued for it.
+struct CameraEntry {
+    camera: Camera,
+    batches: Vec<Batch>,
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -40,6 +95,33 @@ pub struct WindowSettings<'a> {
     pub width: u32,
     pub height: u32,
     pub vsync: bool,
+    /// Hides the window and renders to an offscreen framebuffer instead.
+    ///
+    /// Frames can then be read with `GraphicsManager::read_pixels`.
+    pub headless: bool,
+    /// Asks Mesa to use its software rasterizer (llvmpipe), for machines without a GPU.
+    ///
+    /// Sets `LIBGL_ALWAYS_SOFTWARE`, and has no effect with other drivers.
+    pub software_rendering: bool,
+    /// Directory where linked programs are cached, to start faster on later launches.
+    ///
+    /// Built-in programs are cached there, and it is available to other programs
+    /// through `GraphicsManager::program_cache`. If `None`, programs are always compiled.
+    pub program_cache_directory: Option<&'a Path>,
+}
+
+impl<'a> Default for WindowSettings<'a> {
+    fn default() -> Self {
+        Self {
+            title: "cuivre",
+            width: 800,
+            height: 600,
+            vsync: true,
+            headless: false,
+            software_rendering: false,
+            program_cache_directory: None,
+        }
+    }
 }
 
 /// Manages everything related to graphics and rendering.
@@ -49,18 +131,72 @@ pub struct GraphicsManager {
     #[allow(dead_code)]
     gl_context: sdl2::video::GLContext,
 
+    /// Cache of linked programs, if enabled in the window settings.
+    program_cache: Option<ProgramCache>,
+
     /// Base shader program.
     program: Program,
+    /// Base shader program sampling texture arrays.
+    array_program: Program,
     /// Base mesh used to draw sprites.
     quad: Mesh,
+    /// Program drawing a cubemap as the sky.
+    skybox_program: Program,
+    /// Cube around the camera, the sky is drawn on.
+    skybox_cube: Mesh,
 
-    /// All draw calls to be rendered this frame.
-    batches: Vec<Batch>,
+    /// Program used to draw geometry rebuilt every frame.
+    dynamic_program: Program,
+    /// Buffer used to draw geometry rebuilt every frame.
+    dynamic_buffer: DynamicBuffer,
+    /// 1x1 white texture, used when drawing untextured geometry.
+    white_texture: Texture,
+    /// Materials drawing a single texture with a base program, used for sprites and text.
+    texture_materials: HashMap<(ProgramID, TextureID, TextureTarget), Material>,
+    /// Buffer of the `Frame` uniform block, updated for each camera.
+    frame_uniforms: UniformBuffer<FrameUniforms>,
+    /// Time the manager was created, origin of `FrameUniforms::time`.
+    start_time: Instant,
+
+    /// Debug primitives waiting to be drawn.
+    debug: DebugDraw,
+
+    /// Registered cameras, each with its draw calls to be rendered this frame.
+    ///
+    /// Removed cameras leave an empty slot so that IDs stay valid.
+    cameras: Vec<Option<CameraEntry>>,
+
+    /// Fullscreen passes applied after the scene is rendered.
+    post_process: PostProcessStack,
+
+    /// Render target replacing the window in headless mode.
+    headless_target: Option<Framebuffer>,
+
+    /// Copy the window's frames to `last_frame` as they are rendered.
+    keep_frames: bool,
+    /// Last frame rendered to the window, if `keep_frames` is enabled.
+    last_frame: Option<RgbaImage>,
+
+    /// Files the next rendered frame will be saved to.
+    pending_captures: Vec<PathBuf>,
+    /// Recording in progress.
+    recorder: Option<FrameRecorder>,
+    /// Number of finished recordings still being saved on background threads.
+    saving_recordings: usize,
+    /// Sends the result of a recording once saved.
+    recording_sender: Sender<Result<(), Error>>,
+    /// Receives the results of saved recordings.
+    recording_receiver: Receiver<Result<(), Error>>,
 }
 
 impl GraphicsManager {
     /// Initializes graphics from SDL object, resource loader, default shader paths and window settings
     pub fn new(sdl: &sdl2::Sdl, window_settings: WindowSettings) -> Result<Self, Error> {
+        //Must be set before the OpenGL library is loaded
+        if window_settings.software_rendering {
+            env::set_var("LIBGL_ALWAYS_SOFTWARE", "1");
+        }
+
         //Initialize VideoSubsystem
         let video = sdl.video().map_err(GraphicsError::SdlError)?;
 
@@ -72,22 +208,27 @@ impl GraphicsManager {
         }
 
         //Create Window
-        let window = video
-            .window(
-                window_settings.title,
-                window_settings.width,
-                window_settings.height,
-            )
-            .opengl()
-            .resizable()
-            .build()?;
+        let mut window_builder = video.window(
+            window_settings.title,
+            window_settings.width,
+            window_settings.height,
+        );
+        window_builder.opengl();
+
+        if window_settings.headless {
+            window_builder.hidden();
+        } else {
+            window_builder.resizable();
+        }
+
+        let window = window_builder.build()?;
 
         //Initialize OpenGL
         let gl_context = window.gl_create_context().map_err(GraphicsError::GlError)?;
         gl::load_with(|s| video.gl_get_proc_address(s) as *const gl::types::GLvoid);
 
         //Enable/disable vsync
-        video.gl_set_swap_interval(if window_settings.vsync {
+        video.gl_set_swap_interval(if window_settings.vsync && !window_settings.headless {
             sdl2::video::SwapInterval::VSync
         } else {
             sdl2::video::SwapInterval::Immediate
@@ -102,16 +243,61 @@ impl GraphicsManager {
             gl::Enable(gl::BLEND);
             gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
 
-            //Clear color
-            gl::ClearColor(0.3, 0.3, 0.5, 1.0);
+            //Filter across cubemap faces
+            gl::Enable(gl::TEXTURE_CUBE_MAP_SEAMLESS);
         }
 
-        //Load shaders
-        let vertex_shader =
-            Shader::from_source(include_str!("shaders/standard.vert"), ShaderType::Vertex)?;
-        let fragment_shader =
-            Shader::from_source(include_str!("shaders/standard.frag"), ShaderType::Fragment)?;
-        let program = Program::from_shaders(vertex_shader, fragment_shader)?;
+        //Load shaders, from the cache if enabled
+        let cache = match window_settings.program_cache_directory {
+            Some(directory) => Some(ProgramCache::new(directory)?),
+            None => None,
+        };
+
+        let program = program_cache::program_from_sources(
+            cache.as_ref(),
+            &[
+                (ShaderType::Vertex, include_str!("shaders/standard.vert")),
+                (ShaderType::Fragment, include_str!("shaders/standard.frag")),
+            ],
+        )?;
+
+        let array_program = program_cache::program_from_sources(
+            cache.as_ref(),
+            &[
+                (ShaderType::Vertex, include_str!("shaders/standard.vert")),
+                (
+                    ShaderType::Fragment,
+                    include_str!("shaders/standard_array.frag"),
+                ),
+            ],
+        )?;
+
+        let dynamic_program = program_cache::program_from_sources(
+            cache.as_ref(),
+            &[
+                (ShaderType::Vertex, include_str!("shaders/dynamic.vert")),
+                (ShaderType::Fragment, include_str!("shaders/dynamic.frag")),
+            ],
+        )?;
+
+        let skybox_program = program_cache::program_from_sources(
+            cache.as_ref(),
+            &[
+                (ShaderType::Vertex, include_str!("shaders/skybox.vert")),
+                (ShaderType::Fragment, include_str!("shaders/skybox.frag")),
+            ],
+        )?;
+
+        let white_texture = Texture::from_bytes(&[0xFF; 4], TextureOptions::default(), 1, 1)?;
+
+        let headless_target = if window_settings.headless {
+            Some(Framebuffer::new(
+                window_settings.width,
+                window_settings.height,
+            )?)
+        } else {
+            None
+        };
 
         //Build quad mesh
         let quad = MeshBuilder {
@@ -137,47 +323,494 @@ impl GraphicsManager {
         }
         .build();
 
+        //Share per-camera uniforms with every program
+        let frame_uniforms = UniformBuffer::new(&FrameUniforms {
+            view: Matrix4f::from_scale(1.0),
+            projection: Matrix4f::from_scale(1.0),
+            screen_size: Vector2f::new(window_settings.width as f32, window_settings.height as f32),
+            time: 0.0,
+        });
+        frame_uniforms.bind(FRAME_BLOCK_BINDING);
+
+        let (recording_sender, recording_receiver) = mpsc::channel();
+
         //Build and return graphics manager
         Ok(Self {
             window,
             gl_context,
+            program_cache: cache,
             program,
+            array_program,
             quad,
-            batches: Vec::new(),
+            skybox_program,
+            skybox_cube: primitives::cube(Vector3f::new(2.0, 2.0, 2.0)).build(),
+            dynamic_program,
+            dynamic_buffer: DynamicBuffer::new(),
+            white_texture,
+            texture_materials: HashMap::new(),
+            frame_uniforms,
+            start_time: Instant::now(),
+            debug: DebugDraw::new(),
+            cameras: Vec::new(),
+            post_process: PostProcessStack::new(),
+            headless_target,
+            keep_frames: false,
+            last_frame: None,
+            pending_captures: Vec::new(),
+            recorder: None,
+            saving_recordings: 0,
+            recording_sender,
+            recording_receiver,
         })
     }
 
     /// Get the current window's size.
+    ///
+    /// In headless mode, this is the size of the offscreen framebuffer.
     pub fn window_size(&self) -> Vector2u {
-        self.window.size().into()
+        match &self.headless_target {
+            Some(framebuffer) => framebuffer.size(),
+            None => self.window.size().into(),
+        }
+    }
+
+    /// Returns true if rendering to an offscreen framebuffer instead of the window.
+    pub fn headless(&self) -> bool {
+        self.headless_target.is_some()
     }
 
     /// Sets the OpenGL viewport. Call when the window is resized.
-    pub fn resize(&mut self, width: i32, height: i32) {
+    ///
+    /// Camera viewports are relative to the window and do not need to be updated.
+    ///
+    /// In headless mode, this resizes the offscreen framebuffer.
+    pub fn resize(&mut self, width: u32, height: u32) -> Result<(), Error> {
+        if let Some(framebuffer) = &mut self.headless_target {
+            framebuffer.resize(width, height)?;
+        }
+
         unsafe {
-            gl::Viewport(0, 0, width as gl::types::GLint, height as gl::types::GLint);
+            gl::Viewport(0, 0, width as gl::types::GLsizei, height as gl::types::GLsizei);
+        }
+
+        Ok(())
+    }
+
+    /// Reads the last rendered frame to CPU memory, as an RGBA image.
+    ///
+    /// In headless mode, this reads the offscreen framebuffer. Otherwise, the window's
+    /// contents are undefined once presented, so frames must be kept as they are rendered:
+    /// see `keep_frames`.
+    pub fn read_pixels(&self) -> Result<RgbaImage, Error> {
+        match (&self.headless_target, &self.last_frame) {
+            (Some(framebuffer), _) => framebuffer.read_pixels(),
+            (None, Some(frame)) => Ok(frame.clone()),
+            (None, None) => Err(GraphicsError::NoFrameKept.into()),
+        }
+    }
+
+    /// Keeps a copy of each rendered frame, to be read with `read_pixels`.
+    ///
+    /// Only needed when drawing to a window, as reading it back slows down rendering.
+    pub fn keep_frames(&mut self, keep: bool) {
+        self.keep_frames = keep;
+
+        if !keep {
+            self.last_frame = None;
         }
     }
 
+    /// Saves the next rendered frame to an image file, e.g. a PNG.
+    ///
+    /// The format is deduced from the file extension.
+    /// The file is written at the end of the next call to `render`.
+    pub fn capture_frame<P: Into<PathBuf>>(&mut self, path: P) {
+        self.pending_captures.push(path.into());
+    }
+
+    /// Saves the contents of a render target to an image file, e.g. a PNG.
+    ///
+    /// The format is deduced from the file extension.
+    pub fn capture_framebuffer<P: Into<PathBuf>>(
+        &self,
+        framebuffer: &Framebuffer,
+        path: P,
+    ) -> Result<(), Error> {
+        framebuffer.read_pixels()?.save(path.into())?;
+        Ok(())
+    }
+
+    /// Starts capturing `frame_count` frames, `fps` times per second.
+    ///
+    /// Frames are saved to `output` once they are all captured.
+    /// Any recording in progress is stopped and saved first.
+    pub fn start_recording(
+        &mut self,
+        output: RecordingOutput,
+        frame_count: usize,
+        fps: f32,
+    ) -> Result<(), Error> {
+        self.stop_recording()?;
+        self.recorder = Some(FrameRecorder::new(output, frame_count, fps));
+        Ok(())
+    }
+
+    /// Stops the recording in progress, saving the frames captured so far.
+    ///
+    /// Frames are saved on a background thread. Errors are returned by a later call
+    /// to `stop_recording`, `render` or `wait_for_recordings`.
+    pub fn stop_recording(&mut self) -> Result<(), Error> {
+        if let Some(recorder) = self.recorder.take() {
+            recorder.save_in_background(self.recording_sender.clone());
+            self.saving_recordings += 1;
+        }
+
+        self.poll_recordings()
+    }
+
+    /// Waits until finished recordings are saved.
+    ///
+    /// Returns the first error that happened while saving, if any.
+    pub fn wait_for_recordings(&mut self) -> Result<(), Error> {
+        let mut result = Ok(());
+
+        while self.saving_recordings > 0 {
+            self.saving_recordings -= 1;
+
+            if let Ok(Err(error)) = self.recording_receiver.recv() {
+                if result.is_ok() {
+                    result = Err(error);
+                }
+            }
+        }
+
+        result
+    }
+
+    /// Returns the first error of the recordings saved since the last call, if any.
+    fn poll_recordings(&mut self) -> Result<(), Error> {
+        let mut result = Ok(());
+
+        while let Ok(saved) = self.recording_receiver.try_recv() {
+            self.saving_recordings -= 1;
+
+            if let Err(error) = saved {
+                if result.is_ok() {
+                    result = Err(error);
+                }
+            }
+        }
+
+        result
+    }
+
+    /// Returns true if a recording is in progress.
+    pub fn is_recording(&self) -> bool {
+        self.recorder.is_some()
+    }
+
+    /// Reads the frame that was just rendered, before buffers are swapped.
+    fn read_rendered_pixels(&self) -> Result<RgbaImage, Error> {
+        match &self.headless_target {
+            Some(framebuffer) => framebuffer.read_pixels(),
+            None => Framebuffer::read_default_pixels(self.window_size()),
+        }
+    }
+
+    /// Reads the frame that was just rendered, if it is to be saved, recorded or kept.
+    fn read_captured_frame(&self) -> Result<Option<RgbaImage>, Error> {
+        let recording = self
+            .recorder
+            .as_ref()
+            .map_or(false, FrameRecorder::wants_frame);
+        let keeping = self.keep_frames && self.headless_target.is_none();
+
+        if self.pending_captures.is_empty() && !recording && !keeping {
+            return Ok(None);
+        }
+
+        self.read_rendered_pixels().map(Some)
+    }
+
+    /// Saves requested captures and records a frame that was read with `read_captured_frame`.
+    fn save_captured_frame(&mut self, frame: RgbaImage) -> Result<(), Error> {
+        let mut result = Ok(());
+
+        //Every capture is attempted even if one fails
+        for path in self.pending_captures.drain(..) {
+            if let Err(error) = frame.save(path) {
+                if result.is_ok() {
+                    result = Err(error.into());
+                }
+            }
+        }
+
+        let recording = self
+            .recorder
+            .as_ref()
+            .map_or(false, FrameRecorder::wants_frame);
+
+        if recording {
+            if let Some(recorder) = &mut self.recorder {
+                recorder.add_frame(frame);
+            }
+
+            //Save recording once complete
+            if self.recorder.as_ref().map_or(false, FrameRecorder::is_finished) {
+                self.stop_recording()?;
+            }
+        }
+
+        result
+    }
+
+    /// Registers a camera to render with. Returns its ID, used when drawing.
+    pub fn add_camera(&mut self, camera: Camera) -> CameraID {
+        let entry = CameraEntry {
+            camera,
+            batches: Vec::new(),
+        };
+
+        //Reuse a free slot if there is one
+        match self.cameras.iter().position(Option::is_none) {
+            Some(index) => {
+                self.cameras[index] = Some(entry);
+                CameraID(index)
+            }
+            None => {
+                self.cameras.push(Some(entry));
+                CameraID(self.cameras.len() - 1)
+            }
+        }
+    }
+
+    /// Unregisters a camera and returns it. Draw calls queued for it are discarded.
+    pub fn remove_camera(&mut self, id: CameraID) -> Option<Camera> {
+        self.cameras
+            .get_mut(id.0)
+            .and_then(Option::take)
+            .map(|entry| entry.camera)
+    }
+
+    /// Gets a registered camera.
+    pub fn camera(&self, id: CameraID) -> Option<&Camera> {
+        self.cameras
+            .get(id.0)
+            .and_then(Option::as_ref)
+            .map(|entry| &entry.camera)
+    }
+
+    /// Gets a registered camera, to move it or change its settings.
+    pub fn camera_mut(&mut self, id: CameraID) -> Option<&mut Camera> {
+        self.cameras
+            .get_mut(id.0)
+            .and_then(Option::as_mut)
+            .map(|entry| &mut entry.camera)
+    }
+
+    /// Time since the manager was created, in seconds.
+    ///
+    /// Shaders receive it as `Time` in the `Frame` uniform block.
+    pub fn time(&self) -> f32 {
+        let elapsed = self.start_time.elapsed();
+        elapsed.as_secs() as f32 + elapsed.subsec_nanos() as f32 / 1_000_000_000.0
+    }
+
     /// Draws a `Sprite` on a textured quad mesh.
     ///
     /// `transform` specifies the position, scale, and rotation
     /// of the drawn `Sprite`.
     ///
-    /// `Camera` is the camera the `Sprite` is viewed from.
+    /// `camera` is the registered camera the `Sprite` is viewed from.
     ///
-    /// Note: by default all sprites are square. For non-square sprites,
-    /// you must use `transform.scale` to scale the quad appropriately.
-    pub fn draw_sprite(&mut self, sprite: &Sprite, transform: &Transform, camera: &Camera) {
+    /// The sprite is drawn at its own size (see `SpriteSheet::pixels_per_unit`),
+    /// positioned and rotated around its pivot. `transform.scale` scales it further.
+    ///
+    /// To mirror a sprite, flip it with `Sprite::flipped` rather than using a negative scale.
+    pub fn draw_sprite(
+        &mut self,
+        sprite: &Sprite,
+        transform: &Transform,
+        camera: CameraID,
+    ) -> Result<(), Error> {
+        let matrix = transform.matrix() * sprite.matrix();
+
+        let material =
+            texture_material(&mut self.texture_materials, &self.program, sprite.texture());
+
         let drawcall = DrawCall {
-            program: self.program,
+            material,
             mesh: self.quad,
-            texture: sprite.texture(),
             tex_position: sprite.gl_position(),
-            matrix: camera.matrix(self.window.size().into()) * transform.matrix(),
+            uv_rotated: sprite.region.rotated,
+            layer: 0,
+            matrix,
+            color: Color::WHITE,
         };
 
-        self.queue_drawcall(&drawcall);
+        queue_drawcall(&mut self.cameras, &drawcall, camera)
+    }
+
+    /// Draws a sliced `Sprite` filling `size` world units, using the sprite's borders.
+    ///
+    /// Corners are drawn at their own size (see `SpriteSheet::pixels_per_unit`),
+    /// while edges and center are stretched or tiled depending on `mode`.
+    /// All parts are drawn in the same batch.
+    pub fn draw_sliced_sprite(
+        &mut self,
+        sprite: &Sprite,
+        size: Vector2f,
+        mode: SliceMode,
+        transform: &Transform,
+        camera: CameraID,
+    ) -> Result<(), Error> {
+        let matrix = transform.matrix();
+        let material =
+            texture_material(&mut self.texture_materials, &self.program, sprite.texture());
+
+        for (tex_position, slice_matrix) in sprite.slices(size, mode) {
+            let drawcall = DrawCall {
+                material,
+                mesh: self.quad,
+                tex_position,
+                uv_rotated: sprite.region.rotated,
+                layer: 0,
+                matrix: matrix * slice_matrix,
+                color: Color::WHITE,
+            };
+
+            queue_drawcall(&mut self.cameras, &drawcall, camera)?;
+        }
+
+        Ok(())
+    }
+
+    /// Draws a whole layer of a `TextureArray` on a quad of `size` world units.
+    ///
+    /// Layers of the same array are drawn in the same batch.
+    pub fn draw_texture_layer(
+        &mut self,
+        array: &TextureArray,
+        layer: u32,
+        size: Vector2f,
+        transform: &Transform,
+        camera: CameraID,
+    ) -> Result<(), Error> {
+        let matrix = transform.matrix() * Matrix4f::from_nonuniform_scale(size.x, size.y, 1.0);
+
+        let drawcall = DrawCall {
+            material: texture_material(&mut self.texture_materials, &self.array_program, array),
+            mesh: self.quad,
+            tex_position: Vector4f::new(0.0, 0.0, 1.0, 1.0),
+            uv_rotated: false,
+            layer,
+            matrix,
+            color: Color::WHITE,
+        };
+
+        queue_drawcall(&mut self.cameras, &drawcall, camera)
+    }
+
+    /// Draws a cubemap as the sky, behind everything else drawn by the camera.
+    ///
+    /// Only the camera's rotation is used, so the sky seems infinitely far away.
+    /// Intended for perspective cameras.
+    pub fn draw_skybox(&mut self, cubemap: &Cubemap, camera: CameraID) -> Result<(), Error> {
+        let drawcall = DrawCall {
+            material: texture_material(&mut self.texture_materials, &self.skybox_program, cubemap),
+            mesh: self.skybox_cube,
+            tex_position: Vector4f::new(0.0, 0.0, 1.0, 1.0),
+            uv_rotated: false,
+            layer: 0,
+            matrix: Matrix4f::from_scale(1.0),
+            color: Color::WHITE,
+        };
+
+        queue_drawcall(&mut self.cameras, &drawcall, camera)
+    }
+
+    /// Draws a unit quad with a custom `Material`.
+    ///
+    /// The material's program receives the same vertex attributes as the base program.
+    /// Quads with the same material are drawn in the same batch.
+    pub fn draw_quad(
+        &mut self,
+        material: &Material,
+        transform: &Transform,
+        camera: CameraID,
+    ) -> Result<(), Error> {
+        let quad = self.quad;
+        self.draw_mesh(quad, material, transform, camera)
+    }
+
+    /// Draws a `Mesh` built with `MeshBuilder`, with a `Material`.
+    ///
+    /// Draws of the same mesh with the same material are instanced:
+    /// they are rendered together, in a single draw call per camera.
+    /// The base program (see `base_program`) can be used to draw textured meshes.
+    pub fn draw_mesh(
+        &mut self,
+        mesh: Mesh,
+        material: &Material,
+        transform: &Transform,
+        camera: CameraID,
+    ) -> Result<(), Error> {
+        self.draw_mesh_matrix(mesh, material, transform.matrix(), camera)
+    }
+
+    /// Draws a `Mesh` with a `Material`, placed by a transform matrix.
+    ///
+    /// Use it for transforms that a `Transform` cannot represent,
+    /// e.g. those combining a hierarchy of nodes.
+    pub fn draw_mesh_matrix(
+        &mut self,
+        mesh: Mesh,
+        material: &Material,
+        matrix: Matrix4f,
+        camera: CameraID,
+    ) -> Result<(), Error> {
+        let drawcall = DrawCall {
+            material,
+            mesh,
+            tex_position: Vector4f::new(0.0, 0.0, 1.0, 1.0),
+            uv_rotated: false,
+            layer: 0,
+            matrix,
+            color: Color::WHITE,
+        };
+
+        queue_drawcall(&mut self.cameras, &drawcall, camera)
+    }
+
+    /// Draws a `Mesh` once per transform, with a `Material`.
+    ///
+    /// Equivalent to calling `draw_mesh` for each transform.
+    pub fn draw_mesh_instanced(
+        &mut self,
+        mesh: Mesh,
+        material: &Material,
+        transforms: &[Transform],
+        camera: CameraID,
+    ) -> Result<(), Error> {
+        for transform in transforms {
+            self.draw_mesh(mesh, material, transform, camera)?;
+        }
+
+        Ok(())
+    }
+
+    /// Cache of linked programs, if a directory was set in the window settings.
+    ///
+    /// Pass it to `PostProcessPass` constructors, or use it to load other programs.
+    pub fn program_cache(&self) -> Option<&ProgramCache> {
+        self.program_cache.as_ref()
+    }
+
+    /// Program used to draw sprites, which samples the texture `Tex` of its material.
+    ///
+    /// Use it to create materials for meshes that need no custom shader.
+    pub fn base_program(&self) -> &Program {
+        &self.program
     }
 
     /// Draws a string.
@@ -187,11 +820,13 @@ impl GraphicsManager {
         font: &mut Font,
         settings: TextSettings,
         transform: &Transform,
-        camera: &Camera,
+        camera: CameraID,
     ) -> Result<(), Error> {
-        for char_position in font.get_glyphs(text, settings)? {
-            let texture = font.texture();
+        let glyphs = font.get_glyphs(text, settings)?;
+        let material =
+            texture_material(&mut self.texture_materials, &self.program, font.texture());
 
+        for char_position in glyphs {
             let char_transform = Transform {
                 position: transform.position + Vector3f::new(
                     char_position.world_position.x,
@@ -207,55 +842,331 @@ impl GraphicsManager {
             };
 
             let drawcall = DrawCall {
-                program: self.program,
+                material,
                 mesh: self.quad,
-                texture,
                 tex_position: char_position.texture_position,
-                matrix: camera.matrix(self.window.size().into()) * char_transform.matrix(),
+                uv_rotated: false,
+                layer: 0,
+                matrix: char_transform.matrix(),
+                color: Color::WHITE,
             };
 
-            self.queue_drawcall(&drawcall);
+            queue_drawcall(&mut self.cameras, &drawcall, camera)?;
         }
 
         Ok(())
     }
 
-    /// Adds a drawcall to the render queue.
+    /// Draws a shape tessellated with the `shapes` module, or any mesh built from `Vertex` data.
     ///
-    /// If no suitable batch is found, a new one is created.
-    pub fn queue_drawcall(&mut self, drawcall: &DrawCall) {
-        for batch in &mut self.batches {
-            //Attempts to add drawcall to batch
-            if batch.add(drawcall) {
-                return;
-            }
-        }
+    /// The shape is tinted with `color`, and textured with `texture` if specified.
+    /// Build shapes into a `Mesh` once and reuse it, as meshes are not freed.
+    ///
+    /// Shapes are drawn with the base program. Only draws of the same mesh with the same
+    /// texture are batched together, so draw a shape several times rather than building
+    /// a mesh per draw.
+    pub fn draw_shape(
+        &mut self,
+        shape: Mesh,
+        color: Color,
+        texture: Option<&Texture>,
+        transform: &Transform,
+        camera: CameraID,
+    ) -> Result<(), Error> {
+        let texture = texture.unwrap_or(&self.white_texture);
+
+        let drawcall = DrawCall {
+            material: texture_material(&mut self.texture_materials, &self.program, texture),
+            mesh: shape,
+            tex_position: Vector4f::new(0.0, 0.0, 1.0, 1.0),
+            uv_rotated: false,
+            layer: 0,
+            matrix: transform.matrix(),
+            color,
+        };
+
+        queue_drawcall(&mut self.cameras, &drawcall, camera)
+    }
+
+    /// Draws a line between two points.
+    ///
+    /// Debug primitives stay on screen for `duration` seconds, or one frame if 0.
+    /// If `depth_test` is false, they are drawn over everything else.
+    ///
+    /// They are drawn by every camera with `show_debug` enabled.
+    pub fn debug_line(
+        &mut self,
+        start: Vector3f,
+        end: Vector3f,
+        color: Color,
+        duration: f32,
+        depth_test: bool,
+    ) {
+        self.debug.line(start, end, color, duration, depth_test);
+    }
+
+    /// Draws the outline of a rectangle on the XY plane.
+    ///
+    /// See `debug_line` for the other parameters.
+    pub fn debug_rect(
+        &mut self,
+        center: Vector3f,
+        size: Vector2f,
+        color: Color,
+        duration: f32,
+        depth_test: bool,
+    ) {
+        self.debug.rect(center, size, color, duration, depth_test);
+    }
+
+    /// Draws the outline of a circle on the XY plane.
+    ///
+    /// See `debug_line` for the other parameters.
+    pub fn debug_circle(
+        &mut self,
+        center: Vector3f,
+        radius: f32,
+        color: Color,
+        duration: f32,
+        depth_test: bool,
+    ) {
+        self.debug.circle(center, radius, color, duration, depth_test);
+    }
+
+    /// Draws the edges of an axis-aligned box.
+    ///
+    /// See `debug_line` for the other parameters.
+    pub fn debug_box(
+        &mut self,
+        center: Vector3f,
+        size: Vector3f,
+        color: Color,
+        duration: f32,
+        depth_test: bool,
+    ) {
+        self.debug.cuboid(center, size, color, duration, depth_test);
+    }
 
-        //Could not find suitable batch, create a new one
-        self.batches.push(Batch::new(drawcall));
+    /// Draws an arrow from `start` pointing to `end`.
+    ///
+    /// See `debug_line` for the other parameters.
+    pub fn debug_arrow(
+        &mut self,
+        start: Vector3f,
+        end: Vector3f,
+        color: Color,
+        duration: f32,
+        depth_test: bool,
+    ) {
+        self.debug.arrow(start, end, color, duration, depth_test);
+    }
+
+    /// Draws the world axes from `origin`: X in red, Y in green, Z in blue.
+    ///
+    /// See `debug_line` for the other parameters.
+    pub fn debug_axes(&mut self, origin: Vector3f, size: f32, duration: f32, depth_test: bool) {
+        self.debug.axes(origin, size, duration, depth_test);
+    }
+
+    /// Adds a fullscreen pass at the end of the post-processing chain.
+    ///
+    /// Returns the index of the pass in the chain.
+    pub fn add_post_process(&mut self, pass: PostProcessPass) -> usize {
+        self.post_process.push(pass)
+    }
+
+    /// Post-processing chain, to edit or remove passes.
+    pub fn post_process(&mut self) -> &mut PostProcessStack {
+        &mut self.post_process
+    }
+
+    /// Adds a drawcall to the render queue of a camera.
+    ///
+    /// `drawcall.matrix` places the object in world space: the camera's view and
+    /// projection are applied when rendering, so that they follow moves and resizes.
+    ///
+    /// If no suitable batch is found, a new one is created.
+    pub fn queue_drawcall(&mut self, drawcall: &DrawCall, camera: CameraID) -> Result<(), Error> {
+        queue_drawcall(&mut self.cameras, drawcall, camera)
     }
 
     /// Renders the current queued batches.
+    ///
+    /// If drawing fails, the rest of the frame is still rendered and presented,
+    /// and the queues are cleared, before the first error is returned.
     pub fn render(&mut self) -> Result<(), Error> {
+        let window_size = self.window_size();
+
+        //The frame is always finished and the queues cleared, then the first error is returned
+        let mut result = Ok(());
+
+        //Render scene offscreen if it needs post-processing
+        let mut post_processing = false;
+        if self.post_process.is_active() {
+            match self.post_process.begin(window_size) {
+                Ok(()) => post_processing = true,
+                Err(error) => result = Err(error),
+            }
+        }
+        if !post_processing {
+            match &self.headless_target {
+                Some(framebuffer) => framebuffer.bind(),
+                None => Framebuffer::bind_default(),
+            }
+        }
+
         //Clear render target
         unsafe {
+            gl::Viewport(
+                0,
+                0,
+                window_size.x as gl::types::GLint,
+                window_size.y as gl::types::GLint,
+            );
+            gl::ClearColor(0.0, 0.0, 0.0, 1.0);
             gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
         }
 
-        //println!("Rendering {} batches", self.batches.len());
+        //Debug lines are shared by all cameras
+        let debug_lines = self.debug.vertices(true);
+        let debug_lines_overlay = self.debug.vertices(false);
+
+        //Render cameras by increasing priority
+        let mut order: Vec<usize> = (0..self.cameras.len())
+            .filter(|&i| self.cameras[i].is_some())
+            .collect();
+        order.sort_by_key(|&i| self.cameras[i].as_ref().map(|entry| entry.camera.priority));
+
+        let time = self.time();
+
+        for index in order {
+            if let Some(entry) = &self.cameras[index] {
+                Self::begin_camera(&entry.camera, window_size);
+
+                let viewport_size = entry.camera.viewport.pixel_size(window_size);
+                self.frame_uniforms.update(&FrameUniforms {
+                    view: entry.camera.view_matrix(),
+                    projection: entry.camera.proj_matrix(window_size),
+                    screen_size: Vector2f::new(viewport_size.x as f32, viewport_size.y as f32),
+                    time,
+                });
+
+                //println!("Rendering {} batches", entry.batches.len());
+
+                //Render batches
+                for batch in &entry.batches {
+                    result = result.and(self.draw(batch));
+                }
+
+                //Render debug lines
+                if entry.camera.show_debug {
+                    let white = self.white_texture.id();
+                    self.draw_dynamic(&debug_lines, gl::LINES, white);
+
+                    unsafe { gl::Disable(gl::DEPTH_TEST) }
+                    self.draw_dynamic(&debug_lines_overlay, gl::LINES, white);
+                    unsafe { gl::Enable(gl::DEPTH_TEST) }
+                }
+            }
+
+            //Clear queue
+            if let Some(entry) = &mut self.cameras[index] {
+                entry.batches.clear();
+            }
+        }
+
+        //Remove expired debug primitives
+        self.debug.update();
 
-        //Render batches
-        for batch in &self.batches {
-            self.draw(batch)?
+        //Apply post-processing passes to the final target
+        if post_processing {
+            result = result.and(
+                self.post_process
+                    .apply(window_size, self.headless_target.as_ref()),
+            );
         }
 
-        //Clear queue
-        self.batches.clear();
+        //Read frame before it is swapped out, but only save it once presented
+        let frame = self.read_captured_frame();
 
         //Swap buffers
-        self.window.gl_swap_window();
+        if self.headless_target.is_some() {
+            Framebuffer::bind_default();
+        } else {
+            self.window.gl_swap_window();
+        }
 
-        Ok(())
+        match frame {
+            Ok(Some(frame)) => {
+                if self.keep_frames && self.headless_target.is_none() {
+                    self.last_frame = Some(frame.clone());
+                }
+
+                result = result.and(self.save_captured_frame(frame));
+            }
+            Ok(None) => {}
+            Err(error) => result = result.and(Err(error)),
+        }
+
+        result.and(self.poll_recordings())
+    }
+
+    /// Sets the viewport to the camera's, and clears it according to its settings.
+    fn begin_camera(camera: &Camera, window_size: Vector2u) {
+        let position = camera.viewport.pixel_position(window_size);
+        let size = camera.viewport.pixel_size(window_size);
+
+        unsafe {
+            gl::Viewport(
+                position.x as gl::types::GLint,
+                position.y as gl::types::GLint,
+                size.x as gl::types::GLsizei,
+                size.y as gl::types::GLsizei,
+            );
+
+            let mask = match camera.clear_flags {
+                ClearFlags::ColorAndDepth => gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT,
+                ClearFlags::Depth => gl::DEPTH_BUFFER_BIT,
+                ClearFlags::Nothing => 0,
+            };
+
+            if mask != 0 {
+                //Only clear inside the viewport
+                gl::Enable(gl::SCISSOR_TEST);
+                gl::Scissor(
+                    position.x as gl::types::GLint,
+                    position.y as gl::types::GLint,
+                    size.x as gl::types::GLsizei,
+                    size.y as gl::types::GLsizei,
+                );
+
+                let color = camera.clear_color;
+                gl::ClearColor(color.r, color.g, color.b, color.a);
+                gl::Clear(mask);
+
+                gl::Disable(gl::SCISSOR_TEST);
+            }
+        }
+    }
+
+    /// Draws vertices in world space with the dynamic program.
+    fn draw_dynamic(
+        &self,
+        vertices: &[DynamicVertex],
+        mode: gl::types::GLenum,
+        texture: TextureID,
+    ) {
+        if vertices.is_empty() {
+            return;
+        }
+
+        self.dynamic_program.set_used();
+
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D, texture);
+        }
+
+        self.dynamic_buffer.draw(vertices, mode);
     }
 
     /// Draw a batch.
@@ -263,16 +1174,17 @@ impl GraphicsManager {
         //Check that mesh is valid
         batch.mesh().check()?;
 
-        //Use program
-        batch.program().set_used();
+        //Use program, textures and uniforms
+        batch.material().bind()?;
 
         unsafe {
-            //Bind texture
-            gl::BindTexture(gl::TEXTURE_2D, batch.texture());
-
             //Bind mesh
             gl::BindVertexArray(batch.mesh().vao());
             gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, batch.mesh().ebo());
+
+            //Vertex formats without colors are drawn white.
+            //Reset every time, as drawing a color array leaves the value undefined.
+            gl::VertexAttrib4f(locations::COLOR, 1.0, 1.0, 1.0, 1.0);
         }
 
         //Bind objects data
@@ -292,3 +1204,56 @@ impl GraphicsManager {
         Ok(())
     }
 }
+
+impl Drop for GraphicsManager {
+    fn drop(&mut self) {
+        //Recordings would be cut short if the program exited while they are saved
+        let _ = self.stop_recording();
+        let _ = self.wait_for_recordings();
+    }
+}
+
+/// Adds a draw call to a camera's batches.
+///
+/// If no suitable batch is found, a new one is created.
+fn queue_drawcall(
+    cameras: &mut [Option<CameraEntry>],
+    drawcall: &DrawCall,
+    camera: CameraID,
+) -> Result<(), Error> {
+    let batches = match cameras.get_mut(camera.0) {
+        Some(Some(entry)) => &mut entry.batches,
+        _ => return Err(GraphicsError::CameraNotFound(camera).into()),
+    };
+
+    for batch in batches.iter_mut() {
+        //Attempts to add drawcall to batch
+        if batch.add(drawcall) {
+            return Ok(());
+        }
+    }
+
+    //Could not find suitable batch, create a new one
+    batches.push(Batch::new(drawcall));
+
+    Ok(())
+}
+
+/// Material drawing a single texture, sampled as `Tex`, with `program`.
+///
+/// Materials are created on first use and reused afterwards, so that draw calls can be batched.
+fn texture_material<'m>(
+    materials: &'m mut HashMap<(ProgramID, TextureID, TextureTarget), Material>,
+    program: &Program,
+    texture: &dyn BindableTexture,
+) -> &'m Material {
+    materials
+        .entry((program.id(), texture.id(), texture.target()))
+        .or_insert_with(|| {
+            let mut material = Material::new(program.clone());
+            material
+                .set_texture("Tex", texture)
+                .expect("Could not add texture to empty material.");
+            material
+        })
+}
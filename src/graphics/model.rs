@@ -0,0 +1,469 @@
+//! 3D models loaded from Wavefront OBJ (with MTL) and glTF 2.0 (.gltf and .glb) files.
+//!
+//! A `Model` holds the meshes and materials of a file, and a hierarchy of nodes
+//! placing meshes relative to each other. Skins, animations and cameras are not loaded.
+
+use super::{
+    gltf,
+    material::Material,
+    mesh::Mesh,
+    obj,
+    shaders::{Program, UniformValue},
+    textures::{Texture, TextureOptions},
+    CameraID, GraphicsManager,
+};
+use assets::Asset;
+use cgmath::{self, InnerSpace, SquareMatrix, Zero};
+use failure::Error;
+use maths::{Matrix4f, Vector3f, Vector4f};
+use std::{
+    f32, fs,
+    path::{Path, PathBuf},
+};
+use transform::Transform;
+
+/// Sampler uniform of the base color texture, the one the base program samples.
+pub const BASE_COLOR_TEXTURE: &str = "Tex";
+/// Sampler uniform of the tangent space normal map.
+pub const NORMAL_TEXTURE: &str = "NormalTex";
+/// `vec4` uniform multiplied with the base color texture.
+pub const BASE_COLOR: &str = "BaseColor";
+/// `float` uniform, 0 for dielectrics and 1 for metals.
+pub const METALLIC: &str = "Metallic";
+/// `float` uniform, from 0 (smooth) to 1 (rough).
+pub const ROUGHNESS: &str = "Roughness";
+/// `vec3` uniform, light emitted by the surface.
+pub const EMISSIVE: &str = "Emissive";
+
+/// Tangent of loaded vertices, until tangents are computed.
+pub(crate) const DEFAULT_TANGENT: Vector4f = Vector4f {
+    x: 1.0,
+    y: 0.0,
+    z: 0.0,
+    w: 1.0,
+};
+
+/// Errors related to loading models.
+#[derive(Debug, Fail)]
+pub enum ModelError {
+    /// An OBJ or MTL file could not be parsed. Contains file, line and reason.
+    #[fail(display = "{}:{}: {}", _0, _1, _2)]
+    InvalidObj(String, usize, String),
+    /// A glTF file could not be parsed. Contains reason.
+    #[fail(display = "Invalid glTF: {}", _0)]
+    InvalidGltf(String),
+    /// The glTF file has another major version than 2.
+    #[fail(display = "Unsupported glTF version '{}'", _0)]
+    UnsupportedGltfVersion(String),
+    /// A glTF primitive is drawn as points or lines. Contains the glTF mode.
+    #[fail(display = "Unsupported glTF primitive mode {}", _0)]
+    UnsupportedPrimitiveMode(u32),
+    /// A file references an external file without `ModelOptions::directory`.
+    #[fail(display = "Cannot load '{}' without a directory", _0)]
+    NoDirectory(String),
+}
+
+/// Options for loading a model.
+#[derive(Debug, Clone)]
+pub struct ModelOptions {
+    /// Program of the created materials.
+    pub program: Program,
+    /// Options of the loaded textures.
+    pub texture_options: TextureOptions,
+    /// Directory external files (MTL, buffers, images) are loaded from.
+    ///
+    /// Loading a model referencing external files fails if this is `None`.
+    pub directory: Option<PathBuf>,
+}
+
+impl ModelOptions {
+    /// Options using `program`, default texture options and no directory.
+    pub fn new(program: Program) -> Self {
+        Self {
+            program,
+            texture_options: TextureOptions::default(),
+            directory: None,
+        }
+    }
+
+    /// Reads a file referenced by the model.
+    pub(crate) fn read(&self, name: &str) -> Result<Vec<u8>, Error> {
+        match self.directory {
+            Some(ref directory) => Ok(fs::read(directory.join(name))?),
+            None => Err(ModelError::NoDirectory(name.to_owned()).into()),
+        }
+    }
+}
+
+/// Material of a model, with its name in the file.
+///
+/// Its uniforms are named after the constants of this module.
+#[derive(Debug, Clone)]
+pub struct ModelMaterial {
+    pub name: String,
+    pub material: Material,
+}
+
+/// Part of a mesh drawn with a single material.
+#[derive(Debug, Copy, Clone)]
+pub struct ModelPrimitive {
+    pub mesh: Mesh,
+    /// Index of the material in `Model::materials`.
+    pub material: usize,
+}
+
+/// Mesh of a model, made of primitives.
+#[derive(Debug, Clone)]
+pub struct ModelMesh {
+    pub name: String,
+    pub primitives: Vec<ModelPrimitive>,
+}
+
+/// Node of the hierarchy of a model.
+#[derive(Debug, Clone)]
+pub struct ModelNode {
+    pub name: String,
+    /// Transform relative to the parent node.
+    pub transform: Transform,
+    /// Index of the mesh in `Model::meshes`.
+    pub mesh: Option<usize>,
+    pub parent: Option<usize>,
+    pub children: Vec<usize>,
+}
+
+/// Meshes, materials and node hierarchy loaded from a model file.
+///
+/// This owns the textures of the materials, which stay valid as long as the model exists.
+#[derive(Debug)]
+pub struct Model {
+    meshes: Vec<ModelMesh>,
+    materials: Vec<ModelMaterial>,
+    nodes: Vec<ModelNode>,
+    roots: Vec<usize>,
+    //Only kept alive for the materials using them
+    #[allow(dead_code)]
+    textures: Vec<Texture>,
+}
+
+/// Loads a model, detecting whether the data is OBJ, glTF or GLB.
+impl Asset<ModelOptions> for Model {
+    fn load_from_bytes(data: &[u8], options: ModelOptions) -> Result<Self, Error> {
+        let is_json = data
+            .iter()
+            .find(|byte| !byte.is_ascii_whitespace())
+            .map_or(false, |&byte| byte == b'{');
+
+        if data.starts_with(gltf::GLB_MAGIC) || is_json {
+            gltf::load(data, &options)
+        } else {
+            obj::load(data, "<obj>", &options)
+        }
+    }
+}
+
+impl Model {
+    /// Loads an OBJ file, and the MTL files and textures it references.
+    ///
+    /// Referenced files are relative to the OBJ file, unless `options.directory` is set.
+    pub fn load_obj<P: AsRef<Path>>(path: P, options: ModelOptions) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let name = path.to_string_lossy();
+        obj::load(&fs::read(path)?, &name, &with_directory(options, path))
+    }
+
+    /// Loads a .gltf or .glb file, and the buffers and images it references.
+    ///
+    /// Referenced files are relative to the glTF file, unless `options.directory` is set.
+    pub fn load_gltf<P: AsRef<Path>>(path: P, options: ModelOptions) -> Result<Self, Error> {
+        let path = path.as_ref();
+        gltf::load(&fs::read(path)?, &with_directory(options, path))
+    }
+
+    pub fn meshes(&self) -> &[ModelMesh] {
+        &self.meshes
+    }
+
+    pub fn materials(&self) -> &[ModelMaterial] {
+        &self.materials
+    }
+
+    /// Materials, to change their uniforms or program.
+    pub fn materials_mut(&mut self) -> &mut [ModelMaterial] {
+        &mut self.materials
+    }
+
+    pub fn nodes(&self) -> &[ModelNode] {
+        &self.nodes
+    }
+
+    /// Nodes, to animate their transforms.
+    pub fn nodes_mut(&mut self) -> &mut [ModelNode] {
+        &mut self.nodes
+    }
+
+    /// Indices of the nodes without parent.
+    pub fn roots(&self) -> &[usize] {
+        &self.roots
+    }
+
+    /// Index of the first node named `name`.
+    pub fn find_node(&self, name: &str) -> Option<usize> {
+        self.nodes.iter().position(|node| node.name == name)
+    }
+
+    /// Transform matrix of a node relative to the model, combining those of its parents.
+    pub fn node_matrix(&self, node: usize) -> Matrix4f {
+        let mut matrix = self.nodes[node].transform.matrix();
+        let mut parent = self.nodes[node].parent;
+
+        while let Some(index) = parent {
+            matrix = self.nodes[index].transform.matrix() * matrix;
+            parent = self.nodes[index].parent;
+        }
+
+        matrix
+    }
+
+    /// Draws every mesh of the model, placed by the node hierarchy then by `transform`.
+    pub fn draw(
+        &self,
+        graphics: &mut GraphicsManager,
+        transform: &Transform,
+        camera: CameraID,
+    ) -> Result<(), Error> {
+        let matrix = transform.matrix();
+        for &root in &self.roots {
+            self.draw_node(graphics, root, matrix, camera)?;
+        }
+
+        Ok(())
+    }
+
+    fn draw_node(
+        &self,
+        graphics: &mut GraphicsManager,
+        node: usize,
+        parent_matrix: Matrix4f,
+        camera: CameraID,
+    ) -> Result<(), Error> {
+        let node = &self.nodes[node];
+        let matrix = parent_matrix * node.transform.matrix();
+
+        if let Some(mesh) = node.mesh {
+            for primitive in &self.meshes[mesh].primitives {
+                let material = &self.materials[primitive.material].material;
+                graphics.draw_mesh_matrix(primitive.mesh, material, matrix, camera)?;
+            }
+        }
+
+        for &child in &node.children {
+            self.draw_node(graphics, child, matrix, camera)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Sets the directory of the options to the file's, if there is none.
+fn with_directory(mut options: ModelOptions, path: &Path) -> ModelOptions {
+    if options.directory.is_none() {
+        options.directory = Some(path.parent().map_or_else(PathBuf::new, Path::to_path_buf));
+    }
+
+    options
+}
+
+/// Properties of a material, independent of the file format.
+#[derive(Debug, Clone)]
+pub(crate) struct MaterialDescription {
+    pub name: String,
+    pub base_color: Vector4f,
+    /// Index of the texture in `ModelData::textures`.
+    pub base_color_texture: Option<usize>,
+    pub normal_texture: Option<usize>,
+    pub metallic: f32,
+    pub roughness: f32,
+    pub emissive: Vector3f,
+}
+
+impl MaterialDescription {
+    /// White, rough, dielectric material.
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            base_color: Vector4f::new(1.0, 1.0, 1.0, 1.0),
+            base_color_texture: None,
+            normal_texture: None,
+            metallic: 0.0,
+            roughness: 1.0,
+            emissive: Vector3f::zero(),
+        }
+    }
+}
+
+/// Model being built by a loader.
+#[derive(Debug)]
+pub(crate) struct ModelData {
+    pub meshes: Vec<ModelMesh>,
+    pub materials: Vec<ModelMaterial>,
+    pub nodes: Vec<ModelNode>,
+    pub textures: Vec<Texture>,
+    /// Texture bound to materials without a base color texture.
+    white_texture: Option<usize>,
+    /// Material of primitives without one.
+    default_material: Option<usize>,
+}
+
+impl ModelData {
+    pub fn new() -> Self {
+        Self {
+            meshes: Vec::new(),
+            materials: Vec::new(),
+            nodes: Vec::new(),
+            textures: Vec::new(),
+            white_texture: None,
+            default_material: None,
+        }
+    }
+
+    /// Adds a texture, returning its index.
+    pub fn add_texture(&mut self, texture: Texture) -> usize {
+        self.textures.push(texture);
+        self.textures.len() - 1
+    }
+
+    /// Creates a material, returning its index.
+    pub fn add_material(
+        &mut self,
+        description: &MaterialDescription,
+        options: &ModelOptions,
+    ) -> Result<usize, Error> {
+        let base_color_texture = match description.base_color_texture {
+            Some(texture) => texture,
+            None => self.white_texture()?,
+        };
+
+        let mut material = Material::new(options.program.clone());
+        material.set_texture(BASE_COLOR_TEXTURE, &self.textures[base_color_texture])?;
+        if let Some(texture) = description.normal_texture {
+            material.set_texture(NORMAL_TEXTURE, &self.textures[texture])?;
+        }
+
+        material.set_uniform(BASE_COLOR, UniformValue::Vec4(description.base_color))?;
+        material.set_uniform(METALLIC, UniformValue::Float(description.metallic))?;
+        material.set_uniform(ROUGHNESS, UniformValue::Float(description.roughness))?;
+        material.set_uniform(EMISSIVE, UniformValue::Vec3(description.emissive))?;
+
+        self.materials.push(ModelMaterial {
+            name: description.name.clone(),
+            material,
+        });
+
+        Ok(self.materials.len() - 1)
+    }
+
+    /// Index of the material used by primitives without one, created on first use.
+    pub fn default_material(&mut self, options: &ModelOptions) -> Result<usize, Error> {
+        match self.default_material {
+            Some(material) => Ok(material),
+            None => {
+                let material = self.add_material(&MaterialDescription::new(""), options)?;
+                self.default_material = Some(material);
+                Ok(material)
+            }
+        }
+    }
+
+    /// Adds a node without parent or children, returning its index.
+    pub fn add_node(&mut self, name: &str, transform: Transform, mesh: Option<usize>) -> usize {
+        self.nodes.push(ModelNode {
+            name: name.to_owned(),
+            transform,
+            mesh,
+            parent: None,
+            children: Vec::new(),
+        });
+
+        self.nodes.len() - 1
+    }
+
+    /// Finishes the model. Roots are the nodes without parent if `roots` is `None`.
+    pub fn into_model(self, roots: Option<Vec<usize>>) -> Model {
+        let roots = roots.unwrap_or_else(|| {
+            (0..self.nodes.len())
+                .filter(|&node| self.nodes[node].parent.is_none())
+                .collect()
+        });
+
+        Model {
+            meshes: self.meshes,
+            materials: self.materials,
+            nodes: self.nodes,
+            roots,
+            textures: self.textures,
+        }
+    }
+
+    fn white_texture(&mut self) -> Result<usize, Error> {
+        match self.white_texture {
+            Some(texture) => Ok(texture),
+            None => {
+                let texture = Texture::from_bytes(&[0xFF; 4], TextureOptions::default(), 1, 1)?;
+                let index = self.add_texture(texture);
+                self.white_texture = Some(index);
+                Ok(index)
+            }
+        }
+    }
+}
+
+/// Splits a matrix into the position, rotation and scale of a `Transform`.
+///
+/// Shear cannot be represented, and is lost.
+pub(crate) fn decompose_matrix(matrix: Matrix4f) -> Transform {
+    let mut scale = Vector3f::new(
+        matrix.x.truncate().magnitude(),
+        matrix.y.truncate().magnitude(),
+        matrix.z.truncate().magnitude(),
+    );
+
+    //Mirrored matrices have a negative determinant
+    if matrix.determinant() < 0.0 {
+        scale.x = -scale.x;
+    }
+
+    //The rotation of a flattened matrix is unknown, and dividing by its scale would give NaN
+    if [scale.x, scale.y, scale.z]
+        .iter()
+        .any(|axis| axis.abs() < f32::EPSILON)
+    {
+        return Transform {
+            position: matrix.w.truncate(),
+            scale,
+            rotation: Vector3f::zero(),
+        };
+    }
+
+    let rotation = cgmath::Matrix3::from_cols(
+        matrix.x.truncate() / scale.x,
+        matrix.y.truncate() / scale.y,
+        matrix.z.truncate() / scale.z,
+    );
+
+    Transform {
+        position: matrix.w.truncate(),
+        scale,
+        rotation: quaternion_to_euler(cgmath::Quaternion::from(rotation)),
+    }
+}
+
+/// Euler angles in degrees of a rotation, as used by `Transform`.
+pub(crate) fn quaternion_to_euler(quaternion: cgmath::Quaternion<f32>) -> Vector3f {
+    let euler: cgmath::Euler<cgmath::Rad<f32>> = quaternion.into();
+
+    Vector3f::new(
+        cgmath::Deg::from(euler.x).0,
+        cgmath::Deg::from(euler.y).0,
+        cgmath::Deg::from(euler.z).0,
+    )
+}
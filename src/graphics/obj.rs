@@ -0,0 +1,440 @@
+//! Loader for Wavefront OBJ files and the MTL material libraries they reference.
+//!
+//! Faces are triangulated as fans, and each object (`o`) or group (`g`) becomes a node.
+//! Normals are computed for faces without them, and tangents for every face. MTL files provide
+//! the diffuse color, opacity, emission, the PBR extension's metallic and roughness,
+//! and diffuse and bump maps.
+
+use super::{
+    mesh::{LitVertex, MeshBuilder},
+    model::{
+        self, MaterialDescription, Model, ModelData, ModelError, ModelMesh, ModelOptions,
+        ModelPrimitive,
+    },
+    textures::Texture,
+};
+use assets::Asset;
+use cgmath::Zero;
+use failure::Error;
+use maths::{Vector2f, Vector3f};
+use std::collections::HashMap;
+use std::str::SplitWhitespace;
+use transform::Transform;
+
+/// Indices of the position, UV and normal of a face corner, from 0.
+type Corner = (usize, Option<usize>, Option<usize>);
+
+/// Triangles of an object drawn with the same material.
+struct Group {
+    material: Option<String>,
+    builder: MeshBuilder<LitVertex>,
+    /// Vertex created for each distinct corner.
+    vertices: HashMap<Corner, u32>,
+    /// False if a corner has no normal, in which case normals are computed.
+    has_normals: bool,
+}
+
+impl Group {
+    fn new(material: Option<String>) -> Self {
+        Self {
+            material,
+            builder: MeshBuilder::new(),
+            vertices: HashMap::new(),
+            has_normals: true,
+        }
+    }
+
+    /// Index of the vertex of a corner, added if needed.
+    fn vertex(&mut self, corner: Corner, attributes: &Attributes) -> u32 {
+        if corner.2.is_none() {
+            self.has_normals = false;
+        }
+
+        if let Some(&index) = self.vertices.get(&corner) {
+            return index;
+        }
+
+        let (position, uv, normal) = corner;
+        let uv = uv.map_or(Vector2f::zero(), |uv| attributes.uvs[uv]);
+        let index = self.builder.vertices.len() as u32;
+
+        self.builder.vertices.push(LitVertex {
+            position: attributes.positions[position],
+            uv,
+            normal: normal.map_or(Vector3f::zero(), |normal| attributes.normals[normal]),
+            tangent: model::DEFAULT_TANGENT,
+            uv2: uv,
+        });
+        self.vertices.insert(corner, index);
+
+        index
+    }
+}
+
+/// Object or group of the file, becoming a node of the model.
+struct Object {
+    name: String,
+    groups: Vec<Group>,
+}
+
+impl Object {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            groups: Vec::new(),
+        }
+    }
+
+    /// Group of the object using `material`, added if needed.
+    fn group(&mut self, material: &Option<String>) -> &mut Group {
+        let index = match self
+            .groups
+            .iter()
+            .position(|group| group.material == *material)
+        {
+            Some(index) => index,
+            None => {
+                self.groups.push(Group::new(material.clone()));
+                self.groups.len() - 1
+            }
+        };
+
+        &mut self.groups[index]
+    }
+}
+
+/// Vertex attributes declared so far, shared by every object.
+struct Attributes {
+    positions: Vec<Vector3f>,
+    uvs: Vec<Vector2f>,
+    normals: Vec<Vector3f>,
+}
+
+/// Loads a model from the contents of the OBJ file `name`.
+pub(crate) fn load(data: &[u8], name: &str, options: &ModelOptions) -> Result<Model, Error> {
+    let source = String::from_utf8_lossy(data);
+
+    let mut attributes = Attributes {
+        positions: Vec::new(),
+        uvs: Vec::new(),
+        normals: Vec::new(),
+    };
+    let mut objects = vec![Object::new("")];
+    let mut material = None;
+    let mut libraries = Vec::new();
+
+    for (index, line) in source.lines().enumerate() {
+        let line_number = index + 1;
+        let line = strip_comment(line);
+        let mut words = line.split_whitespace();
+
+        let keyword = match words.next() {
+            Some(keyword) => keyword,
+            None => continue,
+        };
+        let argument = line[keyword.len()..].trim();
+
+        match keyword {
+            "v" => {
+                let position = parse_vector3(&mut words)
+                    .ok_or_else(|| invalid(name, line_number, "invalid position"))?;
+                attributes.positions.push(position);
+            }
+            "vt" => {
+                let u = parse_float(words.next());
+                let v = parse_float(words.next()).or(Some(0.0));
+                match (u, v) {
+                    //OBJ textures start at the bottom, OpenGL ones at the top
+                    (Some(u), Some(v)) => attributes.uvs.push(Vector2f::new(u, 1.0 - v)),
+                    _ => return Err(invalid(name, line_number, "invalid texture coordinates")),
+                }
+            }
+            "vn" => {
+                let normal = parse_vector3(&mut words)
+                    .ok_or_else(|| invalid(name, line_number, "invalid normal"))?;
+                attributes.normals.push(normal);
+            }
+            "f" => {
+                let corners = words
+                    .map(|word| parse_corner(word, &attributes))
+                    .collect::<Option<Vec<_>>>()
+                    .ok_or_else(|| invalid(name, line_number, "invalid face"))?;
+                if corners.len() < 3 {
+                    return Err(invalid(name, line_number, "face has less than 3 vertices"));
+                }
+
+                let group = objects
+                    .last_mut()
+                    .expect("OBJ objects are never empty")
+                    .group(&material);
+                let indices = corners
+                    .into_iter()
+                    .map(|corner| group.vertex(corner, &attributes))
+                    .collect::<Vec<_>>();
+
+                //Triangulate as a fan, which is correct for convex polygons
+                for i in 1..indices.len() - 1 {
+                    group.builder.indices.extend_from_slice(&[
+                        indices[0],
+                        indices[i],
+                        indices[i + 1],
+                    ]);
+                }
+            }
+            "o" | "g" => {
+                let object = objects.last_mut().expect("OBJ objects are never empty");
+                if object.groups.is_empty() {
+                    object.name = argument.to_owned();
+                } else {
+                    objects.push(Object::new(argument));
+                }
+            }
+            "usemtl" => material = Some(argument.to_owned()),
+            "mtllib" => libraries.extend(words.map(str::to_owned)),
+            //Smoothing groups, lines, points and free-form geometry are ignored
+            _ => {}
+        }
+    }
+
+    let mut model = ModelData::new();
+    let mut textures = HashMap::new();
+    let mut descriptions = HashMap::new();
+
+    for library in &libraries {
+        let data = options.read(library)?;
+        load_library(
+            library,
+            &String::from_utf8_lossy(&data),
+            options,
+            &mut model,
+            &mut textures,
+            &mut descriptions,
+        )?;
+    }
+
+    let mut materials = HashMap::new();
+
+    for object in objects {
+        let mut primitives = Vec::new();
+
+        for mut group in object.groups {
+            if group.builder.indices.is_empty() {
+                continue;
+            }
+
+            if !group.has_normals {
+                group.builder.compute_smooth_normals();
+            }
+            group.builder.compute_tangents();
+
+            let material = match group
+                .material
+                .and_then(|material| descriptions.get(&material))
+            {
+                Some(description) => match materials.get(&description.name) {
+                    Some(&material) => material,
+                    None => {
+                        let material = model.add_material(description, options)?;
+                        materials.insert(description.name.clone(), material);
+                        material
+                    }
+                },
+                None => model.default_material(options)?,
+            };
+
+            primitives.push(ModelPrimitive {
+                mesh: group.builder.build(),
+                material,
+            });
+        }
+
+        if primitives.is_empty() {
+            continue;
+        }
+
+        model.meshes.push(ModelMesh {
+            name: object.name.clone(),
+            primitives,
+        });
+        let mesh = model.meshes.len() - 1;
+        model.add_node(&object.name, Transform::new(), Some(mesh));
+    }
+
+    Ok(model.into_model(None))
+}
+
+/// Parses the materials of the MTL file `name`.
+fn load_library(
+    name: &str,
+    source: &str,
+    options: &ModelOptions,
+    model: &mut ModelData,
+    textures: &mut HashMap<String, usize>,
+    descriptions: &mut HashMap<String, MaterialDescription>,
+) -> Result<(), Error> {
+    let mut current: Option<MaterialDescription> = None;
+
+    for (index, line) in source.lines().enumerate() {
+        let line_number = index + 1;
+        let line = strip_comment(line);
+        let mut words = line.split_whitespace();
+
+        let keyword = match words.next() {
+            Some(keyword) => keyword,
+            None => continue,
+        };
+
+        if keyword == "newmtl" {
+            if let Some(description) = current.take() {
+                descriptions.insert(description.name.clone(), description);
+            }
+
+            current = Some(MaterialDescription::new(line[keyword.len()..].trim()));
+            continue;
+        }
+
+        let description = match current {
+            Some(ref mut description) => description,
+            None => return Err(invalid(name, line_number, "property outside of a material")),
+        };
+
+        let invalid_value = || invalid(name, line_number, &format!("invalid {}", keyword));
+
+        match keyword {
+            "Kd" => {
+                let color = parse_vector3(&mut words).ok_or_else(invalid_value)?;
+                description.base_color = color.extend(description.base_color.w);
+            }
+            "d" => {
+                description.base_color.w = parse_float(words.next()).ok_or_else(invalid_value)?
+            }
+            "Tr" => {
+                description.base_color.w =
+                    1.0 - parse_float(words.next()).ok_or_else(invalid_value)?
+            }
+            "Ke" => description.emissive = parse_vector3(&mut words).ok_or_else(invalid_value)?,
+            "Pm" => description.metallic = parse_float(words.next()).ok_or_else(invalid_value)?,
+            "Pr" => description.roughness = parse_float(words.next()).ok_or_else(invalid_value)?,
+            "map_Kd" | "map_Bump" | "map_bump" | "bump" | "norm" => {
+                //Options come before the file name
+                let file = words.last().ok_or_else(invalid_value)?;
+                let texture = load_texture(file, options, model, textures)?;
+
+                if keyword == "map_Kd" {
+                    description.base_color_texture = Some(texture);
+                } else {
+                    description.normal_texture = Some(texture);
+                }
+            }
+            //Phong parameters and other maps are ignored
+            _ => {}
+        }
+    }
+
+    if let Some(description) = current {
+        descriptions.insert(description.name.clone(), description);
+    }
+
+    Ok(())
+}
+
+/// Index of a texture file in the model, loaded if needed.
+fn load_texture(
+    file: &str,
+    options: &ModelOptions,
+    model: &mut ModelData,
+    textures: &mut HashMap<String, usize>,
+) -> Result<usize, Error> {
+    if let Some(&texture) = textures.get(file) {
+        return Ok(texture);
+    }
+
+    let texture = Texture::load_from_bytes(&options.read(file)?, options.texture_options)?;
+    let index = model.add_texture(texture);
+    textures.insert(file.to_owned(), index);
+
+    Ok(index)
+}
+
+/// Parses a face corner: `v`, `v/vt`, `v//vn` or `v/vt/vn`.
+fn parse_corner(word: &str, attributes: &Attributes) -> Option<Corner> {
+    let mut parts = word.split('/');
+
+    let position = resolve_index(parts.next()?, attributes.positions.len())?;
+    let uv = match parts.next() {
+        None | Some("") => None,
+        Some(part) => Some(resolve_index(part, attributes.uvs.len())?),
+    };
+    let normal = match parts.next() {
+        None | Some("") => None,
+        Some(part) => Some(resolve_index(part, attributes.normals.len())?),
+    };
+
+    Some((position, uv, normal))
+}
+
+/// Converts an OBJ index, starting at 1 or relative to the end if negative, to an index from 0.
+fn resolve_index(text: &str, count: usize) -> Option<usize> {
+    let index: i64 = text.parse().ok()?;
+
+    if index > 0 && (index as usize) <= count {
+        Some(index as usize - 1)
+    } else if index < 0 && (-index as usize) <= count {
+        Some(count - (-index as usize))
+    } else {
+        None
+    }
+}
+
+fn parse_float(word: Option<&str>) -> Option<f32> {
+    word?.parse().ok()
+}
+
+fn parse_vector3(words: &mut SplitWhitespace) -> Option<Vector3f> {
+    Some(Vector3f::new(
+        parse_float(words.next())?,
+        parse_float(words.next())?,
+        parse_float(words.next())?,
+    ))
+}
+
+fn strip_comment(line: &str) -> &str {
+    let line = match line.find('#') {
+        Some(index) => &line[..index],
+        None => line,
+    };
+
+    line.trim()
+}
+
+fn invalid(file: &str, line: usize, reason: &str) -> Error {
+    ModelError::InvalidObj(file.to_owned(), line, reason.to_owned()).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn positive_indices_start_at_1() {
+        assert_eq!(resolve_index("1", 3), Some(0));
+        assert_eq!(resolve_index("3", 3), Some(2));
+        assert_eq!(resolve_index("4", 3), None);
+        assert_eq!(resolve_index("0", 3), None);
+    }
+
+    #[test]
+    fn negative_indices_are_relative_to_the_end() {
+        assert_eq!(resolve_index("-1", 3), Some(2));
+        assert_eq!(resolve_index("-3", 3), Some(0));
+        assert_eq!(resolve_index("-4", 3), None);
+    }
+
+    #[test]
+    fn invalid_indices_are_rejected() {
+        assert_eq!(resolve_index("", 3), None);
+        assert_eq!(resolve_index("1.5", 3), None);
+        assert_eq!(resolve_index("a", 3), None);
+        assert_eq!(resolve_index("1", 0), None);
+    }
+}
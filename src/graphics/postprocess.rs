@@ -0,0 +1,341 @@
+use super::{
+    framebuffer::Framebuffer,
+    program_cache::{self, ProgramCache},
+    shaders::{Program, ShaderType, UniformValue},
+};
+use failure::Error;
+use gl;
+use maths::{Vector2f, Vector2u, Vector3f};
+
+/// Settings for the built-in color grading pass.
+#[derive(Debug, Copy, Clone)]
+pub struct ColorGrading {
+    /// Multiplier applied to the color before anything else.
+    pub exposure: f32,
+    /// Contrast around middle gray. 1.0 is neutral.
+    pub contrast: f32,
+    /// 0.0 is grayscale, 1.0 is neutral.
+    pub saturation: f32,
+    /// Gamma correction. 1.0 is neutral.
+    pub gamma: f32,
+    /// Color multiplied with the image.
+    pub tint: Vector3f,
+}
+
+impl Default for ColorGrading {
+    fn default() -> Self {
+        Self {
+            exposure: 1.0,
+            contrast: 1.0,
+            saturation: 1.0,
+            gamma: 1.0,
+            tint: Vector3f::new(1.0, 1.0, 1.0),
+        }
+    }
+}
+
+/// A fullscreen shader pass applied to the rendered image.
+///
+/// The fragment shader receives the previous image as `sampler2D Screen`,
+/// its size in pixels as `vec2 ScreenSize`, and the fullscreen UV as `in vec2 UVCoord`.
+#[derive(Debug)]
+pub struct PostProcessPass {
+    program: Program,
+    uniforms: Vec<(String, UniformValue)>,
+    /// Disabled passes are skipped when rendering.
+    pub enabled: bool,
+}
+
+impl PostProcessPass {
+    /// Creates a pass from a linked program.
+    ///
+    /// The program's vertex shader must cover the screen by itself;
+    /// see `from_fragment_source` to use the built-in one.
+    pub fn new(program: Program) -> Self {
+        Self {
+            program,
+            uniforms: Vec::new(),
+            enabled: true,
+        }
+    }
+
+    /// Creates a pass from the GLSL source of a fragment shader,
+    /// using the built-in fullscreen vertex shader.
+    ///
+    /// The program is loaded from `cache` when one is given,
+    /// such as `GraphicsManager::program_cache`.
+    pub fn from_fragment_source(source: &str, cache: Option<&ProgramCache>) -> Result<Self, Error> {
+        Ok(Self::new(program_cache::program_from_sources(
+            cache,
+            &[
+                (
+                    ShaderType::Vertex,
+                    include_str!("shaders/postprocess/fullscreen.vert"),
+                ),
+                (ShaderType::Fragment, source),
+            ],
+        )?))
+    }
+
+    /// Color grading: exposure, contrast, saturation, gamma and tint.
+    pub fn color_grading(
+        settings: ColorGrading,
+        cache: Option<&ProgramCache>,
+    ) -> Result<Self, Error> {
+        let mut pass = Self::from_fragment_source(
+            include_str!("shaders/postprocess/color_grading.frag"),
+            cache,
+        )?;
+
+        pass.set_uniform("Exposure", UniformValue::Float(settings.exposure));
+        pass.set_uniform("Contrast", UniformValue::Float(settings.contrast));
+        pass.set_uniform("Saturation", UniformValue::Float(settings.saturation));
+        pass.set_uniform("Gamma", UniformValue::Float(settings.gamma));
+        pass.set_uniform("Tint", UniformValue::Vec3(settings.tint));
+
+        Ok(pass)
+    }
+
+    /// Darkens the edges of the screen.
+    ///
+    /// `radius` and `softness` are relative to the screen height.
+    pub fn vignette(
+        intensity: f32,
+        radius: f32,
+        softness: f32,
+        cache: Option<&ProgramCache>,
+    ) -> Result<Self, Error> {
+        let mut pass =
+            Self::from_fragment_source(include_str!("shaders/postprocess/vignette.frag"), cache)?;
+
+        pass.set_uniform("Intensity", UniformValue::Float(intensity));
+        pass.set_uniform("Radius", UniformValue::Float(radius));
+        pass.set_uniform("Softness", UniformValue::Float(softness));
+        pass.set_uniform("VignetteColor", UniformValue::Vec3(Vector3f::new(0.0, 0.0, 0.0)));
+
+        Ok(pass)
+    }
+
+    /// Makes pixels brighter than `threshold` glow.
+    ///
+    /// `radius` is the glow size in pixels.
+    pub fn bloom(
+        threshold: f32,
+        intensity: f32,
+        radius: f32,
+        cache: Option<&ProgramCache>,
+    ) -> Result<Self, Error> {
+        let mut pass =
+            Self::from_fragment_source(include_str!("shaders/postprocess/bloom.frag"), cache)?;
+
+        pass.set_uniform("Threshold", UniformValue::Float(threshold));
+        pass.set_uniform("Intensity", UniformValue::Float(intensity));
+        pass.set_uniform("Radius", UniformValue::Float(radius));
+
+        Ok(pass)
+    }
+
+    /// CRT monitor effect: screen curvature and scanlines.
+    pub fn crt(
+        curvature: f32,
+        scanline_intensity: f32,
+        scanline_count: f32,
+        cache: Option<&ProgramCache>,
+    ) -> Result<Self, Error> {
+        let mut pass =
+            Self::from_fragment_source(include_str!("shaders/postprocess/crt.frag"), cache)?;
+
+        pass.set_uniform("Curvature", UniformValue::Float(curvature));
+        pass.set_uniform("ScanlineIntensity", UniformValue::Float(scanline_intensity));
+        pass.set_uniform("ScanlineCount", UniformValue::Float(scanline_count));
+
+        Ok(pass)
+    }
+
+    /// Fast approximate anti-aliasing.
+    pub fn fxaa(cache: Option<&ProgramCache>) -> Result<Self, Error> {
+        Self::from_fragment_source(include_str!("shaders/postprocess/fxaa.frag"), cache)
+    }
+
+    /// Shader program used by this pass.
+    pub fn program(&self) -> &Program {
+        &self.program
+    }
+
+    /// Sets a uniform that will be applied every time the pass is rendered.
+    pub fn set_uniform(&mut self, name: &str, value: UniformValue) {
+        match self.uniforms.iter_mut().find(|(n, _)| n == name) {
+            Some((_, v)) => *v = value,
+            None => self.uniforms.push((name.to_owned(), value)),
+        }
+    }
+
+    /// Gets the value of a uniform previously set with `set_uniform`.
+    pub fn uniform(&self, name: &str) -> Option<UniformValue> {
+        self.uniforms
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|&(_, value)| value)
+    }
+}
+
+/// Chain of fullscreen passes applied after the scene is rendered.
+///
+/// The scene is rendered to an offscreen framebuffer when at least one pass is enabled,
+/// then each pass reads the output of the previous one.
+pub struct PostProcessStack {
+    passes: Vec<PostProcessPass>,
+    /// Scene target and ping-pong target, created when first needed.
+    framebuffers: Vec<Framebuffer>,
+    /// Empty VAO used to draw the fullscreen triangle.
+    vao: gl::types::GLuint,
+}
+
+impl Drop for PostProcessStack {
+    fn drop(&mut self) {
+        unsafe { gl::DeleteVertexArrays(1, &self.vao) }
+    }
+}
+
+impl Default for PostProcessStack {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PostProcessStack {
+    /// Creates an empty stack.
+    pub fn new() -> Self {
+        let mut vao = 0;
+        unsafe {
+            gl::GenVertexArrays(1, &mut vao);
+        }
+
+        Self {
+            passes: Vec::new(),
+            framebuffers: Vec::new(),
+            vao,
+        }
+    }
+
+    /// Adds a pass at the end of the chain. Returns its index.
+    pub fn push(&mut self, pass: PostProcessPass) -> usize {
+        self.passes.push(pass);
+        self.passes.len() - 1
+    }
+
+    /// Removes and returns the pass at `index`.
+    pub fn remove(&mut self, index: usize) -> PostProcessPass {
+        self.passes.remove(index)
+    }
+
+    /// Removes all passes.
+    pub fn clear(&mut self) {
+        self.passes.clear();
+    }
+
+    /// Gets the pass at `index`.
+    pub fn pass(&self, index: usize) -> Option<&PostProcessPass> {
+        self.passes.get(index)
+    }
+
+    /// Gets the pass at `index`, to change its uniforms or disable it.
+    pub fn pass_mut(&mut self, index: usize) -> Option<&mut PostProcessPass> {
+        self.passes.get_mut(index)
+    }
+
+    /// Number of passes in the chain, including disabled ones.
+    pub fn len(&self) -> usize {
+        self.passes.len()
+    }
+
+    /// Returns true if there are no passes in the chain.
+    pub fn is_empty(&self) -> bool {
+        self.passes.is_empty()
+    }
+
+    /// Returns true if at least one pass is enabled.
+    pub fn is_active(&self) -> bool {
+        self.passes.iter().any(|pass| pass.enabled)
+    }
+
+    /// Binds the offscreen framebuffer the scene should be rendered to.
+    pub(crate) fn begin(&mut self, size: Vector2u) -> Result<(), Error> {
+        //Create or resize targets
+        if self.framebuffers.is_empty() {
+            self.framebuffers.push(Framebuffer::new(size.x, size.y)?);
+            self.framebuffers.push(Framebuffer::new(size.x, size.y)?);
+        } else {
+            for framebuffer in &mut self.framebuffers {
+                framebuffer.resize(size.x, size.y)?;
+            }
+        }
+
+        self.framebuffers[0].bind();
+
+        Ok(())
+    }
+
+    /// Runs every enabled pass on the scene rendered since `begin`.
+    ///
+    /// The last pass renders to `target`, or to the window if `None`.
+    ///
+    /// Fails if a uniform has another type in the pass' program,
+    /// once every pass is drawn and the state is restored.
+    pub(crate) fn apply(&self, size: Vector2u, target: Option<&Framebuffer>) -> Result<(), Error> {
+        let enabled: Vec<&PostProcessPass> =
+            self.passes.iter().filter(|pass| pass.enabled).collect();
+
+        unsafe {
+            gl::Disable(gl::DEPTH_TEST);
+            gl::Disable(gl::BLEND);
+            gl::Viewport(0, 0, size.x as gl::types::GLint, size.y as gl::types::GLint);
+            gl::BindVertexArray(self.vao);
+            gl::ActiveTexture(gl::TEXTURE0);
+        }
+
+        let mut source = 0;
+        let mut result = Ok(());
+
+        for (i, pass) in enabled.iter().enumerate() {
+            //Select output: next ping-pong buffer, or final target
+            if i + 1 == enabled.len() {
+                match target {
+                    Some(framebuffer) => framebuffer.bind(),
+                    None => Framebuffer::bind_default(),
+                }
+            } else {
+                self.framebuffers[1 - source].bind();
+            }
+
+            //Passes are drawn even if a uniform fails, so the image reaches the target
+            pass.program.set_used();
+            result = result.and(pass.program.set_int("Screen", 0).map(|_| ()));
+            result = result.and(
+                pass.program
+                    .set_vec2("ScreenSize", Vector2f::new(size.x as f32, size.y as f32))
+                    .map(|_| ()),
+            );
+
+            for &(ref name, value) in &pass.uniforms {
+                result = result.and(pass.program.set_uniform(name, value).map(|_| ()));
+            }
+
+            unsafe {
+                gl::BindTexture(gl::TEXTURE_2D, self.framebuffers[source].texture().id());
+                gl::DrawArrays(gl::TRIANGLES, 0, 3);
+            }
+
+            source = 1 - source;
+        }
+
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+            gl::BindVertexArray(0);
+            gl::Enable(gl::DEPTH_TEST);
+            gl::Enable(gl::BLEND);
+        }
+
+        result.map_err(Error::from)
+    }
+}
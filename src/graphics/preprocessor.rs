@@ -0,0 +1,399 @@
+//! Preprocessor run on GLSL sources before they are compiled.
+//!
+//! It prepends the `#version` line and `#define`s, and resolves `#include "name"` directives
+//! through an `AssetDatabase`. Lines of the result are mapped back to their original file,
+//! so that compilation errors point to the right place.
+
+use super::shaders::ShaderType;
+use assets::AssetDatabase;
+use failure::Error;
+use std::collections::{btree_map, BTreeMap};
+
+/// Name of the lines added by the preprocessor, in mapped error logs.
+const GENERATED_FILE: &str = "<generated>";
+
+/// Errors related to preprocessing shaders.
+#[derive(Debug, Fail)]
+pub enum PreprocessorError {
+    /// An `#include` directive is not followed by a quoted name. Contains file and line.
+    #[fail(display = "{}:{}: invalid #include directive", _0, _1)]
+    InvalidInclude(String, usize),
+    /// A file was included without an `AssetDatabase` to load it from. Contains included name.
+    #[fail(display = "Cannot include '{}' without an asset database", _0)]
+    NoAssetDatabase(String),
+}
+
+/// Set of `#define`s, selecting a variant of a shader.
+///
+/// Defines are sorted by name, so equal sets produce the same source.
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ShaderDefines {
+    defines: BTreeMap<String, String>,
+}
+
+impl ShaderDefines {
+    /// Creates an empty set.
+    pub fn new() -> Self {
+        Self {
+            defines: BTreeMap::new(),
+        }
+    }
+
+    /// Adds a define with an empty value, as `#define NAME`.
+    pub fn with(self, name: &str) -> Self {
+        self.with_value(name, "")
+    }
+
+    /// Adds a define with a value, as `#define NAME VALUE`.
+    pub fn with_value(mut self, name: &str, value: &str) -> Self {
+        self.set(name, value);
+        self
+    }
+
+    /// Adds a define with a value, or changes its value.
+    pub fn set(&mut self, name: &str, value: &str) {
+        self.defines.insert(name.to_owned(), value.to_owned());
+    }
+
+    pub fn remove(&mut self, name: &str) {
+        self.defines.remove(name);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.defines.get(name).map(String::as_str)
+    }
+
+    pub fn iter(&self) -> btree_map::Iter<String, String> {
+        self.defines.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.defines.is_empty()
+    }
+}
+
+/// Result of preprocessing a shader source.
+#[derive(Debug, Clone)]
+pub struct PreprocessedSource {
+    source: String,
+    /// Names of the files lines come from.
+    files: Vec<String>,
+    /// Index in `files` and line number of every line of `source`.
+    lines: Vec<(usize, usize)>,
+}
+
+impl PreprocessedSource {
+    /// Preprocesses the source of the file `name`, a shader of type `shader_type`.
+    ///
+    /// If the source has no `#version` line, the lowest version supporting `shader_type`
+    /// is used (see `ShaderType::default_version`).
+    ///
+    /// `#include`s are loaded from `assets`, and fail if it is `None`.
+    /// Each file is only included once, so shared files do not need include guards.
+    pub fn new(
+        name: &str,
+        source: &str,
+        shader_type: ShaderType,
+        defines: &ShaderDefines,
+        assets: Option<&AssetDatabase>,
+    ) -> Result<Self, Error> {
+        let mut preprocessed = Self {
+            source: String::new(),
+            files: vec![GENERATED_FILE.to_owned()],
+            lines: Vec::new(),
+        };
+
+        //Version must come first, then defines
+        let version = source
+            .lines()
+            .map(str::trim)
+            .find(|line| line.starts_with("#version"))
+            .unwrap_or_else(|| shader_type.default_version());
+        preprocessed.push_line(version, 0, 0);
+
+        for (define, value) in defines.iter() {
+            preprocessed.push_line(&format!("#define {} {}", define, value), 0, 0);
+        }
+
+        let mut included = vec![name.to_owned()];
+        preprocessed.append(name, source, assets, &mut included)?;
+
+        Ok(preprocessed)
+    }
+
+    /// Preprocessed GLSL source.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// File and line a line of the preprocessed source comes from, both starting at 1.
+    pub fn origin(&self, line: usize) -> Option<(&str, usize)> {
+        let &(file, original) = self.lines.get(line.checked_sub(1)?)?;
+        Some((&self.files[file], original))
+    }
+
+    /// Replaces line references in a compilation log with the original file and line.
+    ///
+    /// Recognizes the `0:LINE` and `0(LINE)` formats drivers use.
+    pub fn map_log(&self, log: &str) -> String {
+        log.lines()
+            .map(|line| self.map_log_line(line).unwrap_or_else(|| line.to_owned()))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Appends the lines of a file, resolving its includes.
+    fn append(
+        &mut self,
+        file: &str,
+        source: &str,
+        assets: Option<&AssetDatabase>,
+        included: &mut Vec<String>,
+    ) -> Result<(), Error> {
+        let file_index = self.files.len();
+        self.files.push(file.to_owned());
+
+        for (index, line) in source.lines().enumerate() {
+            let line_number = index + 1;
+            let directive = line.trim();
+
+            //Already written first, keep an empty line so that line numbers still match
+            if directive.starts_with("#version") {
+                self.push_line("", file_index, line_number);
+                continue;
+            }
+
+            if directive.starts_with("#include") {
+                let name = match parse_include(directive) {
+                    Some(name) => name,
+                    None => {
+                        return Err(
+                            PreprocessorError::InvalidInclude(file.to_owned(), line_number).into(),
+                        )
+                    }
+                };
+
+                if !included.iter().any(|included| included == name) {
+                    included.push(name.to_owned());
+
+                    let data = match assets {
+                        Some(assets) => assets.read(name)?,
+                        None => {
+                            return Err(PreprocessorError::NoAssetDatabase(name.to_owned()).into())
+                        }
+                    };
+
+                    self.append(name, &String::from_utf8(data)?, assets, included)?;
+                }
+
+                continue;
+            }
+
+            self.push_line(line, file_index, line_number);
+        }
+
+        Ok(())
+    }
+
+    fn push_line(&mut self, line: &str, file: usize, line_number: usize) {
+        self.source.push_str(line);
+        self.source.push('\n');
+        self.lines.push((file, line_number));
+    }
+
+    /// Maps the first line reference of a log line, if any.
+    fn map_log_line(&self, line: &str) -> Option<String> {
+        let bytes = line.as_bytes();
+
+        for (start, &byte) in bytes.iter().enumerate() {
+            //Source string number, always 0 as shaders are compiled from a single string
+            if byte != b'0' || (start > 0 && bytes[start - 1].is_ascii_digit()) {
+                continue;
+            }
+
+            let separator = match bytes.get(start + 1) {
+                Some(&separator) if separator == b':' || separator == b'(' => separator,
+                _ => continue,
+            };
+
+            let digits_start = start + 2;
+            let digits_end = digits_start
+                + bytes[digits_start..]
+                    .iter()
+                    .take_while(|byte| byte.is_ascii_digit())
+                    .count();
+            if digits_end == digits_start {
+                continue;
+            }
+
+            let mut end = digits_end;
+            if separator == b'(' {
+                if bytes.get(end) != Some(&b')') {
+                    continue;
+                }
+                end += 1;
+            }
+
+            let (file, original) = self.origin(line[digits_start..digits_end].parse().ok()?)?;
+
+            return Some(format!(
+                "{}{}:{}{}",
+                &line[..start],
+                file,
+                original,
+                &line[end..]
+            ));
+        }
+
+        None
+    }
+}
+
+/// Name in an `#include "name"` or `#include <name>` directive.
+fn parse_include(directive: &str) -> Option<&str> {
+    let argument = directive["#include".len()..].trim();
+
+    if argument.len() < 2 {
+        return None;
+    }
+
+    let (first, last) = (
+        argument.as_bytes()[0],
+        argument.as_bytes()[argument.len() - 1],
+    );
+    match (first, last) {
+        (b'"', b'"') | (b'<', b'>') => Some(&argument[1..argument.len() - 1]),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs, process};
+
+    fn preprocess(source: &str, defines: &ShaderDefines) -> PreprocessedSource {
+        PreprocessedSource::new("test.frag", source, ShaderType::Fragment, defines, None).unwrap()
+    }
+
+    #[test]
+    fn version_and_defines_come_first() {
+        let defines = ShaderDefines::new()
+            .with("SHADOWS")
+            .with_value("LIGHTS", "4");
+        let preprocessed = preprocess("void main() {}\n#version 410 core", &defines);
+
+        let lines: Vec<_> = preprocessed.source().lines().collect();
+        assert_eq!(
+            lines,
+            vec![
+                "#version 410 core",
+                "#define LIGHTS 4",
+                "#define SHADOWS ",
+                "void main() {}",
+                "",
+            ]
+        );
+    }
+
+    #[test]
+    fn default_version_depends_on_shader_type() {
+        let defines = ShaderDefines::new();
+
+        for &(shader_type, version) in &[
+            (ShaderType::Vertex, "#version 330 core"),
+            (ShaderType::TessControl, "#version 400 core"),
+            (ShaderType::Compute, "#version 430 core"),
+        ] {
+            let preprocessed =
+                PreprocessedSource::new("test", "void main() {}", shader_type, &defines, None)
+                    .unwrap();
+            assert_eq!(preprocessed.source().lines().next(), Some(version));
+        }
+    }
+
+    #[test]
+    fn map_log_points_to_original_lines() {
+        let defines = ShaderDefines::new().with("A");
+        let preprocessed = preprocess("#version 330 core\nvoid main() {\n    error;\n}", &defines);
+
+        //Version, define, then the source with its version line emptied
+        assert_eq!(preprocessed.origin(1), Some(("<generated>", 0)));
+        assert_eq!(preprocessed.origin(5), Some(("test.frag", 3)));
+
+        assert_eq!(
+            preprocessed.map_log("0:5(5): error: syntax error"),
+            "test.frag:3(5): error: syntax error"
+        );
+        assert_eq!(
+            preprocessed.map_log("0(5) : error C0000: syntax error"),
+            "test.frag:3 : error C0000: syntax error"
+        );
+    }
+
+    #[test]
+    fn map_log_keeps_unknown_lines() {
+        let preprocessed = preprocess("void main() {}", &ShaderDefines::new());
+        let log = "warning: nothing to map\n0:99: error: out of range\n10:2 not a reference";
+
+        assert_eq!(preprocessed.map_log(log), log);
+    }
+
+    #[test]
+    fn parse_include_accepts_quotes_and_angle_brackets() {
+        assert_eq!(parse_include("#include \"light.glsl\""), Some("light.glsl"));
+        assert_eq!(
+            parse_include("#include   <common/noise.glsl>"),
+            Some("common/noise.glsl")
+        );
+        assert_eq!(parse_include("#include light.glsl"), None);
+        assert_eq!(parse_include("#include \"light.glsl>"), None);
+        assert_eq!(parse_include("#include \""), None);
+        assert_eq!(parse_include("#include"), None);
+    }
+
+    #[test]
+    fn includes_are_inserted_once() {
+        let path = env::temp_dir().join(format!("cuivre-include-{}.glsl", process::id()));
+        fs::write(&path, "float light() { return 1.0; }").unwrap();
+
+        let mut assets = AssetDatabase::new();
+        assets.add("light.glsl", &path);
+
+        let source = "#include \"light.glsl\"\n#include <light.glsl>\nvoid main() {}";
+        let preprocessed = PreprocessedSource::new(
+            "test.frag",
+            source,
+            ShaderType::Fragment,
+            &ShaderDefines::new(),
+            Some(&assets),
+        );
+        fs::remove_file(&path).unwrap();
+        let preprocessed = preprocessed.unwrap();
+
+        let lines: Vec<_> = preprocessed.source().lines().collect();
+        assert_eq!(
+            lines,
+            vec![
+                "#version 330 core",
+                "float light() { return 1.0; }",
+                "void main() {}"
+            ]
+        );
+        assert_eq!(preprocessed.origin(2), Some(("light.glsl", 1)));
+        assert_eq!(preprocessed.origin(3), Some(("test.frag", 3)));
+    }
+
+    #[test]
+    fn includes_require_an_asset_database() {
+        let result = PreprocessedSource::new(
+            "test.frag",
+            "#include \"light.glsl\"",
+            ShaderType::Fragment,
+            &ShaderDefines::new(),
+            None,
+        );
+
+        assert!(result.is_err());
+    }
+}
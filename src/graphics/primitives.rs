@@ -0,0 +1,654 @@
+//! Generation of 3D primitives as `MeshBuilder` data, with normals and tangents.
+//!
+//! Primitives are centered on the origin, with Y up and triangles wound counter-clockwise
+//! when seen from outside. Round primitives have their UV seam facing -Z.
+//!
+//! Combine them with `MeshBuilder::merge` and `MeshBuilder::apply_transform`.
+
+use super::mesh::{LitVertex, MeshBuilder};
+use cgmath::{InnerSpace, Zero};
+use maths::{Vector2f, Vector2u, Vector3f, Vector4f};
+use std::{collections::HashMap, f32::consts::PI};
+
+/// Minimum distance of a vertex from the Y axis to not be considered a pole.
+const POLE_EPSILON: f32 = 1e-5;
+
+/// Point of the profile revolved around the Y axis by `lathe`.
+#[derive(Debug, Copy, Clone)]
+struct ProfilePoint {
+    /// Distance from the Y axis.
+    radius: f32,
+    y: f32,
+    /// Normal in the profile's plane: outwards from the axis, then up.
+    normal: Vector2f,
+    v: f32,
+}
+
+/// Box of the given size, with each face mapping the whole texture.
+pub fn cube(size: Vector3f) -> MeshBuilder<LitVertex> {
+    let half = size / 2.0;
+    let (x, y, z) = (
+        Vector3f::unit_x() * half.x,
+        Vector3f::unit_y() * half.y,
+        Vector3f::unit_z() * half.z,
+    );
+
+    let mut builder = MeshBuilder::new();
+
+    //Center, right and up of each face, seen from outside
+    add_grid(&mut builder, x, -z, y, 1, 1);
+    add_grid(&mut builder, -x, z, y, 1, 1);
+    add_grid(&mut builder, y, x, -z, 1, 1);
+    add_grid(&mut builder, -y, x, z, 1, 1);
+    add_grid(&mut builder, z, x, y, 1, 1);
+    add_grid(&mut builder, -z, -x, y, 1, 1);
+
+    builder.compute_tangents();
+    builder
+}
+
+/// Plane on XZ facing up, divided in `subdivisions.x` by `subdivisions.y` quads.
+///
+/// The top of the texture is towards -Z.
+pub fn plane(size: Vector2f, subdivisions: Vector2u) -> MeshBuilder<LitVertex> {
+    let mut builder = MeshBuilder::new();
+
+    add_grid(
+        &mut builder,
+        Vector3f::zero(),
+        Vector3f::unit_x() * size.x / 2.0,
+        -Vector3f::unit_z() * size.y / 2.0,
+        subdivisions.x.max(1),
+        subdivisions.y.max(1),
+    );
+
+    builder.compute_tangents();
+    builder
+}
+
+/// Sphere made of `segments` meridians and `rings` parallels.
+///
+/// The texture is mapped with an equirectangular projection, its top at the north pole.
+pub fn uv_sphere(radius: f32, segments: u32, rings: u32) -> MeshBuilder<LitVertex> {
+    let rings = rings.max(2);
+
+    let profile = (0..=rings)
+        .map(|ring| {
+            let angle = PI * ring as f32 / rings as f32;
+            ProfilePoint {
+                radius: radius * angle.sin(),
+                y: radius * angle.cos(),
+                normal: Vector2f::new(angle.sin(), angle.cos()),
+                v: ring as f32 / rings as f32,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let mut builder = MeshBuilder::new();
+    lathe(&mut builder, &profile, segments);
+
+    builder.compute_tangents();
+    builder
+}
+
+/// Sphere made of triangles of equal size, by subdividing an icosahedron.
+///
+/// Each subdivision multiplies the number of triangles by 4, starting from 20.
+/// The texture is mapped with an equirectangular projection, like `uv_sphere`.
+pub fn ico_sphere(radius: f32, subdivisions: u32) -> MeshBuilder<LitVertex> {
+    let t = (1.0 + 5f32.sqrt()) / 2.0;
+
+    let mut positions = [
+        (-1.0, t, 0.0),
+        (1.0, t, 0.0),
+        (-1.0, -t, 0.0),
+        (1.0, -t, 0.0),
+        (0.0, -1.0, t),
+        (0.0, 1.0, t),
+        (0.0, -1.0, -t),
+        (0.0, 1.0, -t),
+        (t, 0.0, -1.0),
+        (t, 0.0, 1.0),
+        (-t, 0.0, -1.0),
+        (-t, 0.0, 1.0),
+    ]
+    .iter()
+    .map(|&(x, y, z)| Vector3f::new(x, y, z).normalize())
+    .collect::<Vec<_>>();
+
+    let mut triangles: Vec<[u32; 3]> = vec![
+        [0, 11, 5],
+        [0, 5, 1],
+        [0, 1, 7],
+        [0, 7, 10],
+        [0, 10, 11],
+        [1, 5, 9],
+        [5, 11, 4],
+        [11, 10, 2],
+        [10, 7, 6],
+        [7, 1, 8],
+        [3, 9, 4],
+        [3, 4, 2],
+        [3, 2, 6],
+        [3, 6, 8],
+        [3, 8, 9],
+        [4, 9, 5],
+        [2, 4, 11],
+        [6, 2, 10],
+        [8, 6, 7],
+        [9, 8, 1],
+    ];
+
+    for _ in 0..subdivisions {
+        let mut midpoints = HashMap::new();
+        let mut subdivided = Vec::with_capacity(triangles.len() * 4);
+
+        for &[a, b, c] in &triangles {
+            let ab = midpoint(&mut positions, &mut midpoints, a, b);
+            let bc = midpoint(&mut positions, &mut midpoints, b, c);
+            let ca = midpoint(&mut positions, &mut midpoints, c, a);
+
+            subdivided.extend_from_slice(&[[a, ab, ca], [b, bc, ab], [c, ca, bc], [ab, bc, ca]]);
+        }
+
+        triangles = subdivided;
+    }
+
+    let mut builder = MeshBuilder::new();
+    //Vertices are split where their UVs differ between triangles
+    let mut vertices = HashMap::new();
+
+    for triangle in &triangles {
+        let corners = [
+            positions[triangle[0] as usize],
+            positions[triangle[1] as usize],
+            positions[triangle[2] as usize],
+        ];
+        let mut uvs = [
+            sphere_uv(corners[0]),
+            sphere_uv(corners[1]),
+            sphere_uv(corners[2]),
+        ];
+
+        //Triangles crossing the seam wrap around the texture
+        let max_u = uvs.iter().fold(0.0f32, |max, uv| max.max(uv.x));
+        for uv in &mut uvs {
+            if max_u - uv.x > 0.5 {
+                uv.x += 1.0;
+            }
+        }
+
+        //Poles are on every meridian, use the one in the middle of the triangle
+        for (i, &corner) in corners.iter().enumerate() {
+            if is_pole(corner) {
+                uvs[i].x = (uvs[(i + 1) % 3].x + uvs[(i + 2) % 3].x) / 2.0;
+            }
+        }
+
+        for ((&corner, &uv), &position) in corners.iter().zip(&uvs).zip(triangle) {
+            let vertex_count = builder.vertices.len() as u32;
+            let index = *vertices
+                .entry((position, uv.x.to_bits()))
+                .or_insert(vertex_count);
+
+            if index == vertex_count {
+                builder.vertices.push(LitVertex {
+                    position: corner * radius,
+                    uv,
+                    normal: corner,
+                    tangent: Vector4f::unit_x(),
+                    uv2: uv,
+                });
+            }
+            builder.indices.push(index);
+        }
+    }
+
+    builder.compute_tangents();
+    builder
+}
+
+/// Cylinder along Y, with caps.
+///
+/// The texture wraps around the side, and each cap maps a disc inside the texture.
+pub fn cylinder(radius: f32, height: f32, segments: u32) -> MeshBuilder<LitVertex> {
+    let half = height / 2.0;
+    let side_normal = Vector2f::unit_x();
+
+    let mut builder = MeshBuilder::new();
+    lathe(
+        &mut builder,
+        &[
+            ProfilePoint {
+                radius,
+                y: half,
+                normal: side_normal,
+                v: 0.0,
+            },
+            ProfilePoint {
+                radius,
+                y: -half,
+                normal: side_normal,
+                v: 1.0,
+            },
+        ],
+        segments,
+    );
+    add_disc(&mut builder, radius, half, segments, true);
+    add_disc(&mut builder, radius, -half, segments, false);
+
+    builder.compute_tangents();
+    builder
+}
+
+/// Cone along Y pointing up, with a cap at its base.
+pub fn cone(radius: f32, height: f32, segments: u32) -> MeshBuilder<LitVertex> {
+    let half = height / 2.0;
+    let side_normal = Vector2f::new(height, radius).normalize();
+
+    let mut builder = MeshBuilder::new();
+    lathe(
+        &mut builder,
+        &[
+            ProfilePoint {
+                radius: 0.0,
+                y: half,
+                normal: side_normal,
+                v: 0.0,
+            },
+            ProfilePoint {
+                radius,
+                y: -half,
+                normal: side_normal,
+                v: 1.0,
+            },
+        ],
+        segments,
+    );
+    add_disc(&mut builder, radius, -half, segments, false);
+
+    builder.compute_tangents();
+    builder
+}
+
+/// Cylinder along Y with hemispheres as caps.
+///
+/// `height` includes the hemispheres, and is at least `2 * radius`.
+/// `rings` is the number of parallels of each hemisphere.
+pub fn capsule(radius: f32, height: f32, segments: u32, rings: u32) -> MeshBuilder<LitVertex> {
+    let rings = rings.max(1);
+    let half_cylinder = (height / 2.0 - radius).max(0.0);
+
+    //Texture is spread along the profile by length
+    let arc_length = PI * radius / 2.0;
+    let total_length = 2.0 * arc_length + 2.0 * half_cylinder;
+
+    let mut profile = Vec::with_capacity(2 * rings as usize + 2);
+    for ring in 0..=rings {
+        let angle = PI / 2.0 * ring as f32 / rings as f32;
+        profile.push(ProfilePoint {
+            radius: radius * angle.sin(),
+            y: half_cylinder + radius * angle.cos(),
+            normal: Vector2f::new(angle.sin(), angle.cos()),
+            v: arc_length * ring as f32 / rings as f32 / total_length,
+        });
+    }
+    for ring in 0..=rings {
+        let angle = PI / 2.0 * (1.0 + ring as f32 / rings as f32);
+        profile.push(ProfilePoint {
+            radius: radius * angle.sin(),
+            y: -half_cylinder + radius * angle.cos(),
+            normal: Vector2f::new(angle.sin(), angle.cos()),
+            v: (arc_length * (1.0 + ring as f32 / rings as f32) + 2.0 * half_cylinder)
+                / total_length,
+        });
+    }
+
+    let mut builder = MeshBuilder::new();
+    lathe(&mut builder, &profile, segments);
+
+    builder.compute_tangents();
+    builder
+}
+
+/// Torus around Y, lying on XZ.
+///
+/// `major_radius` is the distance from the center to the middle of the tube,
+/// `minor_radius` the radius of the tube. `sides` is the number of segments around the tube.
+pub fn torus(
+    major_radius: f32,
+    minor_radius: f32,
+    segments: u32,
+    sides: u32,
+) -> MeshBuilder<LitVertex> {
+    let sides = sides.max(3);
+
+    //From the top of the tube, over its outside and back through its inside
+    let profile = (0..=sides)
+        .map(|side| {
+            let angle = PI / 2.0 - 2.0 * PI * side as f32 / sides as f32;
+            ProfilePoint {
+                radius: major_radius + minor_radius * angle.cos(),
+                y: minor_radius * angle.sin(),
+                normal: Vector2f::new(angle.cos(), angle.sin()),
+                v: side as f32 / sides as f32,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let mut builder = MeshBuilder::new();
+    lathe(&mut builder, &profile, segments);
+
+    builder.compute_tangents();
+    builder
+}
+
+/// Adds a grid of `columns` by `rows` quads, facing the direction of `right` x `up`.
+///
+/// `right` and `up` are half the size of the grid. The top left of the texture is
+/// at `center - right + up`.
+fn add_grid(
+    builder: &mut MeshBuilder<LitVertex>,
+    center: Vector3f,
+    right: Vector3f,
+    up: Vector3f,
+    columns: u32,
+    rows: u32,
+) {
+    let first = builder.vertices.len() as u32;
+    let normal = right.cross(up).normalize();
+
+    for row in 0..=rows {
+        for column in 0..=columns {
+            let uv = Vector2f::new(column as f32 / columns as f32, row as f32 / rows as f32);
+
+            builder.vertices.push(LitVertex {
+                position: center + right * (2.0 * uv.x - 1.0) + up * (1.0 - 2.0 * uv.y),
+                uv,
+                normal,
+                tangent: right.normalize().extend(1.0),
+                uv2: uv,
+            });
+        }
+    }
+
+    add_quads(builder, first, columns, rows, true, true);
+}
+
+/// Adds the surface made by revolving a profile around the Y axis.
+///
+/// The profile goes from top to bottom when seen from outside. Triangles touching
+/// the axis are skipped, as they would be degenerate.
+fn lathe(builder: &mut MeshBuilder<LitVertex>, profile: &[ProfilePoint], segments: u32) {
+    let segments = segments.max(3);
+    let first = builder.vertices.len() as u32;
+
+    for point in profile {
+        //The first and last columns are at the same place, for the UV seam
+        for segment in 0..=segments {
+            let angle = 2.0 * PI * segment as f32 / segments as f32;
+            //Starts at -Z, so that the seam is at the back
+            let direction = Vector3f::new(-angle.sin(), 0.0, -angle.cos());
+            let uv = Vector2f::new(segment as f32 / segments as f32, point.v);
+
+            builder.vertices.push(LitVertex {
+                position: direction * point.radius + Vector3f::unit_y() * point.y,
+                uv,
+                normal: (direction * point.normal.x + Vector3f::unit_y() * point.normal.y)
+                    .normalize(),
+                tangent: Vector4f::unit_x(),
+                uv2: uv,
+            });
+        }
+    }
+
+    for (row, points) in profile.windows(2).enumerate() {
+        add_quads(
+            builder,
+            first + row as u32 * (segments + 1),
+            segments,
+            1,
+            points[0].radius > POLE_EPSILON,
+            points[1].radius > POLE_EPSILON,
+        );
+    }
+}
+
+/// Adds a disc on XZ at height `y`, facing up or down.
+fn add_disc(builder: &mut MeshBuilder<LitVertex>, radius: f32, y: f32, segments: u32, up: bool) {
+    let segments = segments.max(3);
+    let normal = if up {
+        Vector3f::unit_y()
+    } else {
+        -Vector3f::unit_y()
+    };
+    let center = builder.vertices.len() as u32;
+
+    builder.vertices.push(LitVertex {
+        position: Vector3f::unit_y() * y,
+        uv: Vector2f::new(0.5, 0.5),
+        normal,
+        tangent: Vector4f::unit_x(),
+        uv2: Vector2f::new(0.5, 0.5),
+    });
+
+    for segment in 0..=segments {
+        let angle = 2.0 * PI * segment as f32 / segments as f32;
+        let (x, z) = (-angle.sin(), -angle.cos());
+        //Seen from outside, the texture is upright with its top towards -Z
+        let uv = Vector2f::new(
+            if up { 0.5 + x / 2.0 } else { 0.5 - x / 2.0 },
+            0.5 + z / 2.0,
+        );
+
+        builder.vertices.push(LitVertex {
+            position: Vector3f::new(x * radius, y, z * radius),
+            uv,
+            normal,
+            tangent: Vector4f::unit_x(),
+            uv2: uv,
+        });
+    }
+
+    for segment in 0..segments {
+        let (current, next) = (center + 1 + segment, center + 2 + segment);
+        if up {
+            builder.indices.extend_from_slice(&[center, current, next]);
+        } else {
+            builder.indices.extend_from_slice(&[center, next, current]);
+        }
+    }
+}
+
+/// Adds the triangles of a grid of vertices, `columns + 1` per row from left to right,
+/// rows from top to bottom.
+///
+/// Upper or lower triangles of the quads can be skipped, where the top or bottom row
+/// is collapsed to a single point.
+fn add_quads(
+    builder: &mut MeshBuilder<LitVertex>,
+    first: u32,
+    columns: u32,
+    rows: u32,
+    upper: bool,
+    lower: bool,
+) {
+    for row in 0..rows {
+        for column in 0..columns {
+            let top_left = first + row * (columns + 1) + column;
+            let top_right = top_left + 1;
+            let bottom_left = top_left + columns + 1;
+            let bottom_right = bottom_left + 1;
+
+            if lower {
+                builder
+                    .indices
+                    .extend_from_slice(&[bottom_left, bottom_right, top_right]);
+            }
+            if upper {
+                builder
+                    .indices
+                    .extend_from_slice(&[bottom_left, top_right, top_left]);
+            }
+        }
+    }
+}
+
+/// Index of the point between 2 points of an icosphere, added if needed.
+fn midpoint(
+    positions: &mut Vec<Vector3f>,
+    midpoints: &mut HashMap<(u32, u32), u32>,
+    a: u32,
+    b: u32,
+) -> u32 {
+    let key = if a < b { (a, b) } else { (b, a) };
+
+    if let Some(&index) = midpoints.get(&key) {
+        return index;
+    }
+
+    let position = (positions[a as usize] + positions[b as usize]).normalize();
+    positions.push(position);
+
+    let index = positions.len() as u32 - 1;
+    midpoints.insert(key, index);
+    index
+}
+
+/// Equirectangular UV of a point on the unit sphere, matching `uv_sphere`.
+fn sphere_uv(point: Vector3f) -> Vector2f {
+    let u = (-point.x).atan2(-point.z) / (2.0 * PI);
+    Vector2f::new(
+        if u < 0.0 { u + 1.0 } else { u },
+        point.y.max(-1.0).min(1.0).acos() / PI,
+    )
+}
+
+fn is_pole(point: Vector3f) -> bool {
+    point.x.abs() < POLE_EPSILON && point.z.abs() < POLE_EPSILON
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn triangle_count(builder: &MeshBuilder<LitVertex>) -> usize {
+        assert_eq!(builder.indices.len() % 3, 0);
+        builder.indices.len() / 3
+    }
+
+    /// Checks that vertex normals and triangles of a shape around the origin face outwards.
+    fn assert_faces_outwards(builder: &MeshBuilder<LitVertex>) {
+        for vertex in &builder.vertices {
+            assert!(vertex.normal.dot(vertex.position) > 0.0, "{:?}", vertex);
+        }
+
+        for triangle in builder.indices.chunks(3) {
+            let (a, b, c) = (
+                builder.vertices[triangle[0] as usize].position,
+                builder.vertices[triangle[1] as usize].position,
+                builder.vertices[triangle[2] as usize].position,
+            );
+            let normal = (b - a).cross(c - a);
+
+            assert!(
+                normal.magnitude() > 0.0,
+                "degenerate triangle {:?}",
+                triangle
+            );
+            assert!(
+                normal.dot(a + b + c) > 0.0,
+                "inward triangle {:?}",
+                triangle
+            );
+        }
+    }
+
+    #[test]
+    fn cube_counts() {
+        let cube = cube(Vector3f::new(1.0, 2.0, 3.0));
+
+        assert_eq!(cube.vertices.len(), 24);
+        assert_eq!(triangle_count(&cube), 12);
+        assert_faces_outwards(&cube);
+    }
+
+    #[test]
+    fn plane_counts() {
+        let plane = plane(Vector2f::new(1.0, 1.0), Vector2u::new(2, 3));
+
+        assert_eq!(plane.vertices.len(), 3 * 4);
+        assert_eq!(triangle_count(&plane), 2 * 3 * 2);
+        assert!(plane
+            .vertices
+            .iter()
+            .all(|vertex| vertex.normal == Vector3f::unit_y()));
+    }
+
+    #[test]
+    fn uv_sphere_counts() {
+        let sphere = uv_sphere(1.0, 8, 4);
+
+        assert_eq!(sphere.vertices.len(), 9 * 5);
+        //No triangles are made at the poles, where quads collapse
+        assert_eq!(triangle_count(&sphere), 4 * 8 * 2 - 2 * 8);
+        assert_faces_outwards(&sphere);
+    }
+
+    #[test]
+    fn ico_sphere_counts() {
+        for subdivisions in 0..3 {
+            let sphere = ico_sphere(2.0, subdivisions);
+
+            assert_eq!(triangle_count(&sphere), 20 * 4usize.pow(subdivisions));
+            assert!(sphere
+                .vertices
+                .iter()
+                .all(|vertex| (vertex.position.magnitude() - 2.0).abs() < 1e-4));
+            assert_faces_outwards(&sphere);
+        }
+    }
+
+    #[test]
+    fn cylinder_counts() {
+        let cylinder = cylinder(1.0, 2.0, 8);
+
+        //Side, then a center and a ring for each cap
+        assert_eq!(cylinder.vertices.len(), 2 * 9 + 2 * (1 + 9));
+        assert_eq!(triangle_count(&cylinder), 8 * 2 + 2 * 8);
+        assert_faces_outwards(&cylinder);
+    }
+
+    #[test]
+    fn cone_counts() {
+        let cone = cone(1.0, 2.0, 8);
+
+        assert_eq!(cone.vertices.len(), 2 * 9 + 1 + 9);
+        assert_eq!(triangle_count(&cone), 8 + 8);
+        assert_faces_outwards(&cone);
+    }
+
+    #[test]
+    fn capsule_counts() {
+        let capsule = capsule(1.0, 4.0, 8, 2);
+
+        assert_eq!(capsule.vertices.len(), 6 * 9);
+        assert_eq!(triangle_count(&capsule), 5 * 8 * 2 - 2 * 8);
+        assert_faces_outwards(&capsule);
+    }
+
+    #[test]
+    fn torus_counts() {
+        let torus = torus(2.0, 0.5, 8, 6);
+
+        assert_eq!(torus.vertices.len(), 7 * 9);
+        assert_eq!(triangle_count(&torus), 6 * 8 * 2);
+
+        //Normals point away from the middle of the tube
+        for vertex in &torus.vertices {
+            let position = vertex.position;
+            let around = Vector3f::new(position.x, 0.0, position.z).normalize() * 2.0;
+            assert!(vertex.normal.dot(position - around) > 0.0);
+        }
+    }
+}
@@ -0,0 +1,251 @@
+use super::{
+    preprocessor::{PreprocessedSource, ShaderDefines},
+    shaders::{Program, Shader, ShaderType},
+};
+use assets::AssetDatabase;
+use failure::Error;
+use gl;
+use std::{
+    ffi::CStr,
+    fs,
+    os::raw::c_char,
+    path::{Path, PathBuf},
+};
+
+/// Extension of cached binary files.
+const BINARY_EXTENSION: &str = "bin";
+
+/// Initial value of the FNV-1a hash.
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+/// Multiplier of the FNV-1a hash.
+const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+/// Saves linked program binaries to a directory, to skip compiling shaders on later launches.
+///
+/// Binaries are keyed by a hash of the preprocessed sources and of the driver,
+/// so changing either compiles the program again.
+#[derive(Debug, Clone)]
+pub struct ProgramCache {
+    directory: PathBuf,
+    /// Vendor, renderer and version of the driver.
+    driver: String,
+}
+
+impl ProgramCache {
+    /// Creates a cache storing binaries in `directory`, created if needed.
+    pub fn new<P: Into<PathBuf>>(directory: P) -> Result<Self, Error> {
+        let directory = directory.into();
+        fs::create_dir_all(&directory)?;
+
+        Ok(Self {
+            directory,
+            driver: driver_string(),
+        })
+    }
+
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    /// Program from the sources of each of its stages, loaded from the cache if possible.
+    ///
+    /// Sources are preprocessed as with `Shader::from_source_with`. If there is no cached
+    /// binary, or the driver rejects it, the program is compiled and its binary saved.
+    /// Failing to save the binary is not an error, as the program can still be used.
+    pub fn program(
+        &self,
+        stages: &[(ShaderType, &str)],
+        defines: &ShaderDefines,
+        assets: Option<&AssetDatabase>,
+    ) -> Result<Program, Error> {
+        let mut sources = Vec::with_capacity(stages.len());
+        for &(shader_type, source) in stages {
+            let preprocessed =
+                PreprocessedSource::new("<source>", source, shader_type, defines, assets)?;
+            sources.push((shader_type, preprocessed));
+        }
+
+        let path = self
+            .directory
+            .join(format!("{:016x}.{}", self.key(&sources), BINARY_EXTENSION));
+
+        if let Some(program) = load_binary(&path) {
+            return Ok(program);
+        }
+
+        //Not cached or rejected, compile from source
+        let mut shaders = Vec::with_capacity(sources.len());
+        for (shader_type, source) in &sources {
+            shaders.push(Shader::compile(source, *shader_type)?);
+        }
+
+        let program = Program::link(&shaders, true)?;
+
+        if let Some((format, binary)) = program.binary() {
+            //The cache is only an optimization
+            let _ = fs::write(&path, &encode_binary(format, &binary));
+        }
+
+        Ok(program)
+    }
+
+    /// Deletes every cached binary.
+    pub fn clear(&self) -> Result<(), Error> {
+        for entry in fs::read_dir(&self.directory)? {
+            let path = entry?.path();
+            if path
+                .extension()
+                .map_or(false, |extension| extension == BINARY_EXTENSION)
+            {
+                fs::remove_file(path)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Hash of the driver and sources, naming the binary file.
+    ///
+    /// Uses FNV-1a, which unlike `DefaultHasher` is stable across Rust versions.
+    fn key(&self, sources: &[(ShaderType, PreprocessedSource)]) -> u64 {
+        let mut hash = fnv1a(FNV_OFFSET_BASIS, self.driver.as_bytes());
+
+        for (shader_type, source) in sources {
+            hash = fnv1a(hash, &u32_bytes(*shader_type as u32));
+            hash = fnv1a(hash, source.source().as_bytes());
+            //Separate sources, so that moving lines between stages changes the hash
+            hash = fnv1a(hash, &[0]);
+        }
+
+        hash
+    }
+}
+
+/// Program from the sources of each of its stages, loaded from `cache` if there is one.
+///
+/// Used for built-in programs, which are not preprocessed with defines or includes.
+pub(crate) fn program_from_sources(
+    cache: Option<&ProgramCache>,
+    stages: &[(ShaderType, &str)],
+) -> Result<Program, Error> {
+    if let Some(cache) = cache {
+        return cache.program(stages, &ShaderDefines::new(), None);
+    }
+
+    let mut shaders = Vec::with_capacity(stages.len());
+    for &(shader_type, source) in stages {
+        shaders.push(Shader::from_source(source, shader_type)?);
+    }
+
+    Program::from_stages(&shaders)
+}
+
+/// Loads a cached binary written by `encode_binary`.
+///
+/// Returns None if there is no binary, or if the driver rejects it.
+fn load_binary(path: &Path) -> Option<Program> {
+    let data = fs::read(path).ok()?;
+    let (format, binary) = decode_binary(&data)?;
+
+    Program::from_binary(format, binary).ok()
+}
+
+/// Cached binary file contents: its format as 4 little-endian bytes, then the binary itself.
+fn encode_binary(format: gl::types::GLenum, binary: &[u8]) -> Vec<u8> {
+    let mut data = u32_bytes(format).to_vec();
+    data.extend_from_slice(binary);
+    data
+}
+
+/// Splits the contents of a cached binary file into its format and binary.
+///
+/// Returns None if the file is too short to hold a format.
+fn decode_binary(data: &[u8]) -> Option<(gl::types::GLenum, &[u8])> {
+    if data.len() < 4 {
+        return None;
+    }
+
+    let format = data[..4].iter().rev().fold(0, |format, &byte| {
+        (format << 8) | gl::types::GLenum::from(byte)
+    });
+
+    Some((format, &data[4..]))
+}
+
+/// Vendor, renderer and version of the OpenGL driver.
+fn driver_string() -> String {
+    [gl::VENDOR, gl::RENDERER, gl::VERSION]
+        .iter()
+        .map(|&name| unsafe {
+            let string = gl::GetString(name);
+            if string.is_null() {
+                String::new()
+            } else {
+                CStr::from_ptr(string as *const c_char)
+                    .to_string_lossy()
+                    .into_owned()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" / ")
+}
+
+fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(FNV_PRIME)
+    })
+}
+
+/// Little-endian bytes of a `u32`.
+fn u32_bytes(value: u32) -> [u8; 4] {
+    [
+        value as u8,
+        (value >> 8) as u8,
+        (value >> 16) as u8,
+        (value >> 24) as u8,
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fnv1a_matches_reference_vectors() {
+        assert_eq!(fnv1a(FNV_OFFSET_BASIS, b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(fnv1a(FNV_OFFSET_BASIS, b"a"), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(fnv1a(FNV_OFFSET_BASIS, b"foobar"), 0x8594_4171_f739_67e8);
+    }
+
+    #[test]
+    fn fnv1a_can_be_chained() {
+        assert_eq!(
+            fnv1a(fnv1a(FNV_OFFSET_BASIS, b"foo"), b"bar"),
+            fnv1a(FNV_OFFSET_BASIS, b"foobar")
+        );
+    }
+
+    #[test]
+    fn u32_bytes_are_little_endian() {
+        assert_eq!(u32_bytes(0x1234_5678), [0x78, 0x56, 0x34, 0x12]);
+    }
+
+    #[test]
+    fn binary_round_trip() {
+        let data = encode_binary(0x8764_1a2b, &[1, 2, 3]);
+        assert_eq!(data, vec![0x2b, 0x1a, 0x64, 0x87, 1, 2, 3]);
+        assert_eq!(decode_binary(&data), Some((0x8764_1a2b, &[1u8, 2, 3][..])));
+    }
+
+    #[test]
+    fn empty_binary_round_trip() {
+        let data = encode_binary(0xffff_ffff, &[]);
+        assert_eq!(decode_binary(&data), Some((0xffff_ffff, &[][..])));
+    }
+
+    #[test]
+    fn short_binary_is_rejected() {
+        assert_eq!(decode_binary(&[]), None);
+        assert_eq!(decode_binary(&[1, 2, 3]), None);
+    }
+}
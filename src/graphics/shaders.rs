@@ -1,9 +1,23 @@
-use assets::Asset;
+use super::{
+    preprocessor::{PreprocessedSource, ShaderDefines},
+    uniform_buffer::{FRAME_BLOCK_BINDING, FRAME_BLOCK_NAME},
+};
+use assets::{Asset, AssetDatabase};
 use cgmath::{Array, Matrix};
 use failure::Error;
 use gl;
 use maths::{Matrix4f, Vector2f, Vector3f, Vector4f};
-use std::{ffi::CString, ptr, str};
+use std::{
+    cell::RefCell,
+    cmp::Ordering,
+    collections::HashMap,
+    ffi::CString,
+    fmt,
+    hash::{Hash, Hasher},
+    ptr,
+    rc::Rc,
+    str,
+};
 
 ///Errors related to shaders.
 #[derive(Debug, Fail)]
@@ -17,40 +31,363 @@ pub enum ShaderError {
     ///Uniform was not found in the current program. Contains uniform name.
     #[fail(display = "Uniform not found: {}", _0)]
     UniformNotFound(String),
+    ///Uniform was set from a value of another type. Contains name, uniform type and value type.
+    #[fail(display = "Uniform {} is {:?}, cannot set it from {:?}", _0, _1, _2)]
+    UniformTypeMismatch(String, GlslType, GlslType),
+    ///A feature requires a newer OpenGL version. Contains feature, required and available versions.
+    #[fail(display = "{} requires OpenGL {}, but only {} is available", _0, _1, _2)]
+    Unsupported(String, GlVersion, GlVersion),
+    ///The driver could not load a program binary. Contains OpenGL Error log.
+    #[fail(display = "Program binary rejected: {}", _0)]
+    BinaryRejected(String),
+    ///A shader of the wrong stage was given. Contains expected and actual stages.
+    #[fail(display = "Expected a {:?} shader, got a {:?} shader", _0, _1)]
+    WrongShaderType(ShaderType, ShaderType),
+}
+
+/// Version of the OpenGL context.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct GlVersion {
+    pub major: u32,
+    pub minor: u32,
+}
+
+impl fmt::Display for GlVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
+    }
+}
+
+impl GlVersion {
+    pub fn new(major: u32, minor: u32) -> Self {
+        Self { major, minor }
+    }
+
+    /// Version of the current context.
+    ///
+    /// Drivers can provide a newer version than the 3.3 requested by `GraphicsManager`.
+    pub fn current() -> Self {
+        let mut major = 0;
+        let mut minor = 0;
+
+        unsafe {
+            gl::GetIntegerv(gl::MAJOR_VERSION, &mut major);
+            gl::GetIntegerv(gl::MINOR_VERSION, &mut minor);
+        }
+
+        Self::new(major as u32, minor as u32)
+    }
+
+    /// Fails with `ShaderError::Unsupported` if the current context is older than `self`.
+    pub(crate) fn require(self, feature: &str) -> Result<(), ShaderError> {
+        let current = Self::current();
+
+        if current < self {
+            Err(ShaderError::Unsupported(feature.to_owned(), self, current))
+        } else {
+            Ok(())
+        }
+    }
 }
 
 /// ID of loaded OpenGL Program
 pub type ProgramID = gl::types::GLuint;
 
+/// Type of a uniform or vertex attribute, as reported by OpenGL.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum GlslType {
+    Bool,
+    Int,
+    UInt,
+    Float,
+    Vec2,
+    Vec3,
+    Vec4,
+    IVec2,
+    IVec3,
+    IVec4,
+    Mat2,
+    Mat3,
+    Mat4,
+    Sampler2D,
+    Sampler2DArray,
+    SamplerCube,
+    Sampler2DShadow,
+    /// Any other type. Contains the OpenGL type enum.
+    Other(gl::types::GLenum),
+}
+
+impl GlslType {
+    fn from_gl(gl_type: gl::types::GLenum) -> Self {
+        match gl_type {
+            gl::BOOL => GlslType::Bool,
+            gl::INT => GlslType::Int,
+            gl::UNSIGNED_INT => GlslType::UInt,
+            gl::FLOAT => GlslType::Float,
+            gl::FLOAT_VEC2 => GlslType::Vec2,
+            gl::FLOAT_VEC3 => GlslType::Vec3,
+            gl::FLOAT_VEC4 => GlslType::Vec4,
+            gl::INT_VEC2 => GlslType::IVec2,
+            gl::INT_VEC3 => GlslType::IVec3,
+            gl::INT_VEC4 => GlslType::IVec4,
+            gl::FLOAT_MAT2 => GlslType::Mat2,
+            gl::FLOAT_MAT3 => GlslType::Mat3,
+            gl::FLOAT_MAT4 => GlslType::Mat4,
+            gl::SAMPLER_2D => GlslType::Sampler2D,
+            gl::SAMPLER_2D_ARRAY => GlslType::Sampler2DArray,
+            gl::SAMPLER_CUBE => GlslType::SamplerCube,
+            gl::SAMPLER_2D_SHADOW => GlslType::Sampler2DShadow,
+            other => GlslType::Other(other),
+        }
+    }
+
+    /// Returns true for sampler types, which are set with an int texture unit.
+    pub fn is_sampler(self) -> bool {
+        match self {
+            GlslType::Sampler2D
+            | GlslType::Sampler2DArray
+            | GlslType::SamplerCube
+            | GlslType::Sampler2DShadow => true,
+            GlslType::Other(gl_type) => match gl_type {
+                gl::SAMPLER_1D
+                | gl::SAMPLER_3D
+                | gl::SAMPLER_2D_MULTISAMPLE
+                | gl::SAMPLER_BUFFER
+                | gl::SAMPLER_CUBE_SHADOW
+                | gl::SAMPLER_2D_ARRAY_SHADOW
+                | gl::INT_SAMPLER_2D
+                | gl::INT_SAMPLER_2D_ARRAY
+                | gl::UNSIGNED_INT_SAMPLER_2D
+                | gl::UNSIGNED_INT_SAMPLER_2D_ARRAY => true,
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+
+    /// Returns true if a uniform of this type can be set from a value of type `value`.
+    fn accepts(self, value: GlslType) -> bool {
+        match value {
+            _ if self == value => true,
+            GlslType::Int => self == GlslType::Bool || self.is_sampler(),
+            GlslType::Float => self == GlslType::Bool,
+            _ => false,
+        }
+    }
+}
+
+/// An active uniform or vertex attribute of a linked `Program`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ShaderVariable {
+    /// Name, without the `[0]` suffix OpenGL adds to arrays.
+    pub name: String,
+    pub ty: GlslType,
+    /// Number of elements, 1 if not an array.
+    pub size: i32,
+    pub location: gl::types::GLint,
+}
+
+/// Active uniforms and attributes of a program, queried once after linking.
+#[derive(Debug)]
+struct Reflection {
+    uniforms: Vec<ShaderVariable>,
+    attributes: Vec<ShaderVariable>,
+    /// Index in `uniforms` by name.
+    uniform_indices: HashMap<String, usize>,
+    /// Locations of array elements other than the first, queried on first use.
+    element_locations: RefCell<HashMap<String, Option<gl::types::GLint>>>,
+}
+
+/// Value that can be assigned to a uniform.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum UniformValue {
+    Int(i32),
+    Float(f32),
+    Vec2(Vector2f),
+    Vec3(Vector3f),
+    Vec4(Vector4f),
+    Mat4(Matrix4f),
+}
+
+impl UniformValue {
+    /// GLSL type of the value.
+    pub fn glsl_type(&self) -> GlslType {
+        match self {
+            UniformValue::Int(_) => GlslType::Int,
+            UniformValue::Float(_) => GlslType::Float,
+            UniformValue::Vec2(_) => GlslType::Vec2,
+            UniformValue::Vec3(_) => GlslType::Vec3,
+            UniformValue::Vec4(_) => GlslType::Vec4,
+            UniformValue::Mat4(_) => GlslType::Mat4,
+        }
+    }
+}
+
 /// Represents an OpenGL shader program.
 /// Required for drawing anything to the screen.
-#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+///
+/// The program's active uniforms and attributes are queried after linking,
+/// so setting uniforms does not need to look up their location.
+/// Clones share this data, and are compared by program ID.
+#[derive(Clone, Debug)]
 pub struct Program {
     id: ProgramID,
+    reflection: Rc<Reflection>,
+}
+
+impl PartialEq for Program {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl Eq for Program {}
+
+impl PartialOrd for Program {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Program {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.id.cmp(&other.id)
+    }
+}
+
+impl Hash for Program {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+    }
 }
 
 impl Program {
     /// Get the underlying program ID.
-    pub fn id(self) -> ProgramID {
+    pub fn id(&self) -> ProgramID {
         self.id
     }
 
     /// Use this program for drawing.
-    pub fn set_used(self) {
+    pub fn set_used(&self) {
         unsafe {
             gl::UseProgram(self.id());
         }
     }
 
+    /// Active uniforms outside of uniform blocks.
+    pub fn uniforms(&self) -> &[ShaderVariable] {
+        &self.reflection.uniforms
+    }
+
+    /// Active vertex attributes.
+    pub fn attributes(&self) -> &[ShaderVariable] {
+        &self.reflection.attributes
+    }
+
+    /// Active uniform named `name`, outside of uniform blocks.
+    pub fn uniform(&self, name: &str) -> Option<&ShaderVariable> {
+        self.reflection
+            .uniform_indices
+            .get(name)
+            .map(|&index| &self.reflection.uniforms[index])
+    }
+
+    /// Checks that the uniform `name` can be set from a value of type `value_type`.
+    ///
+    /// Names that are not active uniforms of the program are accepted,
+    /// as setting them does nothing.
+    pub fn check_uniform(&self, name: &str, value_type: GlslType) -> Result<(), ShaderError> {
+        match self.uniform(array_base_name(name)) {
+            Some(uniform) if !uniform.ty.accepts(value_type) => Err(
+                ShaderError::UniformTypeMismatch(name.to_owned(), uniform.ty, value_type),
+            ),
+            _ => Ok(()),
+        }
+    }
+
+    /// Active vertex attribute named `name`.
+    pub fn attribute(&self, name: &str) -> Option<&ShaderVariable> {
+        self.reflection
+            .attributes
+            .iter()
+            .find(|attribute| attribute.name == name)
+    }
+
+    /// Links the uniform block `name` to a binding point,
+    /// where a `UniformBuffer` can be bound.
+    ///
+    /// Returns true if the block was found, false otherwise.
+    /// The `Frame` block is linked to `FRAME_BLOCK_BINDING` when the program is created.
+    pub fn bind_uniform_block(&self, name: &str, binding: u32) -> bool {
+        let block_name = match CString::new(name) {
+            Ok(block_name) => block_name,
+            Err(_) => return false,
+        };
+
+        unsafe {
+            match gl::GetUniformBlockIndex(self.id, block_name.as_ptr()) {
+                gl::INVALID_INDEX => false,
+                index => {
+                    gl::UniformBlockBinding(self.id, index, binding);
+                    true
+                }
+            }
+        }
+    }
+
+    /// Set a uniform from a `UniformValue`.
+    ///
+    /// Returns true if the uniform name was found and set, false otherwise.
+    /// Fails if the uniform has another type.
+    pub fn set_uniform(&self, name: &str, value: UniformValue) -> Result<bool, ShaderError> {
+        match value {
+            UniformValue::Int(int) => self.set_int(name, int),
+            UniformValue::Float(float) => self.set_float(name, float),
+            UniformValue::Vec2(vec2) => self.set_vec2(name, vec2),
+            UniformValue::Vec3(vec3) => self.set_vec3(name, vec3),
+            UniformValue::Vec4(vec4) => self.set_vec4(name, vec4),
+            UniformValue::Mat4(mat4) => self.set_mat4(name, mat4),
+        }
+    }
+
+    /// Set a uniform int (or sampler).
+    ///
+    /// Returns true if the uniform name was found and set, false otherwise.
+    /// Fails if the uniform has another type.
+    pub fn set_int(&self, name: &str, int: i32) -> Result<bool, ShaderError> {
+        match self.uniform_location(name, GlslType::Int)? {
+            None => Ok(false),
+            Some(loc) => unsafe {
+                gl::Uniform1i(loc, int);
+                Ok(true)
+            },
+        }
+    }
+
+    /// Set a uniform float.
+    ///
+    /// Returns true if the uniform name was found and set, false otherwise.
+    /// Fails if the uniform has another type.
+    pub fn set_float(&self, name: &str, float: f32) -> Result<bool, ShaderError> {
+        match self.uniform_location(name, GlslType::Float)? {
+            None => Ok(false),
+            Some(loc) => unsafe {
+                gl::Uniform1f(loc, float);
+                Ok(true)
+            },
+        }
+    }
+
     /// Set a uniform mat4.
     ///
     /// Returns true if the uniform name was found and set, false otherwise.
-    pub fn set_mat4(self, name: &str, mat4: Matrix4f) -> bool {
-        match self.get_uniform_location(name) {
-            None => false,
+    /// Fails if the uniform has another type.
+    pub fn set_mat4(&self, name: &str, mat4: Matrix4f) -> Result<bool, ShaderError> {
+        match self.uniform_location(name, GlslType::Mat4)? {
+            None => Ok(false),
             Some(loc) => unsafe {
                 gl::UniformMatrix4fv(loc, 1, gl::FALSE, mat4.as_ptr());
-                true
+                Ok(true)
             },
         }
     }
@@ -58,9 +395,10 @@ impl Program {
     /// Set a uniform mat4 array.
     ///
     /// Returns true if the uniform name was found and set, false otherwise.
-    pub fn set_mat4_arr(self, name: &str, mat4s: &[Matrix4f]) -> bool {
-        match self.get_uniform_location(name) {
-            None => false,
+    /// Fails if the uniform has another type.
+    pub fn set_mat4_arr(&self, name: &str, mat4s: &[Matrix4f]) -> Result<bool, ShaderError> {
+        match self.uniform_location(name, GlslType::Mat4)? {
+            None => Ok(false),
             Some(loc) => unsafe {
                 gl::UniformMatrix4fv(
                     loc,
@@ -68,7 +406,7 @@ impl Program {
                     gl::FALSE,
                     mat4s[0].as_ptr(),
                 );
-                true
+                Ok(true)
             },
         }
     }
@@ -76,12 +414,13 @@ impl Program {
     /// Set a uniform vec2.
     ///
     /// Returns true if the uniform name was found and set, false otherwise.
-    pub fn set_vec2(self, name: &str, vec2: Vector2f) -> bool {
-        match self.get_uniform_location(name) {
-            None => false,
+    /// Fails if the uniform has another type.
+    pub fn set_vec2(&self, name: &str, vec2: Vector2f) -> Result<bool, ShaderError> {
+        match self.uniform_location(name, GlslType::Vec2)? {
+            None => Ok(false),
             Some(loc) => unsafe {
                 gl::Uniform2fv(loc, 1 as gl::types::GLint, vec2.as_ptr());
-                true
+                Ok(true)
             },
         }
     }
@@ -89,12 +428,13 @@ impl Program {
     /// Set a uniform vec2 array.
     ///
     /// Returns true if the uniform name was found and set, false otherwise.
-    pub fn set_vec2_arr(self, name: &str, vec2s: &[Vector2f]) -> bool {
-        match self.get_uniform_location(name) {
-            None => false,
+    /// Fails if the uniform has another type.
+    pub fn set_vec2_arr(&self, name: &str, vec2s: &[Vector2f]) -> Result<bool, ShaderError> {
+        match self.uniform_location(name, GlslType::Vec2)? {
+            None => Ok(false),
             Some(loc) => unsafe {
                 gl::Uniform2fv(loc, vec2s.len() as gl::types::GLint, vec2s[0].as_ptr());
-                true
+                Ok(true)
             },
         }
     }
@@ -102,12 +442,13 @@ impl Program {
     /// Set a uniform vec3.
     ///
     /// Returns true if the uniform name was found and set, false otherwise.
-    pub fn set_vec3(self, name: &str, vec3: Vector3f) -> bool {
-        match self.get_uniform_location(name) {
-            None => false,
+    /// Fails if the uniform has another type.
+    pub fn set_vec3(&self, name: &str, vec3: Vector3f) -> Result<bool, ShaderError> {
+        match self.uniform_location(name, GlslType::Vec3)? {
+            None => Ok(false),
             Some(loc) => unsafe {
                 gl::Uniform3fv(loc, 1, vec3.as_ptr());
-                true
+                Ok(true)
             },
         }
     }
@@ -115,12 +456,13 @@ impl Program {
     /// Set a uniform vec3 array.
     ///
     /// Returns true if the uniform name was found and set, false otherwise.
-    pub fn set_vec3_arr(self, name: &str, vec3s: &[Vector3f]) -> bool {
-        match self.get_uniform_location(name) {
-            None => false,
+    /// Fails if the uniform has another type.
+    pub fn set_vec3_arr(&self, name: &str, vec3s: &[Vector3f]) -> Result<bool, ShaderError> {
+        match self.uniform_location(name, GlslType::Vec3)? {
+            None => Ok(false),
             Some(loc) => unsafe {
                 gl::Uniform3fv(loc, vec3s.len() as gl::types::GLint, vec3s[0].as_ptr());
-                true
+                Ok(true)
             },
         }
     }
@@ -128,12 +470,13 @@ impl Program {
     /// Set a uniform vec4.
     ///
     /// Returns true if the uniform name was found and set, false otherwise.
-    pub fn set_vec4(self, name: &str, vec4: Vector4f) -> bool {
-        match self.get_uniform_location(name) {
-            None => false,
+    /// Fails if the uniform has another type.
+    pub fn set_vec4(&self, name: &str, vec4: Vector4f) -> Result<bool, ShaderError> {
+        match self.uniform_location(name, GlslType::Vec4)? {
+            None => Ok(false),
             Some(loc) => unsafe {
                 gl::Uniform4fv(loc, 1, vec4.as_ptr());
-                true
+                Ok(true)
             },
         }
     }
@@ -141,37 +484,167 @@ impl Program {
     /// Set a uniform vec4 array.
     ///
     /// Returns true if the uniform name was found and set, false otherwise.
-    pub fn set_vec4_arr(self, name: &str, vec4s: &[Vector4f]) -> bool {
-        match self.get_uniform_location(name) {
-            None => false,
+    /// Fails if the uniform has another type.
+    pub fn set_vec4_arr(&self, name: &str, vec4s: &[Vector4f]) -> Result<bool, ShaderError> {
+        match self.uniform_location(name, GlslType::Vec4)? {
+            None => Ok(false),
             Some(loc) => unsafe {
                 gl::Uniform4fv(loc, vec4s.len() as gl::types::GLint, vec4s[0].as_ptr());
-                true
+                Ok(true)
             },
         }
     }
 
     /// Returns uniform location in program from uniform name, or None if
     /// the uniform name was not found.
-    fn get_uniform_location(self, name: &str) -> Option<gl::types::GLint> {
-        let uniform_name = CString::new(name).unwrap();
+    ///
+    /// Fails if the uniform cannot be set from a value of type `value_type`.
+    fn uniform_location(
+        &self,
+        name: &str,
+        value_type: GlslType,
+    ) -> Result<Option<gl::types::GLint>, ShaderError> {
+        let base_name = array_base_name(name);
+        let uniform = match self.uniform(base_name) {
+            Some(uniform) => uniform,
+            None => return Ok(None),
+        };
+
+        if !uniform.ty.accepts(value_type) {
+            return Err(ShaderError::UniformTypeMismatch(
+                name.to_owned(),
+                uniform.ty,
+                value_type,
+            ));
+        }
 
-        match unsafe { gl::GetUniformLocation(self.id, uniform_name.as_ptr()) } {
-            0 => None,
-            loc => Some(loc),
+        if base_name.len() == name.len() {
+            return Ok(Some(uniform.location));
         }
+
+        //Element of an array, query its location once
+        let mut element_locations = self.reflection.element_locations.borrow_mut();
+        let id = self.id;
+        let location = *element_locations
+            .entry(name.to_owned())
+            .or_insert_with(|| {
+                let uniform_name = CString::new(name).ok()?;
+                match unsafe { gl::GetUniformLocation(id, uniform_name.as_ptr()) } {
+                    -1 => None,
+                    loc => Some(loc),
+                }
+            });
+
+        Ok(location)
     }
 
     /// Create Program from Shaders. Deletes shaders afterwards.
     pub fn from_shaders(vertex_shader: Shader, fragment_shader: Shader) -> Result<Program, Error> {
+        Self::from_stages(&[vertex_shader, fragment_shader])
+    }
+
+    /// Create Program from Shaders of any stages, e.g. vertex, geometry and fragment.
+    /// Deletes shaders afterwards.
+    pub fn from_stages(shaders: &[Shader]) -> Result<Program, Error> {
+        Self::link(shaders, false)
+    }
+
+    /// Create Program from a binary returned by `binary`, with the same driver.
+    ///
+    /// Fails with `ShaderError::BinaryRejected` if the driver cannot load it,
+    /// for instance after a driver update.
+    pub fn from_binary(format: gl::types::GLenum, binary: &[u8]) -> Result<Program, Error> {
         let program_id = unsafe { gl::CreateProgram() };
 
         unsafe {
-            gl::AttachShader(program_id, vertex_shader.id());
-            gl::AttachShader(program_id, fragment_shader.id());
+            gl::ProgramBinary(
+                program_id,
+                format,
+                binary.as_ptr() as *const gl::types::GLvoid,
+                binary.len() as gl::types::GLsizei,
+            );
+        }
+
+        if let Err(error) = Self::check_link(program_id) {
+            unsafe { gl::DeleteProgram(program_id) }
+            return Err(ShaderError::BinaryRejected(error.to_string()).into());
+        }
+
+        Ok(Self::from_linked(program_id))
+    }
+
+    /// Linked binary of the program and its format, to load it with `from_binary` later.
+    ///
+    /// Returns None if the driver does not provide program binaries.
+    /// Programs linked by `ProgramCache` ask the driver to keep their binary.
+    pub fn binary(&self) -> Option<(gl::types::GLenum, Vec<u8>)> {
+        if !binaries_supported() {
+            return None;
+        }
+
+        let mut length = 0;
+        unsafe {
+            gl::GetProgramiv(self.id, gl::PROGRAM_BINARY_LENGTH, &mut length);
+        }
+
+        if length <= 0 {
+            return None;
+        }
+
+        let mut binary = vec![0u8; length as usize];
+        let mut written = 0;
+        let mut format = 0;
+
+        unsafe {
+            gl::GetProgramBinary(
+                self.id,
+                length,
+                &mut written,
+                &mut format,
+                binary.as_mut_ptr() as *mut gl::types::GLvoid,
+            );
+        }
+
+        binary.truncate(written as usize);
+        Some((format, binary))
+    }
+
+    /// Links shaders into a program, and deletes them if successful.
+    ///
+    /// If `retrievable`, the driver is asked to keep the binary for `binary`.
+    pub(crate) fn link(shaders: &[Shader], retrievable: bool) -> Result<Program, Error> {
+        let program_id = unsafe { gl::CreateProgram() };
+
+        unsafe {
+            for shader in shaders {
+                gl::AttachShader(program_id, shader.id());
+            }
+
+            if retrievable && binaries_supported() {
+                gl::ProgramParameteri(
+                    program_id,
+                    gl::PROGRAM_BINARY_RETRIEVABLE_HINT,
+                    gl::TRUE as gl::types::GLint,
+                );
+            }
+
             gl::LinkProgram(program_id);
         }
 
+        Self::check_link(program_id)?;
+
+        unsafe {
+            for shader in shaders {
+                gl::DetachShader(program_id, shader.id());
+                gl::DeleteShader(shader.id());
+            }
+        }
+
+        Ok(Self::from_linked(program_id))
+    }
+
+    /// Returns the link error log if the program failed to link.
+    fn check_link(program_id: ProgramID) -> Result<(), ShaderError> {
         let mut success: gl::types::GLint = 1;
         unsafe {
             gl::GetProgramiv(program_id, gl::LINK_STATUS, &mut success);
@@ -194,25 +667,186 @@ impl Program {
                 );
             }
 
-            return Err(
-                ShaderError::ProgramLinkingFailed(error.to_string_lossy().into_owned()).into(),
-            );
+            return Err(ShaderError::ProgramLinkingFailed(
+                error.to_string_lossy().into_owned(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Wraps a successfully linked program.
+    fn from_linked(program_id: ProgramID) -> Program {
+        let program = Program {
+            id: program_id,
+            reflection: Rc::new(Reflection::query(program_id)),
+        };
+        program.bind_uniform_block(FRAME_BLOCK_NAME, FRAME_BLOCK_BINDING);
+
+        program
+    }
+}
+
+/// Returns true if the driver can save and load program binaries.
+pub(crate) fn binaries_supported() -> bool {
+    if !gl::GetProgramBinary::is_loaded() || !gl::ProgramBinary::is_loaded() {
+        return false;
+    }
+
+    let mut formats = 0;
+    unsafe {
+        gl::GetIntegerv(gl::NUM_PROGRAM_BINARY_FORMATS, &mut formats);
+    }
+
+    formats > 0
+}
+
+impl Reflection {
+    /// Queries the active uniforms and attributes of a linked program.
+    fn query(program_id: ProgramID) -> Self {
+        let uniforms: Vec<ShaderVariable> = query_variables(
+            program_id,
+            gl::ACTIVE_UNIFORMS,
+            gl::ACTIVE_UNIFORM_MAX_LENGTH,
+            gl::GetActiveUniform,
+            gl::GetUniformLocation,
+        )
+        .into_iter()
+        //Uniforms in blocks have no location
+        .filter(|uniform| uniform.location != -1)
+        .collect();
+
+        let attributes = query_variables(
+            program_id,
+            gl::ACTIVE_ATTRIBUTES,
+            gl::ACTIVE_ATTRIBUTE_MAX_LENGTH,
+            gl::GetActiveAttrib,
+            gl::GetAttribLocation,
+        );
+
+        let uniform_indices = uniforms
+            .iter()
+            .enumerate()
+            .map(|(index, uniform)| (uniform.name.clone(), index))
+            .collect();
+
+        Self {
+            uniforms,
+            attributes,
+            uniform_indices,
+            element_locations: RefCell::new(HashMap::new()),
         }
+    }
+}
+
+/// Lists active uniforms or attributes of a program, with the matching OpenGL functions.
+fn query_variables(
+    program_id: ProgramID,
+    count_parameter: gl::types::GLenum,
+    max_length_parameter: gl::types::GLenum,
+    get_active: unsafe fn(
+        gl::types::GLuint,
+        gl::types::GLuint,
+        gl::types::GLsizei,
+        *mut gl::types::GLsizei,
+        *mut gl::types::GLint,
+        *mut gl::types::GLenum,
+        *mut gl::types::GLchar,
+    ),
+    get_location: unsafe fn(gl::types::GLuint, *const gl::types::GLchar) -> gl::types::GLint,
+) -> Vec<ShaderVariable> {
+    let mut count = 0;
+    let mut max_length = 0;
+    unsafe {
+        gl::GetProgramiv(program_id, count_parameter, &mut count);
+        gl::GetProgramiv(program_id, max_length_parameter, &mut max_length);
+    }
+
+    let mut variables = Vec::with_capacity(count as usize);
+    let mut buffer = vec![0u8; max_length as usize + 1];
+
+    for index in 0..count as gl::types::GLuint {
+        let mut length = 0;
+        let mut size = 0;
+        let mut gl_type = 0;
 
         unsafe {
-            gl::DetachShader(program_id, vertex_shader.id());
-            gl::DetachShader(program_id, fragment_shader.id());
-            gl::DeleteShader(vertex_shader.id());
-            gl::DeleteShader(fragment_shader.id());
+            get_active(
+                program_id,
+                index,
+                buffer.len() as gl::types::GLsizei,
+                &mut length,
+                &mut size,
+                &mut gl_type,
+                buffer.as_mut_ptr() as *mut gl::types::GLchar,
+            );
         }
 
-        Ok(Program { id: program_id })
+        let full_name = match CString::new(&buffer[..length as usize]) {
+            Ok(full_name) => full_name,
+            Err(_) => continue,
+        };
+        let location = unsafe { get_location(program_id, full_name.as_ptr()) };
+
+        variables.push(ShaderVariable {
+            name: array_base_name(&full_name.to_string_lossy()).to_owned(),
+            ty: GlslType::from_gl(gl_type),
+            size,
+            location,
+        });
     }
+
+    variables
 }
 
+/// Name of an array uniform without its element index, e.g. `Lights` for `Lights[2]`.
+fn array_base_name(name: &str) -> &str {
+    match name.rfind('[') {
+        Some(index) if name.ends_with(']') => &name[..index],
+        _ => name,
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
 pub enum ShaderType {
     Vertex = gl::VERTEX_SHADER as isize,
     Fragment = gl::FRAGMENT_SHADER as isize,
+    /// Emits primitives from each input primitive, between the vertex and fragment stages.
+    Geometry = gl::GEOMETRY_SHADER as isize,
+    /// Sets tessellation levels of patches. Requires OpenGL 4.0.
+    TessControl = gl::TESS_CONTROL_SHADER as isize,
+    /// Positions tessellated vertices. Requires OpenGL 4.0.
+    TessEvaluation = gl::TESS_EVALUATION_SHADER as isize,
+    /// General purpose computations, see `ComputeProgram`. Requires OpenGL 4.3.
+    Compute = gl::COMPUTE_SHADER as isize,
+}
+
+impl ShaderType {
+    /// OpenGL version required to compile shaders of this type.
+    pub fn required_version(self) -> GlVersion {
+        match self {
+            ShaderType::Vertex | ShaderType::Fragment | ShaderType::Geometry => {
+                GlVersion::new(3, 3)
+            }
+            ShaderType::TessControl | ShaderType::TessEvaluation => GlVersion::new(4, 0),
+            ShaderType::Compute => GlVersion::new(4, 3),
+        }
+    }
+
+    /// `#version` line added to sources that do not specify one,
+    /// matching `required_version`.
+    pub fn default_version(self) -> &'static str {
+        match self {
+            ShaderType::Vertex | ShaderType::Fragment | ShaderType::Geometry => "#version 330 core",
+            ShaderType::TessControl | ShaderType::TessEvaluation => "#version 400 core",
+            ShaderType::Compute => "#version 430 core",
+        }
+    }
+
+    /// Returns true if the current context can compile shaders of this type.
+    pub fn is_supported(self) -> bool {
+        GlVersion::current() >= self.required_version()
+    }
 }
 
 impl Asset<ShaderType> for Shader {
@@ -225,6 +859,7 @@ impl Asset<ShaderType> for Shader {
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Copy, Clone)]
 pub struct Shader {
     id: gl::types::GLuint,
+    shader_type: ShaderType,
 }
 
 impl Shader {
@@ -233,10 +868,47 @@ impl Shader {
         self.id
     }
 
+    ///Gets the stage the Shader was compiled for.
+    pub fn shader_type(self) -> ShaderType {
+        self.shader_type
+    }
+
     ///Create a new shader from GLSL source (provided as a CString), returns Shader object or OpenGL error log.
     ///shader_type: usually gl::VERTEX_SHADER or gl::FRAGMENT_SHADER
+    ///
+    ///The source is preprocessed without defines, and cannot include other files.
+    ///See `from_source_with`.
     pub fn from_source(source: &str, shader_type: ShaderType) -> Result<Shader, Error> {
-        let cstring_source = CString::new(source)?;
+        Self::from_source_with(source, shader_type, &ShaderDefines::new(), None)
+    }
+
+    ///Create a new shader from GLSL source, after running the preprocessor on it.
+    ///
+    ///`defines` are added after the `#version` line, which is added if missing
+    ///(see `ShaderType::default_version`).
+    ///Files named in `#include "name"` directives are loaded from `assets`.
+    ///Errors in the compilation log refer to the original files and lines.
+    pub fn from_source_with(
+        source: &str,
+        shader_type: ShaderType,
+        defines: &ShaderDefines,
+        assets: Option<&AssetDatabase>,
+    ) -> Result<Shader, Error> {
+        let preprocessed =
+            PreprocessedSource::new("<source>", source, shader_type, defines, assets)?;
+        Self::compile(&preprocessed, shader_type)
+    }
+
+    ///Compiles a preprocessed source.
+    pub(crate) fn compile(
+        preprocessed: &PreprocessedSource,
+        shader_type: ShaderType,
+    ) -> Result<Shader, Error> {
+        shader_type
+            .required_version()
+            .require(&format!("{:?} shader", shader_type))?;
+
+        let cstring_source = CString::new(preprocessed.source())?;
 
         //Create shader and get ID
         let id = unsafe { gl::CreateShader(shader_type as gl::types::GLuint) };
@@ -255,7 +927,7 @@ impl Shader {
 
         //Shader compiled successfully
         if success == 1 {
-            return Ok(Shader { id });
+            return Ok(Shader { id, shader_type });
         }
 
         //Compilation failed, get error message
@@ -279,8 +951,61 @@ impl Shader {
 
         //Return error log
         Err(ShaderError::ShaderCompilationFailed(
-            error_log.to_string_lossy().into_owned(),
-        ))
+            preprocessed.map_log(&error_log.to_string_lossy()),
+        )
+        .into())
+    }
+}
+
+/// Program compiled from the same sources with different sets of `#define`s.
+///
+/// Each variant is compiled on first use, and reused afterwards.
+#[derive(Debug)]
+pub struct ShaderVariants {
+    vertex_source: String,
+    fragment_source: String,
+    programs: HashMap<ShaderDefines, Program>,
+}
+
+impl ShaderVariants {
+    /// Creates an empty cache for the sources of a vertex and a fragment shader.
+    pub fn new(vertex_source: &str, fragment_source: &str) -> Self {
+        Self {
+            vertex_source: vertex_source.to_owned(),
+            fragment_source: fragment_source.to_owned(),
+            programs: HashMap::new(),
+        }
+    }
+
+    /// Program compiled with `defines`, compiling it if needed.
+    ///
+    /// `#include`s are loaded from `assets`, see `Shader::from_source_with`.
+    pub fn get(
+        &mut self,
+        defines: &ShaderDefines,
+        assets: Option<&AssetDatabase>,
+    ) -> Result<Program, Error> {
+        if let Some(program) = self.programs.get(defines) {
+            return Ok(program.clone());
+        }
+
+        let vertex_shader =
+            Shader::from_source_with(&self.vertex_source, ShaderType::Vertex, defines, assets)?;
+        let fragment_shader =
+            Shader::from_source_with(&self.fragment_source, ShaderType::Fragment, defines, assets)?;
+        let program = Program::from_shaders(vertex_shader, fragment_shader)?;
+
+        self.programs.insert(defines.clone(), program.clone());
+        Ok(program)
+    }
+
+    /// Number of compiled variants.
+    pub fn len(&self) -> usize {
+        self.programs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.programs.is_empty()
     }
 }
 
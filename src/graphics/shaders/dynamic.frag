@@ -0,0 +1,18 @@
+#version 330 core
+
+in vec2 UVCoord;
+in vec4 Tint;
+
+uniform sampler2D Tex;
+
+out vec4 Color;
+
+void main() {
+    vec4 color = texture(Tex, UVCoord) * Tint;
+
+    if (color.a <= 1.0/255) {
+        discard;
+    } else {
+        Color = color;
+    }
+}
@@ -0,0 +1,22 @@
+#version 330 core
+
+layout (location = 0) in vec3 Position;
+layout (location = 1) in vec2 UV;
+layout (location = 4) in vec4 VertexColor;
+
+layout (std140) uniform Frame {
+    mat4 View;
+    mat4 Projection;
+    vec2 ScreenSize;
+    float Time;
+};
+
+out vec2 UVCoord;
+out vec4 Tint;
+
+void main() {
+    gl_Position = Projection * View * vec4(Position, 1.0);
+
+    UVCoord = UV;
+    Tint = VertexColor;
+}
@@ -0,0 +1,36 @@
+#version 330 core
+
+in vec2 UVCoord;
+
+uniform sampler2D Screen;
+uniform vec2 ScreenSize;
+
+uniform float Threshold;
+uniform float Intensity;
+uniform float Radius;
+
+out vec4 Color;
+
+const int SAMPLES = 4;
+
+void main() {
+    vec3 color = texture(Screen, UVCoord).rgb;
+    vec2 texel = Radius / ScreenSize / float(SAMPLES);
+
+    //Gaussian-weighted sum of bright neighbouring pixels
+    vec3 bloom = vec3(0.0);
+    float total_weight = 0.0;
+
+    for (int x = -SAMPLES; x <= SAMPLES; x++) {
+        for (int y = -SAMPLES; y <= SAMPLES; y++) {
+            vec3 sample_color = texture(Screen, UVCoord + vec2(x, y) * texel).rgb;
+            float brightness = max(sample_color.r, max(sample_color.g, sample_color.b));
+
+            float weight = exp(-float(x * x + y * y) / float(SAMPLES * SAMPLES));
+            bloom += sample_color * weight * step(Threshold, brightness);
+            total_weight += weight;
+        }
+    }
+
+    Color = vec4(color + Intensity * bloom / total_weight, 1.0);
+}
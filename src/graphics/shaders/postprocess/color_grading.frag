@@ -0,0 +1,32 @@
+#version 330 core
+
+in vec2 UVCoord;
+
+uniform sampler2D Screen;
+
+uniform float Exposure;
+uniform float Contrast;
+uniform float Saturation;
+uniform float Gamma;
+uniform vec3 Tint;
+
+out vec4 Color;
+
+void main() {
+    vec3 color = texture(Screen, UVCoord).rgb;
+
+    //Exposure and tint
+    color *= Exposure * Tint;
+
+    //Contrast around middle gray
+    color = (color - 0.5) * Contrast + 0.5;
+
+    //Saturation
+    float luminance = dot(color, vec3(0.2126, 0.7152, 0.0722));
+    color = mix(vec3(luminance), color, Saturation);
+
+    //Gamma
+    color = pow(max(color, 0.0), vec3(1.0 / Gamma));
+
+    Color = vec4(color, 1.0);
+}
@@ -0,0 +1,38 @@
+#version 330 core
+
+in vec2 UVCoord;
+
+uniform sampler2D Screen;
+uniform vec2 ScreenSize;
+
+uniform float Curvature;
+uniform float ScanlineIntensity;
+uniform float ScanlineCount;
+
+out vec4 Color;
+
+void main() {
+    //Barrel distortion
+    vec2 centered = UVCoord * 2.0 - 1.0;
+    centered *= 1.0 + Curvature * dot(centered.yx, centered.yx);
+    vec2 uv = centered * 0.5 + 0.5;
+
+    //Outside of the curved screen
+    if (uv.x < 0.0 || uv.x > 1.0 || uv.y < 0.0 || uv.y > 1.0) {
+        Color = vec4(0.0, 0.0, 0.0, 1.0);
+        return;
+    }
+
+    vec3 color = texture(Screen, uv).rgb;
+
+    //Scanlines
+    float scanline = sin(uv.y * ScanlineCount * 3.14159265);
+    color *= 1.0 - ScanlineIntensity * (0.5 - 0.5 * scanline);
+
+    //Slight RGB mask on pixel columns
+    float column = mod(floor(uv.x * ScreenSize.x), 3.0);
+    vec3 mask = vec3(column == 0.0, column == 1.0, column == 2.0);
+    color *= mix(vec3(1.0), 0.7 + 0.6 * mask, ScanlineIntensity);
+
+    Color = vec4(color, 1.0);
+}
@@ -0,0 +1,12 @@
+#version 330 core
+
+out vec2 UVCoord;
+
+void main() {
+    //Single triangle covering the whole screen
+    vec2 position = vec2((gl_VertexID << 1) & 2, gl_VertexID & 2);
+
+    gl_Position = vec4(position * 2.0 - 1.0, 0.0, 1.0);
+
+    UVCoord = position;
+}
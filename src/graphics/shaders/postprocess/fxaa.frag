@@ -0,0 +1,58 @@
+#version 330 core
+
+in vec2 UVCoord;
+
+uniform sampler2D Screen;
+uniform vec2 ScreenSize;
+
+out vec4 Color;
+
+const float FXAA_SPAN_MAX = 8.0;
+const float FXAA_REDUCE_MUL = 1.0 / 8.0;
+const float FXAA_REDUCE_MIN = 1.0 / 128.0;
+
+void main() {
+    vec2 texel = 1.0 / ScreenSize;
+    vec3 luma = vec3(0.299, 0.587, 0.114);
+
+    //Sample luminance around pixel
+    float luma_nw = dot(texture(Screen, UVCoord + vec2(-1.0, -1.0) * texel).rgb, luma);
+    float luma_ne = dot(texture(Screen, UVCoord + vec2(1.0, -1.0) * texel).rgb, luma);
+    float luma_sw = dot(texture(Screen, UVCoord + vec2(-1.0, 1.0) * texel).rgb, luma);
+    float luma_se = dot(texture(Screen, UVCoord + vec2(1.0, 1.0) * texel).rgb, luma);
+    float luma_m = dot(texture(Screen, UVCoord).rgb, luma);
+
+    float luma_min = min(luma_m, min(min(luma_nw, luma_ne), min(luma_sw, luma_se)));
+    float luma_max = max(luma_m, max(max(luma_nw, luma_ne), max(luma_sw, luma_se)));
+
+    //Edge direction
+    vec2 direction = vec2(
+        -((luma_nw + luma_ne) - (luma_sw + luma_se)),
+        (luma_nw + luma_sw) - (luma_ne + luma_se)
+    );
+
+    float direction_reduce = max(
+        (luma_nw + luma_ne + luma_sw + luma_se) * 0.25 * FXAA_REDUCE_MUL,
+        FXAA_REDUCE_MIN
+    );
+    float inverse_min = 1.0 / (min(abs(direction.x), abs(direction.y)) + direction_reduce);
+    direction = clamp(direction * inverse_min, -FXAA_SPAN_MAX, FXAA_SPAN_MAX) * texel;
+
+    //Blend along the edge
+    vec3 color_a = 0.5 * (
+        texture(Screen, UVCoord + direction * (1.0 / 3.0 - 0.5)).rgb +
+        texture(Screen, UVCoord + direction * (2.0 / 3.0 - 0.5)).rgb
+    );
+    vec3 color_b = color_a * 0.5 + 0.25 * (
+        texture(Screen, UVCoord + direction * -0.5).rgb +
+        texture(Screen, UVCoord + direction * 0.5).rgb
+    );
+
+    float luma_b = dot(color_b, luma);
+
+    if (luma_b < luma_min || luma_b > luma_max) {
+        Color = vec4(color_a, 1.0);
+    } else {
+        Color = vec4(color_b, 1.0);
+    }
+}
@@ -0,0 +1,25 @@
+#version 330 core
+
+in vec2 UVCoord;
+
+uniform sampler2D Screen;
+uniform vec2 ScreenSize;
+
+uniform float Intensity;
+uniform float Radius;
+uniform float Softness;
+uniform vec3 VignetteColor;
+
+out vec4 Color;
+
+void main() {
+    vec3 color = texture(Screen, UVCoord).rgb;
+
+    //Distance from center, corrected for aspect ratio
+    vec2 offset = UVCoord - 0.5;
+    offset.x *= ScreenSize.x / ScreenSize.y;
+
+    float vignette = smoothstep(Radius, Radius - Softness, length(offset));
+
+    Color = vec4(mix(VignetteColor, color, mix(1.0, vignette, Intensity)), 1.0);
+}
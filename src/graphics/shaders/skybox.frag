@@ -0,0 +1,11 @@
+#version 330 core
+
+in vec3 Direction;
+
+uniform samplerCube Tex;
+
+out vec4 Color;
+
+void main() {
+    Color = texture(Tex, Direction);
+}
@@ -0,0 +1,22 @@
+#version 330 core
+
+layout (location = 0) in vec3 Position;
+
+layout (std140) uniform Frame {
+    mat4 View;
+    mat4 Projection;
+    vec2 ScreenSize;
+    float Time;
+};
+
+out vec3 Direction;
+
+void main() {
+    //Ignore the camera's position, so that the sky stays infinitely far away
+    vec4 position = Projection * mat4(mat3(View)) * vec4(Position, 1.0);
+
+    //Place the sky on the far plane, behind everything else
+    gl_Position = position.xyww;
+
+    Direction = Position;
+}
@@ -3,13 +3,14 @@
 in vec2 UVCoord;
 in vec2 SourcePosition;
 in vec2 SourceSize;
+in vec4 Tint;
 
 uniform sampler2D Tex;
 
 out vec4 Color;
 
 void main() {
-    vec4 color =  texture(Tex, SourcePosition + SourceSize * UVCoord);
+    vec4 color = texture(Tex, SourcePosition + SourceSize * UVCoord) * Tint;
 
     if (color.a <= 1.0/255) {
         discard;
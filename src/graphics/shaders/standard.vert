@@ -4,17 +4,31 @@ const int BATCH_SIZE = 150;
 
 layout (location = 0) in vec3 Position;
 layout (location = 1) in vec2 UV;
-layout (location = 2) in vec4 TexPosition;
-layout (location = 3) in mat4 TransformMatrix;
+layout (location = 4) in vec4 VertexColor;
+layout (location = 8) in vec4 TexPosition;
+layout (location = 9) in mat4 TransformMatrix;
+layout (location = 13) in vec4 TexParameters; // x: rotated 90° clockwise in texture, y: layer
+layout (location = 14) in vec4 InstanceColor;
+
+layout (std140) uniform Frame {
+    mat4 View;
+    mat4 Projection;
+    vec2 ScreenSize;
+    float Time;
+};
 
 out vec2 UVCoord;
 out vec2 SourcePosition;
 out vec2 SourceSize;
+flat out float Layer;
+out vec4 Tint;
 
 void main() {
-    gl_Position = TransformMatrix * vec4(Position, 1.0);
+    gl_Position = Projection * View * TransformMatrix * vec4(Position, 1.0);
 
-    UVCoord = UV;
+    UVCoord = TexParameters.x > 0.5 ? vec2(1.0 - UV.y, UV.x) : UV;
     SourcePosition = TexPosition.xy;
     SourceSize = TexPosition.zw;
+    Layer = TexParameters.y;
+    Tint = InstanceColor * VertexColor;
 }
@@ -0,0 +1,21 @@
+#version 330 core
+
+in vec2 UVCoord;
+in vec2 SourcePosition;
+in vec2 SourceSize;
+in vec4 Tint;
+flat in float Layer;
+
+uniform sampler2DArray Tex;
+
+out vec4 Color;
+
+void main() {
+    vec4 color = texture(Tex, vec3(SourcePosition + SourceSize * UVCoord, Layer)) * Tint;
+
+    if (color.a <= 1.0/255) {
+        discard;
+    } else {
+        Color = color;
+    }
+}
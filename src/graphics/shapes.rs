@@ -0,0 +1,649 @@
+//! Tessellation of 2D shapes into `MeshBuilder` data, on the XY plane.
+//!
+//! Shapes are centered on the origin where it makes sense.
+//! UV coordinates map the shape's bounding box to the whole texture.
+//!
+//! `build` them into a `Mesh`, then draw it with `GraphicsManager::draw_shape`.
+
+use super::mesh::{MeshBuilder, Vertex};
+use cgmath::InnerSpace;
+use gl;
+use maths::{Vector2f, Vector3f};
+use std::f32::consts::PI;
+
+/// Maximum angle covered by one segment of a round join or cap, in radians.
+const ROUND_STEP: f32 = PI / 16.0;
+
+/// Miters longer than this many times the half thickness are drawn as bevels.
+const MITER_LIMIT: f32 = 4.0;
+
+/// How segments of a thick line are connected.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum LineJoin {
+    /// Sharp corner, falls back to `Bevel` on very acute angles.
+    Miter,
+    /// Corner cut flat.
+    Bevel,
+    /// Rounded corner.
+    Round,
+}
+
+/// How the ends of an open thick line are drawn.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum LineCap {
+    /// Line stops exactly at its end points.
+    Butt,
+    /// Line extends past its end points by half its thickness.
+    Square,
+    /// Line ends with a half circle.
+    Round,
+}
+
+/// Settings for outlines and thick lines.
+#[derive(Debug, Copy, Clone)]
+pub struct StrokeStyle {
+    pub thickness: f32,
+    pub join: LineJoin,
+    pub cap: LineCap,
+}
+
+impl StrokeStyle {
+    /// Stroke with mitered joins and butt caps.
+    pub fn new(thickness: f32) -> Self {
+        Self {
+            thickness,
+            join: LineJoin::Miter,
+            cap: LineCap::Butt,
+        }
+    }
+}
+
+/// Filled rectangle.
+pub fn rect(size: Vector2f) -> MeshBuilder {
+    fill_convex(&rect_points(size))
+}
+
+/// Outline of a rectangle.
+pub fn rect_outline(size: Vector2f, style: StrokeStyle) -> MeshBuilder {
+    polyline(&rect_points(size), style, true)
+}
+
+/// Filled rectangle with rounded corners.
+///
+/// `radius` is clamped to half the smallest side.
+/// `segments` is the number of segments per corner.
+pub fn rounded_rect(size: Vector2f, radius: f32, segments: u32) -> MeshBuilder {
+    fill_convex(&rounded_rect_points(size, radius, segments))
+}
+
+/// Outline of a rectangle with rounded corners.
+pub fn rounded_rect_outline(
+    size: Vector2f,
+    radius: f32,
+    segments: u32,
+    style: StrokeStyle,
+) -> MeshBuilder {
+    polyline(&rounded_rect_points(size, radius, segments), style, true)
+}
+
+/// Filled circle.
+pub fn circle(radius: f32, segments: u32) -> MeshBuilder {
+    ellipse(Vector2f::new(radius, radius), segments)
+}
+
+/// Outline of a circle.
+pub fn circle_outline(radius: f32, segments: u32, style: StrokeStyle) -> MeshBuilder {
+    ellipse_outline(Vector2f::new(radius, radius), segments, style)
+}
+
+/// Filled ellipse.
+pub fn ellipse(radii: Vector2f, segments: u32) -> MeshBuilder {
+    fill_convex(&ellipse_points(radii, segments))
+}
+
+/// Outline of an ellipse.
+pub fn ellipse_outline(radii: Vector2f, segments: u32, style: StrokeStyle) -> MeshBuilder {
+    polyline(&ellipse_points(radii, segments), style, true)
+}
+
+/// Filled simple polygon, convex or concave.
+///
+/// Points can be in either winding order, but edges must not cross.
+pub fn polygon(points: &[Vector2f]) -> MeshBuilder {
+    build(points, &triangulate(points))
+}
+
+/// Outline of a polygon.
+pub fn polygon_outline(points: &[Vector2f], style: StrokeStyle) -> MeshBuilder {
+    polyline(points, style, true)
+}
+
+/// Thick arc of a circle, from `start` to `end` (angles in degrees, counter-clockwise from +X).
+pub fn arc(radius: f32, start: f32, end: f32, segments: u32, style: StrokeStyle) -> MeshBuilder {
+    polyline(&arc_points(radius, start, end, segments), style, false)
+}
+
+/// Filled circle sector, from `start` to `end` (angles in degrees, counter-clockwise from +X).
+pub fn pie(radius: f32, start: f32, end: f32, segments: u32) -> MeshBuilder {
+    let mut points = vec![Vector2f::new(0.0, 0.0)];
+    points.extend(arc_points(radius, start, end, segments));
+
+    fill_convex(&points)
+}
+
+/// Thick line going through `points`.
+///
+/// If `closed`, the last point is joined to the first and caps are ignored.
+pub fn polyline(points: &[Vector2f], style: StrokeStyle, closed: bool) -> MeshBuilder {
+    let mut stroke = Stroke::default();
+
+    //Remove consecutive duplicates, which have no direction
+    let mut path: Vec<Vector2f> = Vec::with_capacity(points.len());
+    for &point in points {
+        if path.last().map_or(true, |&last| last != point) {
+            path.push(point);
+        }
+    }
+    if closed && path.len() > 2 && path[0] == path[path.len() - 1] {
+        path.pop();
+    }
+
+    if path.len() < 2 {
+        return MeshBuilder::new();
+    }
+
+    let half = style.thickness / 2.0;
+    let segment_count = if closed { path.len() } else { path.len() - 1 };
+
+    //Segments
+    for i in 0..segment_count {
+        let mut start = path[i];
+        let mut end = path[(i + 1) % path.len()];
+        let direction = (end - start).normalize();
+
+        //Square caps extend the first and last segments
+        if !closed && style.cap == LineCap::Square {
+            if i == 0 {
+                start = start - direction * half;
+            }
+            if i == segment_count - 1 {
+                end = end + direction * half;
+            }
+        }
+
+        let offset = normal(direction) * half;
+        stroke.quad(start + offset, end + offset, end - offset, start - offset);
+    }
+
+    //Joins
+    let joins = if closed {
+        0..path.len()
+    } else {
+        1..path.len() - 1
+    };
+    for i in joins {
+        let previous = path[(i + path.len() - 1) % path.len()];
+        let point = path[i];
+        let next = path[(i + 1) % path.len()];
+
+        stroke.join(previous, point, next, half, style.join);
+    }
+
+    //Caps
+    if !closed && style.cap == LineCap::Round {
+        let first_direction = (path[1] - path[0]).normalize();
+        stroke.fan(path[0], normal(first_direction) * half, PI);
+
+        let last = path.len() - 1;
+        let last_direction = (path[last] - path[last - 1]).normalize();
+        stroke.fan(path[last], -normal(last_direction) * half, PI);
+    }
+
+    build(&stroke.points, &stroke.indices)
+}
+
+/// Triangles of a thick line, before conversion to a `MeshBuilder`.
+#[derive(Default)]
+struct Stroke {
+    points: Vec<Vector2f>,
+    indices: Vec<gl::types::GLuint>,
+}
+
+impl Stroke {
+    fn triangle(&mut self, a: Vector2f, b: Vector2f, c: Vector2f) {
+        let start = self.points.len() as gl::types::GLuint;
+        self.points.extend_from_slice(&[a, b, c]);
+        self.indices
+            .extend_from_slice(&[start, start + 1, start + 2]);
+    }
+
+    fn quad(&mut self, a: Vector2f, b: Vector2f, c: Vector2f, d: Vector2f) {
+        self.triangle(a, b, c);
+        self.triangle(a, c, d);
+    }
+
+    /// Fan around `center`, from `center + offset`, rotating counter-clockwise by `angle`.
+    fn fan(&mut self, center: Vector2f, offset: Vector2f, angle: f32) {
+        let steps = ((angle.abs() / ROUND_STEP).ceil() as usize).max(1);
+        let start = offset.y.atan2(offset.x);
+        let radius = offset.magnitude();
+
+        let point = |i: usize| {
+            let a = start + angle * i as f32 / steps as f32;
+            center + Vector2f::new(a.cos(), a.sin()) * radius
+        };
+
+        for i in 0..steps {
+            self.triangle(center, point(i), point(i + 1));
+        }
+    }
+
+    /// Fills the gap on the outer side of the corner at `point`.
+    fn join(
+        &mut self,
+        previous: Vector2f,
+        point: Vector2f,
+        next: Vector2f,
+        half: f32,
+        join: LineJoin,
+    ) {
+        let in_direction = (point - previous).normalize();
+        let out_direction = (next - point).normalize();
+
+        let turn = cross(in_direction, out_direction);
+        if turn.abs() < 1e-6 && in_direction.dot(out_direction) > 0.0 {
+            //Straight line, no gap
+            return;
+        }
+
+        //Outer side is on the right when turning left
+        let side = if turn > 0.0 { -1.0 } else { 1.0 };
+        let in_offset = normal(in_direction) * half * side;
+        let out_offset = normal(out_direction) * half * side;
+
+        match join {
+            LineJoin::Bevel => self.triangle(point, point + in_offset, point + out_offset),
+            LineJoin::Round => {
+                let angle = cross(in_offset, out_offset).atan2(in_offset.dot(out_offset));
+                self.fan(point, in_offset, angle);
+            }
+            LineJoin::Miter => {
+                let miter = (in_offset + out_offset).normalize();
+                let length = half / miter.dot(in_offset / half);
+
+                if length > half * MITER_LIMIT || !length.is_finite() {
+                    self.join(previous, point, next, half, LineJoin::Bevel);
+                } else {
+                    self.quad(
+                        point,
+                        point + in_offset,
+                        point + miter * length,
+                        point + out_offset,
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Normal of a direction, rotated counter-clockwise.
+fn normal(direction: Vector2f) -> Vector2f {
+    Vector2f::new(-direction.y, direction.x)
+}
+
+/// Z component of the cross product of two 2D vectors.
+fn cross(a: Vector2f, b: Vector2f) -> f32 {
+    a.x * b.y - a.y * b.x
+}
+
+/// Corners of a rectangle, counter-clockwise.
+fn rect_points(size: Vector2f) -> Vec<Vector2f> {
+    let half = size / 2.0;
+    vec![
+        Vector2f::new(-half.x, -half.y),
+        Vector2f::new(half.x, -half.y),
+        Vector2f::new(half.x, half.y),
+        Vector2f::new(-half.x, half.y),
+    ]
+}
+
+/// Outline of a rounded rectangle, counter-clockwise.
+fn rounded_rect_points(size: Vector2f, radius: f32, segments: u32) -> Vec<Vector2f> {
+    let half = size / 2.0;
+    let radius = radius.min(half.x).min(half.y).max(0.0);
+
+    if radius <= 0.0 {
+        return rect_points(size);
+    }
+
+    let inner = Vector2f::new(half.x - radius, half.y - radius);
+    let corners = [
+        (Vector2f::new(inner.x, -inner.y), 270.0),
+        (Vector2f::new(inner.x, inner.y), 0.0),
+        (Vector2f::new(-inner.x, inner.y), 90.0),
+        (Vector2f::new(-inner.x, -inner.y), 180.0),
+    ];
+
+    let mut points = Vec::new();
+    for &(center, start) in &corners {
+        for point in arc_points(radius, start, start + 90.0, segments) {
+            points.push(center + point);
+        }
+    }
+
+    points
+}
+
+/// Outline of an ellipse, counter-clockwise.
+fn ellipse_points(radii: Vector2f, segments: u32) -> Vec<Vector2f> {
+    let segments = segments.max(3);
+    (0..segments)
+        .map(|i| {
+            let angle = 2.0 * PI * i as f32 / segments as f32;
+            Vector2f::new(angle.cos() * radii.x, angle.sin() * radii.y)
+        })
+        .collect()
+}
+
+/// Points along an arc, including both ends.
+fn arc_points(radius: f32, start: f32, end: f32, segments: u32) -> Vec<Vector2f> {
+    let segments = segments.max(1);
+    let (start, end) = (start.to_radians(), end.to_radians());
+
+    (0..=segments)
+        .map(|i| {
+            let angle = start + (end - start) * i as f32 / segments as f32;
+            Vector2f::new(angle.cos(), angle.sin()) * radius
+        })
+        .collect()
+}
+
+/// Fills a convex polygon with a triangle fan.
+fn fill_convex(points: &[Vector2f]) -> MeshBuilder {
+    let mut indices = Vec::new();
+    for i in 1..points.len().saturating_sub(1) {
+        indices.extend_from_slice(&[0, i as gl::types::GLuint, i as gl::types::GLuint + 1]);
+    }
+
+    build(points, &indices)
+}
+
+/// Triangulates a simple polygon by ear clipping.
+fn triangulate(points: &[Vector2f]) -> Vec<gl::types::GLuint> {
+    if points.len() < 3 {
+        return Vec::new();
+    }
+
+    //Work in counter-clockwise order
+    let area: f32 = (0..points.len())
+        .map(|i| cross(points[i], points[(i + 1) % points.len()]))
+        .sum();
+    let mut remaining: Vec<usize> = (0..points.len()).collect();
+    if area < 0.0 {
+        remaining.reverse();
+    }
+
+    let mut indices = Vec::with_capacity((points.len() - 2) * 3);
+
+    while remaining.len() > 3 {
+        let count = remaining.len();
+        let ear = (0..count).find(|&i| {
+            let a = points[remaining[(i + count - 1) % count]];
+            let b = points[remaining[i]];
+            let c = points[remaining[(i + 1) % count]];
+
+            //Reflex vertices can't be ears
+            if cross(b - a, c - b) <= 0.0 {
+                return false;
+            }
+
+            //No other vertex may be inside the ear
+            remaining.iter().all(|&j| {
+                let p = points[j];
+                p == a || p == b || p == c || !in_triangle(p, a, b, c)
+            })
+        });
+
+        //Degenerate polygon: clip anything to guarantee progress
+        let i = ear.unwrap_or(0);
+
+        indices.extend_from_slice(&[
+            remaining[(i + count - 1) % count] as gl::types::GLuint,
+            remaining[i] as gl::types::GLuint,
+            remaining[(i + 1) % count] as gl::types::GLuint,
+        ]);
+        remaining.remove(i);
+    }
+
+    indices.extend(remaining.iter().map(|&i| i as gl::types::GLuint));
+    indices
+}
+
+/// Whether `p` is inside the counter-clockwise triangle `abc`, edges included.
+fn in_triangle(p: Vector2f, a: Vector2f, b: Vector2f, c: Vector2f) -> bool {
+    cross(b - a, p - a) >= 0.0 && cross(c - b, p - b) >= 0.0 && cross(a - c, p - c) >= 0.0
+}
+
+/// Creates a `MeshBuilder` from 2D points, with UVs mapped to their bounding box.
+fn build(points: &[Vector2f], indices: &[gl::types::GLuint]) -> MeshBuilder {
+    if points.is_empty() {
+        return MeshBuilder::new();
+    }
+
+    let mut min = points[0];
+    let mut max = points[0];
+    for point in points {
+        min = Vector2f::new(min.x.min(point.x), min.y.min(point.y));
+        max = Vector2f::new(max.x.max(point.x), max.y.max(point.y));
+    }
+    let size = Vector2f::new((max.x - min.x).max(1e-6), (max.y - min.y).max(1e-6));
+
+    MeshBuilder {
+        vertices: points
+            .iter()
+            .map(|point| Vertex {
+                position: Vector3f::new(point.x, point.y, 0.0),
+                //Texture V axis points down
+                uv: Vector2f::new((point.x - min.x) / size.x, 1.0 - (point.y - min.y) / size.y),
+            })
+            .collect(),
+        indices: indices.to_vec(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn v(x: f32, y: f32) -> Vector2f {
+        Vector2f::new(x, y)
+    }
+
+    /// Signed area of each triangle, positive if counter-clockwise.
+    fn triangle_areas(points: &[Vector2f], indices: &[gl::types::GLuint]) -> Vec<f32> {
+        indices
+            .chunks(3)
+            .map(|t| {
+                let (a, b, c) = (
+                    points[t[0] as usize],
+                    points[t[1] as usize],
+                    points[t[2] as usize],
+                );
+                cross(b - a, c - a) / 2.0
+            })
+            .collect()
+    }
+
+    fn points(builder: &MeshBuilder) -> Vec<Vector2f> {
+        builder
+            .vertices
+            .iter()
+            .map(|vertex| v(vertex.position.x, vertex.position.y))
+            .collect()
+    }
+
+    fn max_x(builder: &MeshBuilder) -> f32 {
+        points(builder)
+            .iter()
+            .fold(std::f32::NEG_INFINITY, |max, point| max.max(point.x))
+    }
+
+    fn min_x(builder: &MeshBuilder) -> f32 {
+        points(builder)
+            .iter()
+            .fold(std::f32::INFINITY, |min, point| min.min(point.x))
+    }
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-4, "{} != {}", a, b);
+    }
+
+    /// Concave arrow pointing up, counter-clockwise, with an area of 7.
+    fn arrow() -> Vec<Vector2f> {
+        vec![
+            v(-1.0, 0.0),
+            v(0.0, 1.0),
+            v(1.0, 0.0),
+            v(2.0, 0.0),
+            v(0.0, 4.0),
+            v(-2.0, 0.0),
+        ]
+    }
+
+    #[test]
+    fn concave_polygon_is_triangulated() {
+        let points = arrow();
+        let indices = triangulate(&points);
+
+        assert_eq!(indices.len(), (points.len() - 2) * 3);
+
+        let areas = triangle_areas(&points, &indices);
+        assert!(areas.iter().all(|&area| area > 0.0));
+        assert_close(areas.iter().sum(), 7.0);
+    }
+
+    #[test]
+    fn clockwise_polygon_is_triangulated_counter_clockwise() {
+        let mut points = arrow();
+        points.reverse();
+        let indices = triangulate(&points);
+
+        assert_eq!(indices.len(), (points.len() - 2) * 3);
+
+        let areas = triangle_areas(&points, &indices);
+        assert!(areas.iter().all(|&area| area > 0.0));
+        assert_close(areas.iter().sum(), 7.0);
+    }
+
+    #[test]
+    fn too_few_points_give_no_triangles() {
+        assert!(triangulate(&[v(0.0, 0.0), v(1.0, 0.0)]).is_empty());
+    }
+
+    #[test]
+    fn straight_line_has_no_joins() {
+        let line = polyline(
+            &[v(0.0, 0.0), v(1.0, 0.0), v(2.0, 0.0)],
+            StrokeStyle::new(0.5),
+            false,
+        );
+
+        //2 triangles per segment
+        assert_eq!(line.indices.len(), 4 * 3);
+    }
+
+    #[test]
+    fn corner_joins() {
+        let corner = [v(0.0, 0.0), v(2.0, 0.0), v(2.0, 2.0)];
+        let style = |join| StrokeStyle {
+            join,
+            ..StrokeStyle::new(1.0)
+        };
+
+        let bevel = polyline(&corner, style(LineJoin::Bevel), false);
+        assert_eq!(bevel.indices.len(), 5 * 3);
+        assert_close(max_x(&bevel), 2.5);
+
+        //The miter reaches the outer corner
+        let miter = polyline(&corner, style(LineJoin::Miter), false);
+        assert_eq!(miter.indices.len(), 6 * 3);
+        assert!(points(&miter)
+            .iter()
+            .any(|point| (*point - v(2.5, -0.5)).magnitude() < 1e-4));
+
+        let round = polyline(&corner, style(LineJoin::Round), false);
+        assert!(round.indices.len() > 5 * 3);
+        assert!(points(&round)
+            .iter()
+            .all(|point| (*point - v(2.0, 0.0)).magnitude() < 0.5 + 1e-4 || point.y >= -0.5));
+    }
+
+    #[test]
+    fn acute_miter_falls_back_to_bevel() {
+        let spike = [v(0.0, 0.0), v(2.0, 0.0), v(0.0, 0.1)];
+        let line = polyline(&spike, StrokeStyle::new(1.0), false);
+
+        assert_eq!(line.indices.len(), 5 * 3);
+    }
+
+    #[test]
+    fn line_caps() {
+        let segment = [v(0.0, 0.0), v(2.0, 0.0)];
+        let style = |cap| StrokeStyle {
+            cap,
+            ..StrokeStyle::new(1.0)
+        };
+
+        let butt = polyline(&segment, style(LineCap::Butt), false);
+        assert_close(min_x(&butt), 0.0);
+        assert_close(max_x(&butt), 2.0);
+
+        let square = polyline(&segment, style(LineCap::Square), false);
+        assert_eq!(square.indices.len(), 2 * 3);
+        assert_close(min_x(&square), -0.5);
+        assert_close(max_x(&square), 2.5);
+
+        //Half circles only reach the full radius if a vertex falls on their middle
+        let round = polyline(&segment, style(LineCap::Round), false);
+        assert!(round.indices.len() > 2 * 3);
+        assert!(min_x(&round) < -0.49 && min_x(&round) > -0.5 - 1e-4);
+        assert!(max_x(&round) > 2.49 && max_x(&round) < 2.5 + 1e-4);
+    }
+
+    #[test]
+    fn closed_lines_join_every_corner_and_ignore_caps() {
+        let style = StrokeStyle {
+            cap: LineCap::Square,
+            ..StrokeStyle::new(1.0)
+        };
+        let outline = rect_outline(v(2.0, 2.0), style);
+
+        //4 segments and 4 mitered corners, of 2 triangles each
+        assert_eq!(outline.indices.len(), 16 * 3);
+        assert_close(max_x(&outline), 1.5);
+    }
+
+    #[test]
+    fn rounded_rect_radius_is_clamped_to_half_the_smallest_side() {
+        let shape = rounded_rect(v(4.0, 2.0), 5.0, 4);
+        let points = points(&shape);
+
+        assert!(points
+            .iter()
+            .all(|point| point.x.abs() <= 2.0 + 1e-4 && point.y.abs() <= 1.0 + 1e-4));
+        assert_close(max_x(&shape), 2.0);
+
+        //The short sides are half circles, touching the box only at their middle
+        let right: Vec<_> = points
+            .iter()
+            .filter(|point| (point.x - 2.0).abs() < 1e-4)
+            .collect();
+        assert!(right.iter().all(|point| point.y.abs() < 1e-4));
+    }
+
+    #[test]
+    fn rounded_rect_without_radius_is_a_rect() {
+        assert_eq!(rounded_rect(v(4.0, 2.0), 0.0, 4).vertices.len(), 4);
+        assert_eq!(rounded_rect(v(4.0, 2.0), -1.0, 4).vertices.len(), 4);
+    }
+}
@@ -0,0 +1,443 @@
+//! Importer for sprite sheets exported by TexturePacker and Aseprite as JSON.
+//!
+//! Both the "hash" (frames as an object) and "array" (frames as a list) layouts are supported.
+
+use super::{
+    sprites::{AnimationTag, Rect, SheetFrame, SpriteRegion, SpriteSheet, TagDirection},
+    textures::{Texture, TextureOptions},
+};
+use assets::Asset;
+use failure::Error;
+use maths::{Vector2f, Vector2u};
+use serde::de::{Deserialize, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde_json;
+use std::{fmt, fs, path::Path};
+
+/// Duration of frames that do not specify one, in milliseconds.
+const DEFAULT_FRAME_DURATION: u32 = 100;
+
+/// Errors related to importing sprite sheets.
+#[derive(Debug, Fail)]
+pub enum SheetImportError {
+    /// A tag references frames that do not exist.
+    #[fail(
+        display = "Tag '{}' references frames {} to {}, but the sheet has {} frames",
+        _0, _1, _2, _3
+    )]
+    TagOutOfRange(String, usize, usize, usize),
+    /// A tag has an unknown direction.
+    #[fail(display = "Tag '{}' has unknown direction '{}'", _0, _1)]
+    UnknownDirection(String, String),
+    /// The sheet does not name its image.
+    #[fail(display = "Sprite sheet does not specify an image")]
+    MissingImage,
+}
+
+#[derive(Deserialize)]
+struct SheetJson {
+    frames: FrameList,
+    #[serde(default)]
+    meta: MetaJson,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MetaJson {
+    image: Option<String>,
+    #[serde(default)]
+    frame_tags: Vec<TagJson>,
+}
+
+#[derive(Deserialize)]
+struct TagJson {
+    name: String,
+    from: usize,
+    to: usize,
+    #[serde(default = "forward")]
+    direction: String,
+}
+
+fn forward() -> String {
+    "forward".to_owned()
+}
+
+#[derive(Copy, Clone, Deserialize)]
+struct RectJson {
+    x: u32,
+    y: u32,
+    w: u32,
+    h: u32,
+}
+
+#[derive(Copy, Clone, Deserialize)]
+struct SizeJson {
+    w: u32,
+    h: u32,
+}
+
+#[derive(Copy, Clone, Deserialize)]
+struct PointJson {
+    x: f32,
+    y: f32,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FrameJson {
+    frame: RectJson,
+    #[serde(default)]
+    rotated: bool,
+    #[serde(default)]
+    trimmed: bool,
+    sprite_source_size: Option<RectJson>,
+    source_size: Option<SizeJson>,
+    pivot: Option<PointJson>,
+    /// In milliseconds, only exported by Aseprite.
+    duration: Option<u32>,
+}
+
+#[derive(Deserialize)]
+struct NamedFrameJson {
+    filename: String,
+    #[serde(flatten)]
+    frame: FrameJson,
+}
+
+/// Frames in the order they appear in the file, which tags refer to.
+struct FrameList(Vec<(String, FrameJson)>);
+
+impl<'de> Deserialize<'de> for FrameList {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(FrameListVisitor)
+    }
+}
+
+struct FrameListVisitor;
+
+impl<'de> Visitor<'de> for FrameListVisitor {
+    type Value = FrameList;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a list or map of frames")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut frames = Vec::new();
+        while let Some(named) = seq.next_element::<NamedFrameJson>()? {
+            frames.push((named.filename, named.frame));
+        }
+
+        Ok(FrameList(frames))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut frames = Vec::new();
+        while let Some(entry) = map.next_entry::<String, FrameJson>()? {
+            frames.push(entry);
+        }
+
+        Ok(FrameList(frames))
+    }
+}
+
+impl FrameJson {
+    fn region(&self) -> SpriteRegion {
+        //Rotated frames list their unrotated size
+        let frame = self.frame;
+        let mut region = if self.rotated {
+            SpriteRegion::new(Rect::new(frame.x, frame.y, frame.h, frame.w)).with_rotation(true)
+        } else {
+            SpriteRegion::new(Rect::new(frame.x, frame.y, frame.w, frame.h))
+        };
+
+        if let (true, Some(source), Some(size)) =
+            (self.trimmed, self.sprite_source_size, self.source_size)
+        {
+            region = region.with_trim(
+                Vector2u::new(size.w, size.h),
+                Vector2u::new(source.x, source.y),
+            );
+        }
+
+        if let Some(pivot) = self.pivot {
+            region = region.with_pivot(Vector2f::new(pivot.x, pivot.y));
+        }
+
+        region
+    }
+}
+
+impl SheetJson {
+    fn parse(data: &[u8]) -> Result<Self, Error> {
+        Ok(serde_json::from_slice(data)?)
+    }
+
+    /// Animation frames and their regions, in the order of the file.
+    fn frames(&self) -> Vec<(SheetFrame, SpriteRegion)> {
+        self.frames
+            .0
+            .iter()
+            .map(|(name, frame)| {
+                let duration = frame.duration.unwrap_or(DEFAULT_FRAME_DURATION);
+                let sheet_frame = SheetFrame {
+                    name: name.clone(),
+                    duration: duration as f32 / 1000.0,
+                };
+
+                (sheet_frame, frame.region())
+            })
+            .collect()
+    }
+
+    /// Animation tags, checked against the number of frames.
+    fn tags(&self, frame_count: usize) -> Result<Vec<AnimationTag>, Error> {
+        let mut tags = Vec::with_capacity(self.meta.frame_tags.len());
+
+        for tag in &self.meta.frame_tags {
+            if tag.from > tag.to || tag.to >= frame_count {
+                return Err(SheetImportError::TagOutOfRange(
+                    tag.name.clone(),
+                    tag.from,
+                    tag.to,
+                    frame_count,
+                )
+                .into());
+            }
+
+            let direction = match tag.direction.as_str() {
+                "forward" => TagDirection::Forward,
+                "reverse" => TagDirection::Reverse,
+                "pingpong" => TagDirection::PingPong,
+                "pingpong_reverse" => TagDirection::PingPongReverse,
+                _ => {
+                    return Err(SheetImportError::UnknownDirection(
+                        tag.name.clone(),
+                        tag.direction.clone(),
+                    )
+                    .into())
+                }
+            };
+
+            tags.push(AnimationTag {
+                name: tag.name.clone(),
+                from: tag.from,
+                to: tag.to,
+                direction,
+            });
+        }
+
+        Ok(tags)
+    }
+
+    fn into_sprite_sheet(self, texture: Texture) -> Result<SpriteSheet, Error> {
+        let frames = self.frames();
+        let tags = self.tags(frames.len())?;
+
+        let regions = frames
+            .iter()
+            .map(|(frame, region)| (frame.name.clone(), *region));
+        let mut sheet = SpriteSheet::from_regions(texture, regions);
+
+        for (frame, _) in frames {
+            sheet.push_frame(frame);
+        }
+        for tag in tags {
+            sheet.add_tag(tag);
+        }
+
+        Ok(sheet)
+    }
+}
+
+/// Loads a sprite sheet from the JSON data, using an already loaded texture.
+impl Asset<Texture> for SpriteSheet {
+    fn load_from_bytes(data: &[u8], texture: Texture) -> Result<Self, Error> {
+        SheetJson::parse(data)?.into_sprite_sheet(texture)
+    }
+}
+
+impl SpriteSheet {
+    /// Loads a sprite sheet from a TexturePacker or Aseprite JSON file,
+    /// and the image it references (relative to the JSON file).
+    pub fn load_json<P: AsRef<Path>>(path: P, options: TextureOptions) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let sheet = SheetJson::parse(&fs::read(path)?)?;
+
+        let image = match sheet.meta.image {
+            Some(ref image) => image.clone(),
+            None => return Err(SheetImportError::MissingImage.into()),
+        };
+
+        let image_path = match path.parent() {
+            Some(directory) => directory.join(image),
+            None => image.into(),
+        };
+
+        let texture = Texture::load_from_bytes(&fs::read(image_path)?, options)?;
+
+        sheet.into_sprite_sheet(texture)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(json: &str) -> SheetJson {
+        SheetJson::parse(json.as_bytes()).unwrap()
+    }
+
+    fn names(sheet: &SheetJson) -> Vec<String> {
+        sheet
+            .frames()
+            .into_iter()
+            .map(|(frame, _)| frame.name)
+            .collect()
+    }
+
+    /// Region of the only frame of a sheet.
+    fn frame_region(frame_json: &str) -> SpriteRegion {
+        let sheet = parse(&format!(r#"{{"frames": {{"a": {}}}}}"#, frame_json));
+        sheet.frames()[0].1
+    }
+
+    const FRAME: &str = r#"{"frame": {"x": 0, "y": 0, "w": 4, "h": 2}}"#;
+
+    #[test]
+    fn hash_layout_keeps_file_order() {
+        let sheet = parse(&format!(
+            r#"{{"frames": {{"b": {0}, "c": {0}, "a": {0}}}}}"#,
+            FRAME
+        ));
+
+        assert_eq!(names(&sheet), vec!["b", "c", "a"]);
+    }
+
+    #[test]
+    fn array_layout_keeps_file_order() {
+        let frame = r#""frame": {"x": 0, "y": 0, "w": 4, "h": 2}"#;
+        let sheet = parse(&format!(
+            r#"{{"frames": [{{"filename": "b", {0}}}, {{"filename": "a", {0}}}]}}"#,
+            frame
+        ));
+
+        assert_eq!(names(&sheet), vec!["b", "a"]);
+        assert_eq!(
+            sheet.frames()[0].1,
+            SpriteRegion::new(Rect::new(0, 0, 4, 2))
+        );
+    }
+
+    #[test]
+    fn rotated_frames_swap_width_and_height() {
+        let region =
+            frame_region(r#"{"frame": {"x": 1, "y": 2, "w": 4, "h": 2}, "rotated": true}"#);
+
+        assert!(region.rotated);
+        assert_eq!(region.rect, Rect::new(1, 2, 2, 4));
+        assert_eq!(region.size(), Vector2u::new(4, 2));
+    }
+
+    #[test]
+    fn trimmed_frames_keep_their_offset() {
+        let region = frame_region(
+            r#"{
+                "frame": {"x": 0, "y": 0, "w": 4, "h": 2},
+                "trimmed": true,
+                "spriteSourceSize": {"x": 1, "y": 3, "w": 4, "h": 2},
+                "sourceSize": {"w": 8, "h": 6}
+            }"#,
+        );
+
+        assert_eq!(region.source_size, Vector2u::new(8, 6));
+        assert_eq!(region.trim_offset, Vector2u::new(1, 3));
+    }
+
+    #[test]
+    fn untrimmed_frames_ignore_source_size() {
+        let region = frame_region(
+            r#"{
+                "frame": {"x": 0, "y": 0, "w": 4, "h": 2},
+                "spriteSourceSize": {"x": 1, "y": 3, "w": 4, "h": 2},
+                "sourceSize": {"w": 8, "h": 6}
+            }"#,
+        );
+
+        assert_eq!(region.source_size, Vector2u::new(4, 2));
+        assert_eq!(region.trim_offset, Vector2u::new(0, 0));
+    }
+
+    #[test]
+    fn pivot_is_read() {
+        let region = frame_region(
+            r#"{"frame": {"x": 0, "y": 0, "w": 4, "h": 2}, "pivot": {"x": 0.25, "y": 1}}"#,
+        );
+
+        assert_eq!(region.pivot, Vector2f::new(0.25, 1.0));
+        assert_eq!(frame_region(FRAME).pivot, Vector2f::new(0.5, 0.5));
+    }
+
+    #[test]
+    fn durations_default_to_100_ms() {
+        let sheet = parse(
+            r#"{"frames": {
+                "a": {"frame": {"x": 0, "y": 0, "w": 4, "h": 2}},
+                "b": {"frame": {"x": 4, "y": 0, "w": 4, "h": 2}, "duration": 250}
+            }}"#,
+        );
+        let frames = sheet.frames();
+
+        assert_eq!(frames[0].0.duration, 0.1);
+        assert_eq!(frames[1].0.duration, 0.25);
+    }
+
+    #[test]
+    fn tags_are_read() {
+        let sheet = parse(&format!(
+            r#"{{"frames": {{"a": {0}, "b": {0}}}, "meta": {{"frameTags": [
+                {{"name": "walk", "from": 0, "to": 1}},
+                {{"name": "idle", "from": 1, "to": 1, "direction": "pingpong"}}
+            ]}}}}"#,
+            FRAME
+        ));
+        let tags = sheet.tags(2).unwrap();
+
+        assert_eq!(tags.len(), 2);
+        assert_eq!(tags[0].direction, TagDirection::Forward);
+        assert_eq!((tags[1].from, tags[1].to), (1, 1));
+        assert_eq!(tags[1].direction, TagDirection::PingPong);
+    }
+
+    #[test]
+    fn tags_out_of_range_are_rejected() {
+        let sheet = parse(
+            r#"{"frames": {}, "meta": {"frameTags": [{"name": "walk", "from": 0, "to": 2}]}}"#,
+        );
+
+        match sheet.tags(2).unwrap_err().downcast::<SheetImportError>() {
+            Ok(SheetImportError::TagOutOfRange(name, 0, 2, 2)) => assert_eq!(name, "walk"),
+            other => panic!("unexpected result {:?}", other),
+        }
+
+        let reversed = parse(
+            r#"{"frames": {}, "meta": {"frameTags": [{"name": "walk", "from": 1, "to": 0}]}}"#,
+        );
+        assert!(reversed.tags(2).is_err());
+    }
+
+    #[test]
+    fn unknown_directions_are_rejected() {
+        let sheet = parse(
+            r#"{"frames": {}, "meta": {"frameTags": [
+                {"name": "walk", "from": 0, "to": 0, "direction": "sideways"}
+            ]}}"#,
+        );
+
+        match sheet.tags(1).unwrap_err().downcast::<SheetImportError>() {
+            Ok(SheetImportError::UnknownDirection(name, direction)) => {
+                assert_eq!((name.as_str(), direction.as_str()), ("walk", "sideways"))
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+}
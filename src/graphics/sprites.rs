@@ -1,27 +1,252 @@
 use super::Texture;
-use maths::{Vector2f, Vector4f};
+use maths::{Matrix4f, Vector2f, Vector2u, Vector3f, Vector4f};
+use std::collections::HashMap;
+
+/// Rectangle on a texture, in pixels. (0, 0) is the top left corner.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct Rect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Rect {
+    pub fn new(x: u32, y: u32, width: u32, height: u32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// Size of the rectangle in pixels.
+    pub fn size(&self) -> Vector2u {
+        Vector2u::new(self.width, self.height)
+    }
+}
+
+/// Border insets of a sliced sprite, in pixels.
+///
+/// Nine-slice sprites have all four borders. Three-slice sprites only have
+/// left and right borders (horizontal) or top and bottom borders (vertical).
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct Borders {
+    pub left: u32,
+    pub right: u32,
+    pub top: u32,
+    pub bottom: u32,
+}
+
+impl Borders {
+    pub fn new(left: u32, right: u32, top: u32, bottom: u32) -> Self {
+        Self {
+            left,
+            right,
+            top,
+            bottom,
+        }
+    }
+
+    /// Same inset on every side.
+    pub fn uniform(inset: u32) -> Self {
+        Self::new(inset, inset, inset, inset)
+    }
+
+    /// Left and right borders only, for horizontal three-slice sprites.
+    pub fn horizontal(left: u32, right: u32) -> Self {
+        Self::new(left, right, 0, 0)
+    }
+
+    /// Top and bottom borders only, for vertical three-slice sprites.
+    pub fn vertical(top: u32, bottom: u32) -> Self {
+        Self::new(0, 0, top, bottom)
+    }
+}
+
+/// How the edges and center of a sliced sprite fill their area.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum SliceMode {
+    Stretch,
+    /// Repeat at their original size, cutting the last tile.
+    Tile,
+}
+
+/// Area of a texture used by a sprite, and how it is placed when drawn.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SpriteRegion {
+    /// Area of the texture covered by the sprite, in pixels.
+    pub rect: Rect,
+    /// Whether the sprite is rotated 90° clockwise in `rect`, as done by some atlas packers.
+    ///
+    /// Every other field describes the sprite as displayed, unrotated.
+    pub rotated: bool,
+    /// Point the sprite is positioned and rotated around, relative to the untrimmed sprite:
+    /// (0, 0) is the top left corner, (1, 1) the bottom right corner.
+    ///
+    /// Default: (0.5, 0.5), the center.
+    pub pivot: Vector2f,
+    /// Size of the sprite before its transparent borders were trimmed, in pixels.
+    pub source_size: Vector2u,
+    /// Position of `rect` inside the untrimmed sprite, in pixels from its top left corner.
+    pub trim_offset: Vector2u,
+    /// Borders kept at their original size when the sprite is drawn sliced.
+    pub borders: Borders,
+}
+
+impl SpriteRegion {
+    /// Creates an untrimmed region with a centered pivot.
+    pub fn new(rect: Rect) -> Self {
+        Self {
+            rect,
+            rotated: false,
+            pivot: Vector2f::new(0.5, 0.5),
+            source_size: rect.size(),
+            trim_offset: Vector2u::new(0, 0),
+            borders: Borders::default(),
+        }
+    }
+
+    /// Same region, rotated 90° clockwise in the texture if `rotated` is true.
+    ///
+    /// This resets the untrimmed size to the displayed size: trim afterwards.
+    pub fn with_rotation(self, rotated: bool) -> Self {
+        let region = Self { rotated, ..self };
+        Self {
+            source_size: region.size(),
+            trim_offset: Vector2u::new(0, 0),
+            ..region
+        }
+    }
+
+    /// Size of the sprite as displayed (unrotated) in pixels, trimmed.
+    pub fn size(&self) -> Vector2u {
+        if self.rotated {
+            Vector2u::new(self.rect.height, self.rect.width)
+        } else {
+            self.rect.size()
+        }
+    }
+
+    /// Same region with a different pivot.
+    pub fn with_pivot(self, pivot: Vector2f) -> Self {
+        Self { pivot, ..self }
+    }
+
+    /// Same region with borders, to be drawn sliced.
+    pub fn with_borders(self, borders: Borders) -> Self {
+        Self { borders, ..self }
+    }
+
+    /// Same region, trimmed from a sprite of `source_size` pixels where it was at `trim_offset`.
+    pub fn with_trim(self, source_size: Vector2u, trim_offset: Vector2u) -> Self {
+        Self {
+            source_size,
+            trim_offset,
+            ..self
+        }
+    }
+}
+
+impl From<Rect> for SpriteRegion {
+    fn from(rect: Rect) -> Self {
+        Self::new(rect)
+    }
+}
+
+/// Frame of an animated sprite sheet: a named region and how long it is displayed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SheetFrame {
+    /// Name of the frame's region.
+    pub name: String,
+    /// Time the frame is displayed for, in seconds.
+    pub duration: f32,
+}
+
+/// Order in which the frames of an `AnimationTag` are played.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum TagDirection {
+    Forward,
+    Reverse,
+    /// Forward then backward.
+    PingPong,
+    /// Backward then forward.
+    PingPongReverse,
+}
+
+/// Named range of frames in a sprite sheet, usually one animation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnimationTag {
+    pub name: String,
+    /// Index of the first frame.
+    pub from: usize,
+    /// Index of the last frame, included.
+    pub to: usize,
+    pub direction: TagDirection,
+}
 
 /// Represents a texture sliced into rectangular sprites.
 ///
+/// Sprites are either cells of a uniform grid, or named regions
+/// of arbitrary size. Named regions can also be listed as ordered frames,
+/// and grouped into animation tags.
+///
 /// This consumes the `Texture`.
 #[derive(Debug)]
 pub struct SpriteSheet {
     texture: Texture,
     sprite_width: u32,
     sprite_height: u32,
-    gl_size: Vector2f,
+    pixels_per_unit: f32,
+    regions: HashMap<String, SpriteRegion>,
+    frames: Vec<SheetFrame>,
+    tags: Vec<AnimationTag>,
 }
 
 impl SpriteSheet {
     /// Creates a new sprite sheet from a texture and sprite size (in pixels).
+    ///
+    /// Pixels per unit are set so that the largest side of a cell is 1 unit.
     pub fn new(texture: Texture, sprite_width: u32, sprite_height: u32) -> SpriteSheet {
         SpriteSheet {
+            texture,
             sprite_width,
             sprite_height,
-            gl_size: Vector2f::new(
-                sprite_width as f32 / texture.width() as f32,
-                sprite_height as f32 / texture.height() as f32,
-            ),
+            pixels_per_unit: sprite_width.max(sprite_height).max(1) as f32,
+            regions: HashMap::new(),
+            frames: Vec::new(),
+            tags: Vec::new(),
+        }
+    }
+
+    /// Creates a new sprite sheet from a texture and named regions.
+    ///
+    /// The grid size is set to the whole texture. As with the cells of `new`,
+    /// pixels per unit are set so that the largest side of an (untrimmed) region is 1 unit.
+    pub fn from_regions<I, R>(texture: Texture, regions: I) -> SpriteSheet
+    where
+        I: IntoIterator<Item = (String, R)>,
+        R: Into<SpriteRegion>,
+    {
+        let regions: HashMap<String, SpriteRegion> = regions
+            .into_iter()
+            .map(|(name, region)| (name, region.into()))
+            .collect();
+
+        let largest_side = regions
+            .values()
+            .map(|region| region.source_size.x.max(region.source_size.y))
+            .max()
+            .unwrap_or(0);
+
+        SpriteSheet {
+            sprite_width: texture.width(),
+            sprite_height: texture.height(),
+            pixels_per_unit: largest_side.max(1) as f32,
+            regions,
+            frames: Vec::new(),
+            tags: Vec::new(),
             texture,
         }
     }
@@ -30,9 +255,103 @@ impl SpriteSheet {
         &self.texture
     }
 
+    /// Number of texture pixels per world unit when sprites are drawn.
+    pub fn pixels_per_unit(&self) -> f32 {
+        self.pixels_per_unit
+    }
+
+    /// Sets the number of texture pixels per world unit when sprites are drawn.
+    pub fn set_pixels_per_unit(&mut self, pixels_per_unit: f32) {
+        self.pixels_per_unit = pixels_per_unit;
+    }
+
     /// Retrieves the sprite at selected position on the grid.
-    pub fn sprite(&self, x: i32, y: i32) -> Sprite {
-        Sprite { sheet: self, x, y }
+    pub fn sprite(&self, x: u32, y: u32) -> Sprite {
+        self.sprite_from_region(Rect::new(
+            self.sprite_width * x,
+            self.sprite_height * y,
+            self.sprite_width,
+            self.sprite_height,
+        ))
+    }
+
+    /// Retrieves a sprite by name, if a region with this name exists.
+    pub fn named(&self, name: &str) -> Option<Sprite> {
+        self.regions
+            .get(name)
+            .map(|&region| self.sprite_from_region(region))
+    }
+
+    /// Creates a sprite from any region of the texture.
+    #[allow(deprecated)]
+    pub fn sprite_from_region<R: Into<SpriteRegion>>(&self, region: R) -> Sprite {
+        let region = region.into();
+
+        Sprite {
+            sheet: self,
+            x: (region.rect.x / self.sprite_width.max(1)) as i32,
+            y: (region.rect.y / self.sprite_height.max(1)) as i32,
+            region,
+            flip_x: false,
+            flip_y: false,
+        }
+    }
+
+    /// Adds or replaces a named region.
+    pub fn add_region<R: Into<SpriteRegion>>(&mut self, name: &str, region: R) {
+        self.regions.insert(name.to_owned(), region.into());
+    }
+
+    /// Gets a named region.
+    pub fn region(&self, name: &str) -> Option<SpriteRegion> {
+        self.regions.get(name).cloned()
+    }
+
+    /// Names of all regions, in arbitrary order.
+    pub fn region_names(&self) -> impl Iterator<Item = &str> {
+        self.regions.keys().map(String::as_str)
+    }
+
+    /// Adds a named region as the next animation frame, displayed for `duration` seconds.
+    pub fn add_frame<R: Into<SpriteRegion>>(&mut self, name: &str, region: R, duration: f32) {
+        self.add_region(name, region);
+        self.frames.push(SheetFrame {
+            name: name.to_owned(),
+            duration,
+        });
+    }
+
+    /// Adds an animation frame showing a region that was already added.
+    pub(crate) fn push_frame(&mut self, frame: SheetFrame) {
+        self.frames.push(frame);
+    }
+
+    /// Animation frames, in order.
+    pub fn frames(&self) -> &[SheetFrame] {
+        &self.frames
+    }
+
+    /// Retrieves the sprite of an animation frame.
+    pub fn frame(&self, index: usize) -> Option<Sprite> {
+        self.frames
+            .get(index)
+            .and_then(|frame| self.named(&frame.name))
+    }
+
+    /// Adds or replaces an animation tag.
+    pub fn add_tag(&mut self, tag: AnimationTag) {
+        self.tags.retain(|existing| existing.name != tag.name);
+        self.tags.push(tag);
+    }
+
+    /// Animation tags, in the order they were added.
+    pub fn tags(&self) -> &[AnimationTag] {
+        &self.tags
+    }
+
+    /// Gets an animation tag by name.
+    pub fn tag(&self, name: &str) -> Option<&AnimationTag> {
+        self.tags.iter().find(|tag| tag.name == name)
     }
 
     pub fn sprite_width(&self) -> u32 {
@@ -43,33 +362,397 @@ impl SpriteSheet {
         self.sprite_height
     }
 
-    pub fn gl_position(&self, x: i32, y: i32) -> Vector4f {
+    /// Position of a rectangle on the texture as OpenGL coordinates.
+    pub fn gl_position(&self, rect: Rect) -> Vector4f {
         Vector4f::new(
-            (self.sprite_width() as i32 * x) as f32 / self.texture.width() as f32,
-            (self.sprite_height() as i32 * y) as f32 / self.texture.height() as f32,
-            self.gl_size.x,
-            self.gl_size.y,
+            rect.x as f32 / self.texture.width() as f32,
+            rect.y as f32 / self.texture.height() as f32,
+            rect.width as f32 / self.texture.width() as f32,
+            rect.height as f32 / self.texture.height() as f32,
         )
     }
 }
 
-/// Represents one tile of a sprite sheet.
-#[derive(Debug)]
+/// Represents one region of a sprite sheet.
+#[derive(Debug, Copy, Clone)]
 pub struct Sprite<'s> {
     sheet: &'s SpriteSheet,
+    /// Column of the grid cell containing the top left corner of the sprite.
+    #[deprecated(note = "sprites are not limited to grid cells, use `region` instead")]
     pub x: i32,
+    /// Row of the grid cell containing the top left corner of the sprite.
+    #[deprecated(note = "sprites are not limited to grid cells, use `region` instead")]
     pub y: i32,
+    /// Area of the texture used by the sprite, and its pivot.
+    pub region: SpriteRegion,
+    /// Mirrors the sprite horizontally, around its pivot.
+    pub flip_x: bool,
+    /// Mirrors the sprite vertically, around its pivot.
+    pub flip_y: bool,
 }
 
 impl<'s> Sprite<'s> {
+    /// Sprite sheet this sprite belongs to.
+    pub fn sheet(&self) -> &'s SpriteSheet {
+        self.sheet
+    }
+
     /// Texture used by this sprite.
     pub fn texture(&self) -> &'s Texture {
         &self.sheet.texture
     }
 
+    /// Same sprite, with the specified flip flags.
+    pub fn flipped(self, flip_x: bool, flip_y: bool) -> Self {
+        Self {
+            flip_x,
+            flip_y,
+            ..self
+        }
+    }
+
+    /// Same sprite, with a different pivot.
+    pub fn with_pivot(self, pivot: Vector2f) -> Self {
+        Self {
+            region: self.region.with_pivot(pivot),
+            ..self
+        }
+    }
+
     /// Position of the sprite on the texture
     /// as OpenGL coordinates.
+    ///
+    /// Flipped sprites have negative sizes, so that they are read backwards.
     pub fn gl_position(&self) -> Vector4f {
-        self.sheet.gl_position(self.x, self.y)
+        let size = self.region.size();
+        self.tex_position(0.0, 0.0, size.x as f32, size.y as f32)
+    }
+
+    /// Position of part of the sprite on the texture as OpenGL coordinates,
+    /// accounting for flips and rotation.
+    ///
+    /// The part is given in pixels of the displayed sprite, from its top left corner.
+    fn tex_position(&self, x: f32, y: f32, width: f32, height: f32) -> Vector4f {
+        let region = &self.region;
+        let size = region.size();
+
+        //Read mirrored parts backwards
+        let (x, width) = if self.flip_x {
+            (size.x as f32 - x, -width)
+        } else {
+            (x, width)
+        };
+        let (y, height) = if self.flip_y {
+            (size.y as f32 - y, -height)
+        } else {
+            (y, height)
+        };
+
+        //The top of rotated sprites is on the right of their rect
+        let (x, y, width, height) = if region.rotated {
+            (size.y as f32 - y - height, x, height, width)
+        } else {
+            (x, y, width, height)
+        };
+
+        let texture_width = self.sheet.texture.width() as f32;
+        let texture_height = self.sheet.texture.height() as f32;
+
+        Vector4f::new(
+            (region.rect.x as f32 + x) / texture_width,
+            (region.rect.y as f32 + y) / texture_height,
+            width / texture_width,
+            height / texture_height,
+        )
+    }
+
+    /// Size of the drawn (trimmed) sprite in world units.
+    pub fn size(&self) -> Vector2f {
+        let size = self.region.size();
+        Vector2f::new(size.x as f32, size.y as f32) / self.sheet.pixels_per_unit
+    }
+
+    /// Size of the untrimmed sprite in world units.
+    pub fn source_size(&self) -> Vector2f {
+        Vector2f::new(
+            self.region.source_size.x as f32,
+            self.region.source_size.y as f32,
+        ) / self.sheet.pixels_per_unit
+    }
+
+    /// Offset from the pivot to the center of the drawn sprite, in world units (Y up).
+    pub fn pivot_offset(&self) -> Vector2f {
+        let region = &self.region;
+        let size = region.size();
+
+        //Pixel coordinates in the untrimmed sprite, Y down
+        let center = Vector2f::new(
+            region.trim_offset.x as f32 + size.x as f32 / 2.0,
+            region.trim_offset.y as f32 + size.y as f32 / 2.0,
+        );
+        let pivot = Vector2f::new(
+            region.pivot.x * region.source_size.x as f32,
+            region.pivot.y * region.source_size.y as f32,
+        );
+
+        let offset = Vector2f::new(
+            if self.flip_x {
+                pivot.x - center.x
+            } else {
+                center.x - pivot.x
+            },
+            if self.flip_y {
+                center.y - pivot.y
+            } else {
+                pivot.y - center.y
+            },
+        );
+
+        offset / self.sheet.pixels_per_unit
+    }
+
+    /// Matrix scaling the unit quad to the sprite's size, and moving it relative to the pivot.
+    pub fn matrix(&self) -> Matrix4f {
+        let size = self.size();
+        let offset = self.pivot_offset();
+
+        Matrix4f::from_translation(Vector3f::new(offset.x, offset.y, 0.0))
+            * Matrix4f::from_nonuniform_scale(size.x, size.y, 1.0)
+    }
+
+    /// Splits the sprite along its borders into quads filling `size` world units,
+    /// positioned relative to the pivot. Trimming is ignored.
+    ///
+    /// Corners keep their size, unless they do not fit in `size`.
+    /// Returns the texture position and matrix of each quad.
+    pub fn slices(&self, size: Vector2f, mode: SliceMode) -> Vec<(Vector4f, Matrix4f)> {
+        let region = &self.region;
+        let ppu = self.sheet.pixels_per_unit;
+        let pixel_size = region.size();
+
+        //Mirrored sprites have their borders and pivot swapped
+        let borders = region.borders;
+        let (left, right, pivot_x) = if self.flip_x {
+            (borders.right, borders.left, 1.0 - region.pivot.x)
+        } else {
+            (borders.left, borders.right, region.pivot.x)
+        };
+        let (top, bottom, pivot_y) = if self.flip_y {
+            (borders.bottom, borders.top, 1.0 - region.pivot.y)
+        } else {
+            (borders.top, borders.bottom, region.pivot.y)
+        };
+
+        let columns = slice_axis(pixel_size.x, left, right, size.x, ppu, mode);
+        let rows = slice_axis(pixel_size.y, top, bottom, size.y, ppu, mode);
+
+        //Top left corner, relative to the pivot (Y up)
+        let origin = Vector2f::new(-pivot_x * size.x, pivot_y * size.y);
+
+        let mut quads = Vec::with_capacity(columns.len() * rows.len());
+        for row in &rows {
+            for column in &columns {
+                let tex_position = self.tex_position(
+                    column.pixel_start,
+                    row.pixel_start,
+                    column.pixel_size,
+                    row.pixel_size,
+                );
+
+                let center = Vector3f::new(
+                    origin.x + column.start + column.size / 2.0,
+                    origin.y - row.start - row.size / 2.0,
+                    0.0,
+                );
+
+                let matrix = Matrix4f::from_translation(center)
+                    * Matrix4f::from_nonuniform_scale(column.size, row.size, 1.0);
+
+                quads.push((tex_position, matrix));
+            }
+        }
+
+        quads
+    }
+}
+
+/// Part of a sliced sprite along one axis.
+struct Segment {
+    /// Position from the start of the sprite, in world units.
+    start: f32,
+    /// Size in world units.
+    size: f32,
+    /// Position in the region, in pixels.
+    pixel_start: f32,
+    /// Size in the region, in pixels.
+    pixel_size: f32,
+}
+
+/// Splits one axis of a region of `length` pixels into segments filling `size` world units.
+fn slice_axis(
+    length: u32,
+    start_border: u32,
+    end_border: u32,
+    size: f32,
+    pixels_per_unit: f32,
+    mode: SliceMode,
+) -> Vec<Segment> {
+    let length = length as f32;
+    let start_border = (start_border as f32).min(length);
+    let end_border = (end_border as f32).min(length - start_border);
+    let middle = length - start_border - end_border;
+
+    //Shrink borders if they do not fit
+    let mut start_size = start_border / pixels_per_unit;
+    let mut end_size = end_border / pixels_per_unit;
+    if start_size + end_size > size {
+        let scale = size.max(0.0) / (start_size + end_size);
+        start_size *= scale;
+        end_size *= scale;
+    }
+    let middle_size = size - start_size - end_size;
+
+    let mut segments = vec![Segment {
+        start: 0.0,
+        size: start_size,
+        pixel_start: 0.0,
+        pixel_size: start_border,
+    }];
+
+    match mode {
+        SliceMode::Tile if middle > 0.0 && middle_size > 0.0 => {
+            let tile_size = middle / pixels_per_unit;
+            let tile_count = (middle_size / tile_size).ceil() as usize;
+
+            for i in 0..tile_count {
+                let position = i as f32 * tile_size;
+                let size = tile_size.min(middle_size - position);
+
+                segments.push(Segment {
+                    start: start_size + position,
+                    size,
+                    pixel_start: start_border,
+                    pixel_size: middle * size / tile_size,
+                });
+            }
+        }
+        _ => segments.push(Segment {
+            start: start_size,
+            size: middle_size,
+            pixel_start: start_border,
+            pixel_size: middle,
+        }),
+    }
+
+    segments.push(Segment {
+        start: size - end_size,
+        size: end_size,
+        pixel_start: start_border + middle,
+        pixel_size: end_border,
+    });
+
+    segments.retain(|segment| segment.size > 0.0 && segment.pixel_size > 0.0);
+    segments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Start, size, pixel start and pixel size of each segment.
+    fn slice(
+        length: u32,
+        borders: (u32, u32),
+        size: f32,
+        pixels_per_unit: f32,
+        mode: SliceMode,
+    ) -> Vec<(f32, f32, f32, f32)> {
+        slice_axis(length, borders.0, borders.1, size, pixels_per_unit, mode)
+            .iter()
+            .map(|s| (s.start, s.size, s.pixel_start, s.pixel_size))
+            .collect()
+    }
+
+    #[test]
+    fn stretch_fills_the_middle() {
+        assert_eq!(
+            slice(10, (2, 3), 20.0, 1.0, SliceMode::Stretch),
+            vec![
+                (0.0, 2.0, 0.0, 2.0),
+                (2.0, 15.0, 2.0, 5.0),
+                (17.0, 3.0, 7.0, 3.0)
+            ]
+        );
+    }
+
+    #[test]
+    fn borders_use_pixels_per_unit() {
+        assert_eq!(
+            slice(10, (2, 4), 10.0, 2.0, SliceMode::Stretch),
+            vec![
+                (0.0, 1.0, 0.0, 2.0),
+                (1.0, 7.0, 2.0, 4.0),
+                (8.0, 2.0, 6.0, 4.0)
+            ]
+        );
+    }
+
+    #[test]
+    fn tile_cuts_the_last_tile() {
+        assert_eq!(
+            slice(10, (2, 3), 14.0, 1.0, SliceMode::Tile),
+            vec![
+                (0.0, 2.0, 0.0, 2.0),
+                (2.0, 5.0, 2.0, 5.0),
+                (7.0, 4.0, 2.0, 4.0),
+                (11.0, 3.0, 7.0, 3.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn tile_without_cut() {
+        assert_eq!(
+            slice(10, (2, 3), 15.0, 1.0, SliceMode::Tile),
+            vec![
+                (0.0, 2.0, 0.0, 2.0),
+                (2.0, 5.0, 2.0, 5.0),
+                (7.0, 5.0, 2.0, 5.0),
+                (12.0, 3.0, 7.0, 3.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn borders_shrink_when_larger_than_size() {
+        for &mode in &[SliceMode::Stretch, SliceMode::Tile] {
+            assert_eq!(
+                slice(10, (4, 4), 4.0, 1.0, mode),
+                vec![(0.0, 2.0, 0.0, 4.0), (2.0, 2.0, 6.0, 4.0)]
+            );
+        }
+    }
+
+    #[test]
+    fn borders_are_clamped_to_region() {
+        for &mode in &[SliceMode::Stretch, SliceMode::Tile] {
+            assert_eq!(
+                slice(4, (3, 3), 10.0, 1.0, mode),
+                vec![(0.0, 3.0, 0.0, 3.0), (9.0, 1.0, 3.0, 1.0)]
+            );
+        }
+    }
+
+    #[test]
+    fn no_borders_is_a_single_segment() {
+        assert_eq!(
+            slice(8, (0, 0), 3.0, 1.0, SliceMode::Stretch),
+            vec![(0.0, 3.0, 0.0, 8.0)]
+        );
+    }
+
+    #[test]
+    fn zero_size_is_empty() {
+        assert!(slice(8, (2, 2), 0.0, 1.0, SliceMode::Tile).is_empty());
     }
 }
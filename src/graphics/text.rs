@@ -1,6 +1,5 @@
 use assets::Asset;
 use failure::Error;
-use gl;
 use graphics::textures::{
     MaxFilterMode, MinFilterMode, Texture, TextureFormat, TextureOptions, WrapMode,
 };
@@ -11,7 +10,7 @@ use rusttype::{
     gpu_cache::{Cache, CacheBuilder},
     Point, PositionedGlyph, Scale,
 };
-use std::{os::raw::c_void, sync::Arc};
+use std::sync::Arc;
 use unicode_normalization::UnicodeNormalization;
 
 const CACHE_WIDTH: u32 = 1024;
@@ -92,7 +91,7 @@ impl<'a> Font<'a> {
             settings.line_width,
         );
 
-        let texture = &self.texture;
+        let texture = &mut self.texture;
 
         let cache = &mut self.cache;
 
@@ -100,7 +99,10 @@ impl<'a> Font<'a> {
             cache.queue_glyph(0, glyph.clone());
         }
 
-        cache.cache_queued(|rect, data| unsafe {
+        //Keep the first error, the cache callback cannot return one
+        let mut result = Ok(());
+
+        cache.cache_queued(|rect, data| {
             let mut rgb_data: Vec<u8> =
                 Vec::with_capacity((4 * rect.width() * rect.height()) as usize);
 
@@ -111,19 +113,19 @@ impl<'a> Font<'a> {
                 rgb_data.push(*alpha);
             }
 
-            gl::TextureSubImage2D(
-                texture.id(),
-                0,
-                rect.min.x as gl::types::GLint,
-                rect.min.y as gl::types::GLint,
-                rect.width() as gl::types::GLint,
-                rect.height() as gl::types::GLint,
-                texture.options().format as gl::types::GLenum,
-                gl::UNSIGNED_BYTE,
-                rgb_data.as_ptr() as *const c_void,
-            );
+            if result.is_ok() {
+                result = texture.update_region(
+                    rect.min.x,
+                    rect.min.y,
+                    rect.width(),
+                    rect.height(),
+                    &rgb_data,
+                );
+            }
         })?;
 
+        result?;
+
         //Get texture coordinates as Vector4f for each character
         let vec = glyphs
             .par_iter()
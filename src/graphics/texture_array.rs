@@ -0,0 +1,198 @@
+use super::textures::{
+    self, BindableTexture, TextureError, TextureFormat, TextureID, TextureOptions, TextureTarget,
+};
+use failure::Error;
+use gl;
+use image::RgbaImage;
+use maths::Vector2u;
+use std::ptr;
+
+/// Stack of same-sized 2D textures, sampled in shaders as a `sampler2DArray`.
+///
+/// Useful for layered sprite sets or terrain, where every layer
+/// can be drawn in the same batch.
+///
+/// This owns the texture, meaning the OpenGL texture is deleted when
+/// `TextureArray` goes out of scope.
+#[derive(Debug)]
+pub struct TextureArray {
+    id: TextureID,
+    size: Vector2u,
+    layers: u32,
+    options: TextureOptions,
+}
+
+impl Drop for TextureArray {
+    fn drop(&mut self) {
+        unsafe { gl::DeleteTextures(1, &self.id) }
+    }
+}
+
+impl BindableTexture for TextureArray {
+    fn id(&self) -> TextureID {
+        self.id
+    }
+
+    fn target(&self) -> TextureTarget {
+        TextureTarget::Texture2DArray
+    }
+}
+
+impl PartialEq for TextureArray {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl Eq for TextureArray {}
+
+impl TextureArray {
+    /// Creates a texture array with uninitialized contents.
+    pub fn empty(options: TextureOptions, width: u32, height: u32, layers: u32) -> Self {
+        let mut id = 0;
+
+        unsafe {
+            gl::GenTextures(1, &mut id);
+            gl::BindTexture(gl::TEXTURE_2D_ARRAY, id);
+
+            gl::TexImage3D(
+                gl::TEXTURE_2D_ARRAY,
+                0,
+                options.format.internal_format() as gl::types::GLint,
+                width as gl::types::GLsizei,
+                height as gl::types::GLsizei,
+                layers as gl::types::GLsizei,
+                0,
+                options.format.external_format(),
+                options.format.pixel_type(),
+                ptr::null(),
+            );
+
+            textures::apply_options(TextureTarget::Texture2DArray, &options);
+
+            gl::BindTexture(gl::TEXTURE_2D_ARRAY, 0);
+        }
+
+        Self {
+            id,
+            size: Vector2u::new(width, height),
+            layers,
+            options,
+        }
+    }
+
+    /// Creates a texture array from raw pixel data, one slice per layer.
+    pub fn from_layers(
+        layers: &[&[u8]],
+        options: TextureOptions,
+        width: u32,
+        height: u32,
+    ) -> Result<Self, Error> {
+        let mut array = Self::empty(options, width, height, layers.len() as u32);
+
+        for (layer, data) in layers.iter().enumerate() {
+            array.write_layer(layer as u32, data)?;
+        }
+
+        array.generate_mipmaps();
+
+        Ok(array)
+    }
+
+    /// Creates an RGBA texture array from images of the same size, one per layer.
+    ///
+    /// The format in `options` is ignored.
+    pub fn from_images(images: &[RgbaImage], options: TextureOptions) -> Result<Self, Error> {
+        let (width, height) = images.first().map_or((0, 0), |image| image.dimensions());
+
+        for image in images {
+            if image.dimensions() != (width, height) {
+                return Err(TextureError::ImageSizeMismatch(
+                    width,
+                    height,
+                    image.width(),
+                    image.height(),
+                )
+                .into());
+            }
+        }
+
+        let layers: Vec<&[u8]> = images.iter().map(|image| image.as_ref()).collect();
+        let options = TextureOptions {
+            format: TextureFormat::Rgba,
+            ..options
+        };
+
+        Self::from_layers(&layers, options, width, height)
+    }
+
+    /// Size of each layer in pixels.
+    pub fn size(&self) -> Vector2u {
+        self.size
+    }
+
+    /// Number of layers.
+    pub fn layers(&self) -> u32 {
+        self.layers
+    }
+
+    pub fn options(&self) -> &TextureOptions {
+        &self.options
+    }
+
+    /// Replaces the contents of a layer with raw pixel data.
+    pub fn update_layer(&mut self, layer: u32, data: &[u8]) -> Result<(), Error> {
+        self.write_layer(layer, data)?;
+        self.generate_mipmaps();
+
+        Ok(())
+    }
+
+    /// Uploads a layer, without regenerating mipmaps.
+    fn write_layer(&mut self, layer: u32, data: &[u8]) -> Result<(), TextureError> {
+        if layer >= self.layers {
+            return Err(TextureError::LayerOutOfRange(layer, self.layers));
+        }
+
+        let pixel_length = self.options.format.pixel_length();
+        if data.len() != (pixel_length * self.size.x * self.size.y) as usize {
+            return Err(TextureError::InvalidTextureData(
+                pixel_length,
+                self.size.x,
+                self.size.y,
+                data.len(),
+            ));
+        }
+
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D_ARRAY, self.id);
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+
+            gl::TexSubImage3D(
+                gl::TEXTURE_2D_ARRAY,
+                0,
+                0,
+                0,
+                layer as gl::types::GLint,
+                self.size.x as gl::types::GLsizei,
+                self.size.y as gl::types::GLsizei,
+                1,
+                self.options.format.external_format(),
+                self.options.format.pixel_type(),
+                data.as_ptr() as *const gl::types::GLvoid,
+            );
+
+            gl::BindTexture(gl::TEXTURE_2D_ARRAY, 0);
+        }
+
+        Ok(())
+    }
+
+    fn generate_mipmaps(&self) {
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D_ARRAY, self.id);
+            textures::generate_mipmaps(TextureTarget::Texture2DArray, &self.options);
+            gl::BindTexture(gl::TEXTURE_2D_ARRAY, 0);
+        }
+    }
+}
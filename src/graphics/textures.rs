@@ -1,9 +1,9 @@
 use assets::Asset;
 use failure::Error;
 use gl;
-use image;
+use image::{self, GenericImage};
 use maths::Vector2u;
-use std::cmp::Ordering;
+use std::{cmp::Ordering, fmt, ptr};
 
 /// ID of loaded OpenGL Texture
 pub type TextureID = gl::types::GLuint;
@@ -21,26 +21,172 @@ pub enum TextureError {
         _3
     )]
     InvalidTextureData(u32, u32, u32, usize),
+    /// Tried loading an image file into a format images cannot be decoded to.
+    #[fail(display = "Images cannot be loaded as {:?} textures", _0)]
+    UnsupportedImageFormat(TextureFormat),
+    /// Tried accessing pixels outside of the texture.
+    /// Contains the region (x, y, width, height) and the texture width and height.
+    #[fail(
+        display = "Region {}x{} at ({}, {}) is outside of {}x{} texture",
+        _2, _3, _0, _1, _4, _5
+    )]
+    RegionOutOfBounds(u32, u32, u32, u32, u32, u32),
+    /// Tried changing the format of an existing texture. Contains the old and new formats.
+    #[fail(display = "Cannot change texture format from {:?} to {:?}", _0, _1)]
+    FormatChanged(TextureFormat, TextureFormat),
+    /// Tried accessing a layer that does not exist. Contains the layer and layer count.
+    #[fail(display = "Layer {} is out of range of {} layers", _0, _1)]
+    LayerOutOfRange(u32, u32),
+    /// Images making up one texture have different sizes.
+    /// Contains the expected and actual width and height.
+    #[fail(display = "Expected {}x{} image, got {}x{}", _0, _1, _2, _3)]
+    ImageSizeMismatch(u32, u32, u32, u32),
 }
 
-/// Texture format.
+/// Texture format: channels, and how each channel is stored.
+///
+/// 16-bit and 32-bit formats expect data in native endianness.
+/// Half float (`F16`) formats expect 16-bit IEEE floats.
+/// Depth formats have no mipmaps: mipmapped filters fall back to `Nearest` or `Linear`.
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
 pub enum TextureFormat {
-    Rgb = gl::RGB as isize,
-    Rgba = gl::RGBA as isize,
+    R8,
+    Rg8,
+    Rgb,
+    Rgba,
+    /// RGB stored in sRGB color space, converted to linear when sampled.
+    Srgb,
+    /// RGBA with color stored in sRGB color space, converted to linear when sampled.
+    Srgba,
+    /// Single channel, sampled as a gray color.
+    Luminance,
+    /// Two channels, sampled as a gray color and alpha.
+    LuminanceAlpha,
+    R16,
+    Rg16,
+    Rgb16,
+    Rgba16,
+    R16F,
+    Rg16F,
+    Rgb16F,
+    Rgba16F,
+    R32F,
+    Rg32F,
+    Rgb32F,
+    Rgba32F,
+    Depth16,
+    /// 24-bit depth, stored in 32-bit integers.
+    Depth24,
+    Depth32F,
+    Depth24Stencil8,
 }
 
 impl TextureFormat {
     /// Length of one pixel in a texture of this format,
     /// in bytes.
     pub fn pixel_length(self) -> u32 {
+        self.gl_formats().3
+    }
+
+    /// OpenGL internal format, used to store the texture.
+    pub fn internal_format(self) -> gl::types::GLenum {
+        self.gl_formats().0
+    }
+
+    /// OpenGL format of the pixel data sent to or read from the texture.
+    pub fn external_format(self) -> gl::types::GLenum {
+        self.gl_formats().1
+    }
+
+    /// OpenGL type of each channel of the pixel data.
+    pub fn pixel_type(self) -> gl::types::GLenum {
+        self.gl_formats().2
+    }
+
+    /// Returns true for depth and depth-stencil formats.
+    pub fn is_depth(self) -> bool {
+        match self {
+            TextureFormat::Depth16
+            | TextureFormat::Depth24
+            | TextureFormat::Depth32F
+            | TextureFormat::Depth24Stencil8 => true,
+            _ => false,
+        }
+    }
+
+    /// Internal format, external format, pixel type and pixel length in bytes.
+    fn gl_formats(self) -> (gl::types::GLenum, gl::types::GLenum, gl::types::GLenum, u32) {
+        use self::TextureFormat::*;
+
+        match self {
+            R8 | Luminance => (gl::R8, gl::RED, gl::UNSIGNED_BYTE, 1),
+            Rg8 | LuminanceAlpha => (gl::RG8, gl::RG, gl::UNSIGNED_BYTE, 2),
+            Rgb => (gl::RGB8, gl::RGB, gl::UNSIGNED_BYTE, 3),
+            Rgba => (gl::RGBA8, gl::RGBA, gl::UNSIGNED_BYTE, 4),
+            Srgb => (gl::SRGB8, gl::RGB, gl::UNSIGNED_BYTE, 3),
+            Srgba => (gl::SRGB8_ALPHA8, gl::RGBA, gl::UNSIGNED_BYTE, 4),
+            R16 => (gl::R16, gl::RED, gl::UNSIGNED_SHORT, 2),
+            Rg16 => (gl::RG16, gl::RG, gl::UNSIGNED_SHORT, 4),
+            Rgb16 => (gl::RGB16, gl::RGB, gl::UNSIGNED_SHORT, 6),
+            Rgba16 => (gl::RGBA16, gl::RGBA, gl::UNSIGNED_SHORT, 8),
+            R16F => (gl::R16F, gl::RED, gl::HALF_FLOAT, 2),
+            Rg16F => (gl::RG16F, gl::RG, gl::HALF_FLOAT, 4),
+            Rgb16F => (gl::RGB16F, gl::RGB, gl::HALF_FLOAT, 6),
+            Rgba16F => (gl::RGBA16F, gl::RGBA, gl::HALF_FLOAT, 8),
+            R32F => (gl::R32F, gl::RED, gl::FLOAT, 4),
+            Rg32F => (gl::RG32F, gl::RG, gl::FLOAT, 8),
+            Rgb32F => (gl::RGB32F, gl::RGB, gl::FLOAT, 12),
+            Rgba32F => (gl::RGBA32F, gl::RGBA, gl::FLOAT, 16),
+            Depth16 => (
+                gl::DEPTH_COMPONENT16,
+                gl::DEPTH_COMPONENT,
+                gl::UNSIGNED_SHORT,
+                2,
+            ),
+            Depth24 => (
+                gl::DEPTH_COMPONENT24,
+                gl::DEPTH_COMPONENT,
+                gl::UNSIGNED_INT,
+                4,
+            ),
+            Depth32F => (gl::DEPTH_COMPONENT32F, gl::DEPTH_COMPONENT, gl::FLOAT, 4),
+            Depth24Stencil8 => (
+                gl::DEPTH24_STENCIL8,
+                gl::DEPTH_STENCIL,
+                gl::UNSIGNED_INT_24_8,
+                4,
+            ),
+        }
+    }
+
+    /// Channels sampled from each texture channel, for formats that need swizzling.
+    pub(crate) fn swizzle(self) -> Option<[gl::types::GLint; 4]> {
+        let red = gl::RED as gl::types::GLint;
+
         match self {
-            TextureFormat::Rgb => 3,
-            TextureFormat::Rgba => 4,
+            TextureFormat::Luminance => Some([red, red, red, gl::ONE as gl::types::GLint]),
+            TextureFormat::LuminanceAlpha => Some([red, red, red, gl::GREEN as gl::types::GLint]),
+            _ => None,
         }
     }
 }
 
+/// Kind of OpenGL texture, which it is bound to.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum TextureTarget {
+    Texture2D = gl::TEXTURE_2D as isize,
+    Texture2DArray = gl::TEXTURE_2D_ARRAY as isize,
+    Cubemap = gl::TEXTURE_CUBE_MAP as isize,
+}
+
+/// Any loaded OpenGL texture that can be bound to draw with.
+pub trait BindableTexture: fmt::Debug {
+    /// ID of the loaded texture in OpenGL.
+    fn id(&self) -> TextureID;
+    /// Kind of texture.
+    fn target(&self) -> TextureTarget;
+}
+
 /// Texture wrap mode.
 ///
 /// Default: `Repeat`
@@ -66,6 +212,28 @@ pub enum MinFilterMode {
     LinearMipmapLinear = gl::LINEAR_MIPMAP_LINEAR as isize,
 }
 
+impl MinFilterMode {
+    /// Returns true if this mode samples from mipmaps.
+    pub fn uses_mipmaps(self) -> bool {
+        match self {
+            MinFilterMode::Nearest | MinFilterMode::Linear => false,
+            _ => true,
+        }
+    }
+
+    /// Equivalent mode sampling from the base level only.
+    pub fn without_mipmaps(self) -> Self {
+        match self {
+            MinFilterMode::Nearest
+            | MinFilterMode::NearestMipmapNearest
+            | MinFilterMode::NearestMipmapLinear => MinFilterMode::Nearest,
+            MinFilterMode::Linear
+            | MinFilterMode::LinearMipmapNearest
+            | MinFilterMode::LinearMipmapLinear => MinFilterMode::Linear,
+        }
+    }
+}
+
 /// Texture magnification filtering mode.
 ///
 /// Default: `Nearest`
@@ -111,13 +279,53 @@ pub struct Texture {
     options: TextureOptions,
 }
 
+/// Picks the format an image is loaded as.
+///
+/// Grayscale images requested as `Rgb` or `Rgba` are loaded as `Luminance`,
+/// or `LuminanceAlpha` if they have alpha and `Rgba` was requested.
+/// These are sampled the same as the requested format but use less memory.
+fn image_format(requested: TextureFormat, color: image::ColorType) -> TextureFormat {
+    match (requested, color) {
+        (TextureFormat::Rgb, image::ColorType::Gray(_))
+        | (TextureFormat::Rgb, image::ColorType::GrayA(_))
+        | (TextureFormat::Rgba, image::ColorType::Gray(_)) => TextureFormat::Luminance,
+        (TextureFormat::Rgba, image::ColorType::GrayA(_)) => TextureFormat::LuminanceAlpha,
+        _ => requested,
+    }
+}
+
+/// Loads an image file (PNG, JPEG...) as a texture.
+///
+/// The image is converted to the format in `options`, except grayscale images
+/// requested as `Rgb` or `Rgba`, which are loaded as `Luminance` or `LuminanceAlpha`.
+/// Check the texture's `options` for the format that was used.
 impl Asset<TextureOptions> for Texture {
-    fn load_from_bytes(data: &[u8], options: TextureOptions) -> Result<Self, Error> {
+    fn load_from_bytes(data: &[u8], mut options: TextureOptions) -> Result<Self, Error> {
         //Load image from bytes
-        let img = image::load_from_memory(data)?.to_rgba();
+        let img = image::load_from_memory(data)?;
         let (width, height) = img.dimensions();
+        let format = image_format(options.format, img.color());
+        options.format = format;
+
+        let pixels = match format {
+            TextureFormat::R8 | TextureFormat::Luminance => img.to_luma().into_raw(),
+            TextureFormat::Rg8 | TextureFormat::LuminanceAlpha => img.to_luma_alpha().into_raw(),
+            TextureFormat::Rgb | TextureFormat::Srgb => img.to_rgb().into_raw(),
+            TextureFormat::Rgba | TextureFormat::Srgba => img.to_rgba().into_raw(),
+            _ => return Err(TextureError::UnsupportedImageFormat(format).into()),
+        };
+
+        Self::from_bytes(&pixels, options, width, height)
+    }
+}
+
+impl BindableTexture for Texture {
+    fn id(&self) -> TextureID {
+        self.id
+    }
 
-        Self::from_bytes(img.as_ref(), options, width, height)
+    fn target(&self) -> TextureTarget {
+        TextureTarget::Texture2D
     }
 }
 
@@ -190,64 +398,332 @@ impl Texture {
             .into());
         }
 
-        //Allocate texture
-        let mut id = 0;
+        Ok(Self::allocate(
+            data.as_ptr() as *const gl::types::GLvoid,
+            options,
+            width,
+            height,
+        ))
+    }
 
-        unsafe {
-            //Create texture
-            gl::GenTextures(1, &mut id);
+    /// Create a texture with uninitialized contents.
+    ///
+    /// Useful as a render target, or for textures that will be filled later.
+    pub fn empty(options: TextureOptions, width: u32, height: u32) -> Self {
+        Self::allocate(ptr::null(), options, width, height)
+    }
 
-            //Bind texture
-            gl::BindTexture(gl::TEXTURE_2D, id);
+    /// Writes pixel data to a rectangle of the texture, in pixels from the top left corner.
+    ///
+    /// `data` must be in the texture's format.
+    pub fn update_region(
+        &mut self,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+        data: &[u8],
+    ) -> Result<(), Error> {
+        self.check_region(x, y, width, height)?;
+        self.check_data(width, height, data)?;
+
+        let format = self.options.format;
+
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D, self.id);
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
 
-            //Fill texture
-            gl::TexImage2D(
+            gl::TexSubImage2D(
                 gl::TEXTURE_2D,
                 0,
-                gl::RGBA as gl::types::GLint,
-                width as gl::types::GLint,
-                height as gl::types::GLint,
-                0,
-                options.format as gl::types::GLenum,
-                gl::UNSIGNED_BYTE,
+                x as gl::types::GLint,
+                y as gl::types::GLint,
+                width as gl::types::GLsizei,
+                height as gl::types::GLsizei,
+                format.external_format(),
+                format.pixel_type(),
                 data.as_ptr() as *const gl::types::GLvoid,
             );
 
-            //Texture wrapping
-            gl::TexParameteri(
-                gl::TEXTURE_2D,
-                gl::TEXTURE_WRAP_S,
-                options.h_wrap_mode as gl::types::GLint,
-            );
-            gl::TexParameteri(
-                gl::TEXTURE_2D,
-                gl::TEXTURE_WRAP_T,
-                options.v_wrap_mode as gl::types::GLint,
-            );
+            generate_mipmaps(TextureTarget::Texture2D, &self.options);
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+        }
 
-            //Texture filtering
-            gl::TexParameteri(
-                gl::TEXTURE_2D,
-                gl::TEXTURE_MIN_FILTER,
-                options.min_filter_mode as gl::types::GLint,
-            );
-            gl::TexParameteri(
+        Ok(())
+    }
+
+    /// Changes the size of the texture.
+    ///
+    /// The part of the contents that fits in the new size is kept,
+    /// new pixels are zeroed.
+    pub fn resize(&mut self, width: u32, height: u32) -> Result<(), Error> {
+        if self.size == Vector2u::new(width, height) {
+            return Ok(());
+        }
+
+        let pixel_length = self.options.format.pixel_length() as usize;
+        let old_pixels = self.read_pixels()?;
+        let mut pixels = vec![0u8; pixel_length * (width * height) as usize];
+
+        //Copy the overlapping part row by row
+        let row_length = pixel_length * width.min(self.width()) as usize;
+        for row in 0..height.min(self.height()) as usize {
+            let old_start = row * pixel_length * self.width() as usize;
+            let new_start = row * pixel_length * width as usize;
+
+            pixels[new_start..new_start + row_length]
+                .copy_from_slice(&old_pixels[old_start..old_start + row_length]);
+        }
+
+        self.size = Vector2u::new(width, height);
+
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D, self.id);
+            self.upload(pixels.as_ptr() as *const gl::types::GLvoid);
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+        }
+
+        Ok(())
+    }
+
+    /// Changes the wrap and filter modes of the texture.
+    ///
+    /// The format cannot be changed, create a new texture instead.
+    pub fn set_options(&mut self, options: TextureOptions) -> Result<(), Error> {
+        if options.format != self.options.format {
+            return Err(TextureError::FormatChanged(self.options.format, options.format).into());
+        }
+
+        let had_mipmaps = self.options.min_filter_mode.uses_mipmaps();
+        self.options = options;
+
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D, self.id);
+            apply_options(TextureTarget::Texture2D, &self.options);
+
+            if !had_mipmaps {
+                generate_mipmaps(TextureTarget::Texture2D, &self.options);
+            }
+
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+        }
+
+        Ok(())
+    }
+
+    /// Reads the contents of the texture to CPU memory, in the texture's format.
+    ///
+    /// The first row is the top of the texture.
+    pub fn read_pixels(&self) -> Result<Vec<u8>, Error> {
+        let length = (self.options.format.pixel_length() * self.width() * self.height()) as usize;
+        let mut data = vec![0u8; length];
+
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D, self.id);
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+
+            gl::GetTexImage(
                 gl::TEXTURE_2D,
-                gl::TEXTURE_MAG_FILTER,
-                options.max_filter_mode as gl::types::GLint,
+                0,
+                self.options.format.external_format(),
+                self.options.format.pixel_type(),
+                data.as_mut_ptr() as *mut gl::types::GLvoid,
             );
 
-            //Generate mipmaps
-            gl::GenerateMipmap(gl::TEXTURE_2D);
-
-            //Unbind texture
             gl::BindTexture(gl::TEXTURE_2D, 0);
         }
 
-        Ok(Self {
+        Ok(data)
+    }
+
+    /// Checks that a rectangle is inside the texture.
+    fn check_region(&self, x: u32, y: u32, width: u32, height: u32) -> Result<(), TextureError> {
+        //Compare in u64 to avoid overflows
+        if x as u64 + width as u64 > self.width() as u64
+            || y as u64 + height as u64 > self.height() as u64
+        {
+            return Err(TextureError::RegionOutOfBounds(
+                x,
+                y,
+                width,
+                height,
+                self.width(),
+                self.height(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Checks that `data` is the right length for `width` x `height` pixels.
+    fn check_data(&self, width: u32, height: u32, data: &[u8]) -> Result<(), TextureError> {
+        let pixel_length = self.options.format.pixel_length();
+
+        if data.len() != (pixel_length * width * height) as usize {
+            return Err(TextureError::InvalidTextureData(
+                pixel_length,
+                width,
+                height,
+                data.len(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Creates the OpenGL texture and fills it with `data`, which may be null.
+    fn allocate(
+        data: *const gl::types::GLvoid,
+        options: TextureOptions,
+        width: u32,
+        height: u32,
+    ) -> Self {
+        //Allocate texture
+        let mut id = 0;
+
+        unsafe {
+            //Create texture
+            gl::GenTextures(1, &mut id);
+        }
+
+        let texture = Self {
             id,
             size: Vector2u::new(width, height),
             options,
-        })
+        };
+
+        unsafe {
+            //Bind texture
+            gl::BindTexture(gl::TEXTURE_2D, id);
+
+            texture.upload(data);
+            apply_options(TextureTarget::Texture2D, &texture.options);
+
+            //Unbind texture
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+        }
+
+        texture
+    }
+
+    /// Allocates the storage of the bound texture and fills it with `data`, which may be null.
+    unsafe fn upload(&self, data: *const gl::types::GLvoid) {
+        let format = self.options.format;
+
+        //Rows of 1 or 3 byte pixels are not always 4-byte aligned
+        gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+
+        //Fill texture
+        gl::TexImage2D(
+            gl::TEXTURE_2D,
+            0,
+            format.internal_format() as gl::types::GLint,
+            self.width() as gl::types::GLint,
+            self.height() as gl::types::GLint,
+            0,
+            format.external_format(),
+            format.pixel_type(),
+            data,
+        );
+
+        generate_mipmaps(TextureTarget::Texture2D, &self.options);
+    }
+}
+
+/// Sets the wrap and filter modes of the texture bound to `target`.
+pub(crate) unsafe fn apply_options(target: TextureTarget, options: &TextureOptions) {
+    let target = target as gl::types::GLenum;
+
+    //Texture wrapping
+    gl::TexParameteri(
+        target,
+        gl::TEXTURE_WRAP_S,
+        options.h_wrap_mode as gl::types::GLint,
+    );
+    gl::TexParameteri(
+        target,
+        gl::TEXTURE_WRAP_T,
+        options.v_wrap_mode as gl::types::GLint,
+    );
+
+    //Texture filtering, without mipmaps for depth textures which have none
+    let min_filter_mode = if options.format.is_depth() {
+        options.min_filter_mode.without_mipmaps()
+    } else {
+        options.min_filter_mode
+    };
+    gl::TexParameteri(
+        target,
+        gl::TEXTURE_MIN_FILTER,
+        min_filter_mode as gl::types::GLint,
+    );
+    gl::TexParameteri(
+        target,
+        gl::TEXTURE_MAG_FILTER,
+        options.max_filter_mode as gl::types::GLint,
+    );
+
+    //Sample single channel formats as gray
+    if let Some(swizzle) = options.format.swizzle() {
+        gl::TexParameteriv(target, gl::TEXTURE_SWIZZLE_RGBA, swizzle.as_ptr());
+    }
+}
+
+/// Regenerates the mipmaps of the texture bound to `target`, if they are used.
+pub(crate) unsafe fn generate_mipmaps(target: TextureTarget, options: &TextureOptions) {
+    if options.min_filter_mode.uses_mipmaps() && !options.format.is_depth() {
+        gl::GenerateMipmap(target as gl::types::GLenum);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::ColorType;
+
+    #[test]
+    fn grayscale_images_keep_their_channels() {
+        for &requested in &[TextureFormat::Rgb, TextureFormat::Rgba] {
+            assert_eq!(
+                image_format(requested, ColorType::Gray(8)),
+                TextureFormat::Luminance
+            );
+        }
+        assert_eq!(
+            image_format(TextureFormat::Rgba, ColorType::GrayA(8)),
+            TextureFormat::LuminanceAlpha
+        );
+    }
+
+    #[test]
+    fn alpha_is_dropped_when_not_requested() {
+        assert_eq!(
+            image_format(TextureFormat::Rgb, ColorType::GrayA(8)),
+            TextureFormat::Luminance
+        );
+    }
+
+    #[test]
+    fn color_images_use_requested_format() {
+        assert_eq!(
+            image_format(TextureFormat::Rgba, ColorType::RGB(8)),
+            TextureFormat::Rgba
+        );
+        assert_eq!(
+            image_format(TextureFormat::Rgb, ColorType::RGBA(8)),
+            TextureFormat::Rgb
+        );
+        assert_eq!(
+            image_format(TextureFormat::Rgba, ColorType::Palette(8)),
+            TextureFormat::Rgba
+        );
+    }
+
+    #[test]
+    fn other_formats_are_not_replaced() {
+        for &requested in &[TextureFormat::Srgba, TextureFormat::R8, TextureFormat::Rg8] {
+            assert_eq!(image_format(requested, ColorType::Gray(8)), requested);
+            assert_eq!(image_format(requested, ColorType::GrayA(8)), requested);
+        }
     }
 }
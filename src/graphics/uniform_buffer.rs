@@ -0,0 +1,319 @@
+use gl;
+use maths::{Matrix4f, Vector2f, Vector3f, Vector4f};
+use std::{marker::PhantomData, mem};
+
+/// Name of the uniform block holding `FrameUniforms`.
+pub const FRAME_BLOCK_NAME: &str = "Frame";
+
+/// Binding point of the `Frame` uniform block.
+///
+/// Programs are linked to it automatically. Other blocks can use any other binding point.
+pub const FRAME_BLOCK_BINDING: u32 = 0;
+
+/// Data of a uniform block, written with the `std140` layout.
+///
+/// Fields must be written in the order they are declared in the GLSL block.
+/// Blocks must be declared with `layout (std140)`.
+pub trait UniformBlock {
+    fn write(&self, writer: &mut Std140Writer);
+}
+
+/// Writes values with the alignment rules of the `std140` layout.
+#[derive(Debug, Clone, Default)]
+pub struct Std140Writer {
+    /// Data as 4-byte words, the size of every scalar.
+    words: Vec<u32>,
+}
+
+impl Std140Writer {
+    pub fn new() -> Self {
+        Self { words: Vec::new() }
+    }
+
+    pub fn float(&mut self, value: f32) {
+        self.words.push(value.to_bits());
+    }
+
+    pub fn int(&mut self, value: i32) {
+        self.words.push(value as u32);
+    }
+
+    pub fn uint(&mut self, value: u32) {
+        self.words.push(value);
+    }
+
+    pub fn bool(&mut self, value: bool) {
+        self.words.push(value as u32);
+    }
+
+    pub fn vec2(&mut self, value: Vector2f) {
+        self.align(2);
+        self.float(value.x);
+        self.float(value.y);
+    }
+
+    /// Writes a vec3. A scalar can follow in the same 16 bytes.
+    pub fn vec3(&mut self, value: Vector3f) {
+        self.align(4);
+        self.float(value.x);
+        self.float(value.y);
+        self.float(value.z);
+    }
+
+    pub fn vec4(&mut self, value: Vector4f) {
+        self.align(4);
+        self.float(value.x);
+        self.float(value.y);
+        self.float(value.z);
+        self.float(value.w);
+    }
+
+    pub fn mat4(&mut self, value: Matrix4f) {
+        for column in &[value.x, value.y, value.z, value.w] {
+            self.vec4(*column);
+        }
+    }
+
+    /// Writes a float array. Each element takes 16 bytes.
+    pub fn float_array(&mut self, values: &[f32]) {
+        for &value in values {
+            self.align(4);
+            self.float(value);
+        }
+        self.align(4);
+    }
+
+    pub fn vec4_array(&mut self, values: &[Vector4f]) {
+        for &value in values {
+            self.vec4(value);
+        }
+    }
+
+    pub fn mat4_array(&mut self, values: &[Matrix4f]) {
+        for &value in values {
+            self.mat4(value);
+        }
+    }
+
+    /// Pads the data to a multiple of `words` 4-byte words.
+    fn align(&mut self, words: usize) {
+        while self.words.len() % words != 0 {
+            self.words.push(0);
+        }
+    }
+
+    /// Data padded to the size of a vec4, as blocks are.
+    fn finish(mut self) -> Vec<u32> {
+        self.align(4);
+        self.words
+    }
+}
+
+/// Uniforms shared by every program, updated before each camera renders.
+///
+/// Shaders access them by declaring:
+///
+/// ```glsl
+/// layout (std140) uniform Frame {
+///     mat4 View;
+///     mat4 Projection;
+///     vec2 ScreenSize;
+///     float Time;
+/// };
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct FrameUniforms {
+    /// View matrix of the camera.
+    pub view: Matrix4f,
+    /// Projection matrix of the camera.
+    pub projection: Matrix4f,
+    /// Size of the camera's viewport in pixels.
+    pub screen_size: Vector2f,
+    /// Time since the `GraphicsManager` was created, in seconds.
+    pub time: f32,
+}
+
+impl UniformBlock for FrameUniforms {
+    fn write(&self, writer: &mut Std140Writer) {
+        writer.mat4(self.view);
+        writer.mat4(self.projection);
+        writer.vec2(self.screen_size);
+        writer.float(self.time);
+    }
+}
+
+/// OpenGL buffer holding the data of a uniform block.
+///
+/// Bind it to a binding point with `bind`, and link the block of a program
+/// to the same binding point with `Program::bind_uniform_block`.
+///
+/// This owns the buffer, meaning the OpenGL buffer is deleted when
+/// `UniformBuffer` goes out of scope.
+#[derive(Debug)]
+pub struct UniformBuffer<T> {
+    id: gl::types::GLuint,
+    /// Size of the buffer in bytes.
+    size: usize,
+    block: PhantomData<T>,
+}
+
+impl<T> Drop for UniformBuffer<T> {
+    fn drop(&mut self) {
+        unsafe { gl::DeleteBuffers(1, &self.id) }
+    }
+}
+
+impl<T: UniformBlock> UniformBuffer<T> {
+    /// Creates a buffer holding `block`.
+    pub fn new(block: &T) -> Self {
+        let mut id = 0;
+        unsafe { gl::GenBuffers(1, &mut id) }
+
+        let mut buffer = Self {
+            id,
+            size: 0,
+            block: PhantomData,
+        };
+        buffer.update(block);
+
+        buffer
+    }
+
+    /// Replaces the contents of the buffer.
+    pub fn update(&mut self, block: &T) {
+        let mut writer = Std140Writer::new();
+        block.write(&mut writer);
+        let words = writer.finish();
+        let size = words.len() * mem::size_of::<u32>();
+
+        unsafe {
+            gl::BindBuffer(gl::UNIFORM_BUFFER, self.id);
+
+            if size == self.size {
+                gl::BufferSubData(
+                    gl::UNIFORM_BUFFER,
+                    0,
+                    size as gl::types::GLsizeiptr,
+                    words.as_ptr() as *const gl::types::GLvoid,
+                );
+            } else {
+                gl::BufferData(
+                    gl::UNIFORM_BUFFER,
+                    size as gl::types::GLsizeiptr,
+                    words.as_ptr() as *const gl::types::GLvoid,
+                    gl::DYNAMIC_DRAW,
+                );
+                self.size = size;
+            }
+
+            gl::BindBuffer(gl::UNIFORM_BUFFER, 0);
+        }
+    }
+
+    /// Binds the buffer to a uniform block binding point.
+    pub fn bind(&self, binding: u32) {
+        unsafe { gl::BindBufferBase(gl::UNIFORM_BUFFER, binding, self.id) }
+    }
+
+    pub fn id(&self) -> gl::types::GLuint {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bits(values: &[f32]) -> Vec<u32> {
+        values.iter().map(|value| value.to_bits()).collect()
+    }
+
+    #[test]
+    fn scalars_are_packed() {
+        let mut writer = Std140Writer::new();
+        writer.float(1.0);
+        writer.int(-1);
+        writer.uint(7);
+        writer.bool(true);
+
+        assert_eq!(writer.words, vec![1f32.to_bits(), u32::max_value(), 7, 1]);
+    }
+
+    #[test]
+    fn vec2_is_aligned_to_8_bytes() {
+        let mut writer = Std140Writer::new();
+        writer.float(1.0);
+        writer.vec2(Vector2f::new(2.0, 3.0));
+
+        assert_eq!(writer.words, bits(&[1.0, 0.0, 2.0, 3.0]));
+    }
+
+    #[test]
+    fn vec3_is_aligned_to_16_bytes_and_shares_them_with_a_scalar() {
+        let mut writer = Std140Writer::new();
+        writer.float(1.0);
+        writer.vec3(Vector3f::new(2.0, 3.0, 4.0));
+        writer.float(5.0);
+
+        assert_eq!(
+            writer.words,
+            bits(&[1.0, 0.0, 0.0, 0.0, 2.0, 3.0, 4.0, 5.0])
+        );
+    }
+
+    #[test]
+    fn vec4_is_aligned_to_16_bytes() {
+        let mut writer = Std140Writer::new();
+        writer.vec2(Vector2f::new(1.0, 2.0));
+        writer.vec4(Vector4f::new(3.0, 4.0, 5.0, 6.0));
+
+        assert_eq!(
+            writer.words,
+            bits(&[1.0, 2.0, 0.0, 0.0, 3.0, 4.0, 5.0, 6.0])
+        );
+    }
+
+    #[test]
+    fn mat4_is_written_by_columns() {
+        let matrix = Matrix4f::new(
+            1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0, 10.0, 11.0, 12.0, 13.0, 14.0, 15.0, 16.0,
+        );
+
+        let mut writer = Std140Writer::new();
+        writer.float(0.0);
+        writer.mat4(matrix);
+
+        let mut expected = vec![0.0, 0.0, 0.0, 0.0];
+        expected.extend((1..17).map(|i| i as f32));
+        assert_eq!(writer.words, bits(&expected));
+    }
+
+    #[test]
+    fn array_elements_take_16_bytes() {
+        let mut writer = Std140Writer::new();
+        writer.float_array(&[1.0, 2.0]);
+        writer.float(3.0);
+
+        assert_eq!(
+            writer.words,
+            bits(&[1.0, 0.0, 0.0, 0.0, 2.0, 0.0, 0.0, 0.0, 3.0])
+        );
+    }
+
+    #[test]
+    fn blocks_are_padded_to_16_bytes() {
+        let mut writer = Std140Writer::new();
+        FrameUniforms {
+            view: Matrix4f::from_scale(1.0),
+            projection: Matrix4f::from_scale(2.0),
+            screen_size: Vector2f::new(800.0, 600.0),
+            time: 1.5,
+        }
+        .write(&mut writer);
+
+        let words = writer.finish();
+        assert_eq!(words.len(), 36);
+        assert_eq!(&words[32..], &bits(&[800.0, 600.0, 1.5, 0.0])[..]);
+        assert_eq!(words[16], 2f32.to_bits());
+    }
+}
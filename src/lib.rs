@@ -2,13 +2,19 @@
 extern crate cgmath;
 #[macro_use]
 extern crate failure;
+extern crate gif;
 extern crate gl;
 extern crate image;
 extern crate rayon;
 #[macro_use]
 extern crate lazy_static;
+extern crate ron;
 extern crate rusttype;
 extern crate sdl2;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
 extern crate unicode_normalization;
 
 pub mod assets;